@@ -1,30 +1,16 @@
-use actix_web::{App, HttpMessage, HttpResponse, HttpServer, get};
+use actix_web::{App, HttpResponse, HttpServer, get};
 use cookiebox::cookiebox_macros::{FromRequest, cookie};
-use cookiebox::cookies::{Cookie, CookieName, IncomingConfig, OutgoingConfig};
+use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, IncomingConfig, OutgoingConfig};
 use cookiebox::{Attributes, SameSite};
-use cookiebox::{
-    CookieMiddleware, Key, Processor, ProcessorConfig,
-    config::{CryptoAlgorithm, CryptoRule},
-};
+use cookiebox::{CookieMiddleware, Key, encrypted_processor};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Set up the processor for the middleware
-    let mut cookie_config = ProcessorConfig::default();
-
-    // Set up the rules encrypted cookies
-    let crypto_rule = CryptoRule {
-        cookie_names: vec!["__cookie-b".to_string()],
-        algorithm: CryptoAlgorithm::Encryption,
-        key: Key::generate(),
-        fallbacks: vec![],
-    };
-
-    cookie_config.crypto_rules.push(crypto_rule);
-
-    let processor: Processor = cookie_config.into();
+    // Set up the processor for the middleware, which encrypts `__cookie-b`
+    // For several rules or fallback keys, build a `ProcessorConfig` with `CryptoRule` instead
+    let processor = encrypted_processor(["__cookie-b"], Key::generate());
 
     HttpServer::new(move || {
         App::new()
@@ -79,10 +65,10 @@ impl OutgoingConfig for CookieB {
     type Insert = (String, i32);
 
     // Customize serialization method
-    fn serialize(values: Self::Insert) -> serde_json::Value {
-        json!({
+    fn serialize(values: Self::Insert) -> Result<serde_json::Value, CookieBoxError> {
+        Ok(json!({
             "data": format!("Name: {} - Age: {}", values.0, values.1)
-        })
+        }))
     }
     // Configure attributes for cookie
     fn attributes<'c>() -> Attributes<'c> {
@@ -97,12 +83,15 @@ pub struct CookieCollection<'c> {
 }
 
 #[get("add_cookie_b")]
-async fn add_cookie_b(cookies_collection: CookieCollection<'_>) -> HttpResponse {
+async fn add_cookie_b(
+    cookies_collection: CookieCollection<'_>,
+) -> Result<HttpResponse, CookieBoxError> {
+    // This returns Err(CookieBoxError) if the value fails to serialize, which responds with 500 Internal Server Error
     cookies_collection
         .cookie_b
-        .insert(("Scarlet".to_string(), 27));
+        .insert(("Scarlet".to_string(), 27))?;
 
-    HttpResponse::Ok().body("Encrypted cookie added")
+    Ok(HttpResponse::Ok().body("Encrypted cookie added"))
 }
 #[get("get_cookie_b")]
 async fn get_cookie_b(cookies_collection: CookieCollection<'_>) -> HttpResponse {
@@ -116,7 +105,9 @@ async fn get_cookie_b(cookies_collection: CookieCollection<'_>) -> HttpResponse
 }
 
 #[get("update_cookie_b")]
-async fn update_cookie_b(cookies_collection: CookieCollection<'_>) -> HttpResponse {
+async fn update_cookie_b(
+    cookies_collection: CookieCollection<'_>,
+) -> Result<HttpResponse, CookieBoxError> {
     // This returns a Ok(CookieData) if found, otherwise Err(CookieBoxError)
     let old_data = cookies_collection
         .cookie_b
@@ -126,12 +117,12 @@ async fn update_cookie_b(cookies_collection: CookieCollection<'_>) -> HttpRespon
     // Since the path, domain, and name are the same, this would replace the current data with the below
     cookies_collection
         .cookie_b
-        .insert(("Jason".to_string(), 22));
+        .insert(("Jason".to_string(), 22))?;
 
-    HttpResponse::Ok().body(format!(
+    Ok(HttpResponse::Ok().body(format!(
         "old data: {:?} - Go to get_cookie_b to check the new value",
         old_data
-    ))
+    )))
 }
 
 #[get("remove_cookie_b")]
@@ -143,10 +134,12 @@ async fn remove_cookie_b(cookies_collection: CookieCollection<'_>) -> HttpRespon
 
 //Add a new cookie in the browser with the value `%22STRING%22` and set the attributes to default values to get
 #[get("add_cookie_a")]
-async fn add_cookie_a(cookies_collection: CookieCollection<'_>) -> HttpResponse {
-    cookies_collection.cookie_a.insert("Cookie A".to_string());
+async fn add_cookie_a(
+    cookies_collection: CookieCollection<'_>,
+) -> Result<HttpResponse, CookieBoxError> {
+    cookies_collection.cookie_a.insert("Cookie A".to_string())?;
 
-    HttpResponse::Ok().body("__cookie-a added")
+    Ok(HttpResponse::Ok().body("__cookie-a added"))
 }
 // Add a new cookie in the browser with the value `%22STRING%22` and set the attributes to default values to get
 #[get("get_cookie_a")]
@@ -161,7 +154,9 @@ async fn get_cookie_a(cookies_collection: CookieCollection<'_>) -> HttpResponse
 }
 
 #[get("update_cookie_a")]
-async fn update_cookie_a(cookies_collection: CookieCollection<'_>) -> HttpResponse {
+async fn update_cookie_a(
+    cookies_collection: CookieCollection<'_>,
+) -> Result<HttpResponse, CookieBoxError> {
     // This returns a Ok(CookieData) if found, otherwise Err(CookieBoxError)
     let old_data = cookies_collection
         .cookie_a
@@ -171,12 +166,12 @@ async fn update_cookie_a(cookies_collection: CookieCollection<'_>) -> HttpRespon
     // Since the path, domain, and name are the same, this would replace the current data with the below
     cookies_collection
         .cookie_a
-        .insert("New cookie A value".to_string());
+        .insert("New cookie A value".to_string())?;
 
-    HttpResponse::Ok().body(format!(
+    Ok(HttpResponse::Ok().body(format!(
         "old data: {:?} - Go to get_cookie_a to check the new value",
         old_data
-    ))
+    )))
 }
 
 #[get("remove_cookie_a")]
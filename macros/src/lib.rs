@@ -3,55 +3,260 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{
-    parse_macro_input, DeriveInput, Expr, Fields, ItemStruct, Lit, Meta, PathArguments, Type,
+    parse_macro_input, punctuated::Punctuated, DeriveInput, Expr, Fields, ItemStruct, Lit, LitStr,
+    Meta, PathArguments, Token, Type,
 };
 
 /// Implements a CookieName trait using passed in name from the macro attribute
+///
+/// Accepts a comma-separated list of arguments:
+/// - `name = "..."` sets the cookie name. When omitted, the name is the kebab-case form of the struct identifier,
+///   e.g. `#[cookie] struct SessionId;` is named `session-id`
+/// - `signed` additionally implements `RequiresSigning` for the struct
+/// - `encrypted` additionally implements `RequiresEncryption` for the struct
+/// - `allow_invalid` skips the check that the name is a valid RFC 6265 token
+/// - `require_prefix = "..."` fails the compilation unless the name starts with the given prefix
+/// - `same_site = "..."` sets the `SameSite` attribute of the default `OutgoingConfig::attributes`, one of `strict`, `lax`, or `none`
+/// - `max_age = ...` sets the `Max-Age` attribute of the default `OutgoingConfig::attributes` in seconds, as an integer
+///   or a string literal
+///
+/// `same_site` and `max_age` are generated into a hidden `CookieName` method that `OutgoingConfig::attributes` defaults
+/// to, so a hand-written `attributes` replaces them rather than conflicting with them.
+///
+/// e.g. `#[cookie(name = "__session", signed)]` or `#[cookie(name = "theme", same_site = "strict", max_age = 3600)]`
 #[proc_macro_attribute]
 pub fn cookie(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as ItemStruct);
 
-    let parsed_attr = parse_macro_input!(attr as Meta);
+    let parsed_attrs =
+        parse_macro_input!(attr with Punctuated::<Meta, Token![,]>::parse_terminated);
 
-    let mut cookie_name = String::new();
+    let mut cookie_name = None;
+    let mut signed = false;
+    let mut encrypted = false;
+    let mut allow_invalid = false;
+    let mut name_literal = None;
+    let mut required_prefix = None;
+    let mut same_site = None;
+    let mut max_age = None;
+
+    for parsed_attr in &parsed_attrs {
+        match parsed_attr {
+            Meta::NameValue(nv) if nv.path.is_ident("name") => match string_literal(&nv.value) {
+                Ok(lit_str) => {
+                    cookie_name = Some(lit_str.value());
+                    name_literal = Some(lit_str);
+                }
+                Err(e) => return e.into_compile_error().into(),
+            },
+            Meta::NameValue(nv) if nv.path.is_ident("require_prefix") => {
+                match string_literal(&nv.value) {
+                    Ok(lit_str) => required_prefix = Some(lit_str.value()),
+                    Err(e) => return e.into_compile_error().into(),
+                }
+            }
+            Meta::NameValue(nv) if nv.path.is_ident("same_site") => {
+                match string_literal(&nv.value).and_then(|lit_str| same_site_variant(&lit_str)) {
+                    Ok(variant) => same_site = Some(variant),
+                    Err(e) => return e.into_compile_error().into(),
+                }
+            }
+            Meta::NameValue(nv) if nv.path.is_ident("max_age") => match max_age_secs(&nv.value) {
+                Ok(secs) => max_age = Some(secs),
+                Err(e) => return e.into_compile_error().into(),
+            },
+            Meta::Path(path) if path.is_ident("signed") => signed = true,
+            Meta::Path(path) if path.is_ident("encrypted") => encrypted = true,
+            Meta::Path(path) if path.is_ident("allow_invalid") => allow_invalid = true,
+            meta => {
+                return syn::Error::new_spanned(
+                    meta,
+                    "Unexpected parameter: expected `name = \"...\"`, `signed`, `encrypted`, `allow_invalid`, `require_prefix = \"...\"`, `same_site = \"...\"`, or `max_age = ...`",
+                )
+                .into_compile_error()
+                .into();
+            }
+        }
+    }
+
+    let cookie_name = match cookie_name {
+        Some(cookie_name) => cookie_name,
+        None => {
+            let cookie_name = to_kebab_case(&input.ident.to_string());
+            name_literal = Some(LitStr::new(&cookie_name, input.ident.span()));
+            cookie_name
+        }
+    };
+
+    if !allow_invalid {
+        if let Err(message) = validate_cookie_name(&cookie_name) {
+            return syn::Error::new_spanned(name_literal, message)
+                .into_compile_error()
+                .into();
+        }
+    }
+
+    if let Some(prefix) = required_prefix {
+        if !cookie_name.starts_with(&prefix) {
+            return syn::Error::new_spanned(
+                name_literal,
+                format!("`{cookie_name}` does not start with the required prefix `{prefix}`"),
+            )
+            .into_compile_error()
+            .into();
+        }
+    }
 
-    if !parsed_attr.path().is_ident("name") {
+    if signed && encrypted {
         return syn::Error::new_spanned(
-            parsed_attr.path().get_ident(),
-            "Expected `name` parameter: #[cookie(name = \"...\")]",
+            &parsed_attrs,
+            "A cookie cannot be both `signed` and `encrypted`",
         )
         .into_compile_error()
         .into();
     }
-    if let Meta::NameValue(nv) = parsed_attr {
-        if let Expr::Lit(expr) = &nv.value {
-            if let Lit::Str(lit_str) = &expr.lit {
-                cookie_name.push_str(&lit_str.value());
-            }
-        }
-    }
 
     let cookie_struct = &input.ident;
 
+    let crypto_impl = if signed {
+        quote! { impl cookiebox::cookies::RequiresSigning for #cookie_struct {} }
+    } else if encrypted {
+        quote! { impl cookiebox::cookies::RequiresEncryption for #cookie_struct {} }
+    } else {
+        quote! {}
+    };
+
+    let default_attributes = if same_site.is_some() || max_age.is_some() {
+        let same_site =
+            same_site.map(|variant| quote! { .same_site(cookiebox::SameSite::#variant) });
+        let max_age = max_age.map(|secs| quote! { .max_age_secs(#secs) });
+        quote! {
+            fn __cookiebox_default_attributes<'c>() -> cookiebox::Attributes<'c> {
+                cookiebox::Attributes::default() #same_site #max_age
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let expanded = quote! {
         #input
 
         impl CookieName for #cookie_struct {
             const COOKIE_NAME: &'static str = #cookie_name;
+
+            #default_attributes
         }
+
+        #crypto_impl
     };
 
     expanded.into()
 }
 
+/// Converts a struct identifier such as `HTTPSessionId` into `http-session-id`
+fn to_kebab_case(ident: &str) -> String {
+    let chars: Vec<char> = ident.trim_start_matches("r#").chars().collect();
+    let mut name = String::with_capacity(chars.len() + 4);
+
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_' {
+            name.push('-');
+            continue;
+        }
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower)
+            {
+                name.push('-');
+            }
+        }
+        name.extend(c.to_lowercase());
+    }
+
+    name
+}
+
+/// Extracts a string literal from an attribute argument value
+fn string_literal(value: &Expr) -> Result<LitStr, syn::Error> {
+    if let Expr::Lit(expr) = value {
+        if let Lit::Str(lit_str) = &expr.lit {
+            return Ok(lit_str.clone());
+        }
+    }
+    Err(syn::Error::new_spanned(value, "Expected a string literal"))
+}
+
+/// Maps a `same_site` argument to the matching `SameSite` variant
+fn same_site_variant(lit_str: &LitStr) -> Result<syn::Ident, syn::Error> {
+    let variant = match lit_str.value().to_ascii_lowercase().as_str() {
+        "strict" => "Strict",
+        "lax" => "Lax",
+        "none" => "None",
+        _ => {
+            return Err(syn::Error::new_spanned(
+                lit_str,
+                "Expected `same_site` to be one of \"strict\", \"lax\", or \"none\"",
+            ))
+        }
+    };
+    Ok(syn::Ident::new(variant, lit_str.span()))
+}
+
+/// Extracts the seconds of a `max_age` argument, given as an integer or a string literal
+fn max_age_secs(value: &Expr) -> Result<u64, syn::Error> {
+    let parsed = match value {
+        Expr::Lit(expr) => match &expr.lit {
+            Lit::Int(lit_int) => lit_int.base10_parse::<u64>().ok(),
+            Lit::Str(lit_str) => lit_str.value().parse::<u64>().ok(),
+            _ => None,
+        },
+        _ => None,
+    };
+    parsed.ok_or_else(|| {
+        syn::Error::new_spanned(
+            value,
+            "Expected `max_age` to be a number of seconds, e.g. `3600` or `\"3600\"`",
+        )
+    })
+}
+
+/// Checks the name against the `cookie-name` token grammar of RFC 6265
+fn validate_cookie_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("The cookie name cannot be empty".to_string());
+    }
+
+    match name
+        .chars()
+        .find(|c| !c.is_ascii() || c.is_ascii_control() || "()<>@,;:\\\"/[]?={} \t".contains(*c))
+    {
+        Some(c) => Err(format!(
+            "`{name}` is not a valid cookie name: {c:?} is not allowed by RFC 6265, use `allow_invalid` to opt out"
+        )),
+        None => Ok(()),
+    }
+}
+
 /// Implements a FromRequest for a struct that holds cookie types
 ///
-/// **Note**: only allows structs with either a single unnamed field or multiple unnamed fields
-#[proc_macro_derive(FromRequest)]
+/// The extraction error defaults to `Box<dyn std::error::Error>`. It can be set with `#[from_request(error = "...")]`
+/// to a type that implements `From<&'static str>`, which is used when the storage is missing from the request.
+///
+/// **Note**: only allows structs with either unnamed fields or named fields
+#[proc_macro_derive(FromRequest, attributes(from_request))]
 pub fn cookie_collection(item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as DeriveInput);
     let collection_struct = &input.ident;
 
+    let error_type = match extract_error_type(&input) {
+        Ok(error_type) => error_type,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
     // Extract the field types based on whether it's a tuple or named struct.
     let (field_names, field_types) = match extract_fields_types(&input) {
         Ok(fields) => fields,
@@ -61,19 +266,8 @@ pub fn cookie_collection(item: TokenStream) -> TokenStream {
     // Extract the generic type argument from a Cookie<'c, SomeType> type.
     let inner_types = field_types
         .iter()
-        .try_fold(
-            Vec::new(),
-            |mut types, field_type| match extract_cookie_inner_type(field_type) {
-                Some(inner_type) => {
-                    types.push(inner_type);
-                    Ok(types)
-                }
-                None => Err(syn::Error::new_spanned(
-                    field_type,
-                    "Expected field type to be `Cookie<'c, SomeType>`",
-                )),
-            },
-        );
+        .map(|field_type| extract_cookie_inner_type(field_type))
+        .collect::<Result<Vec<_>, _>>();
 
     let inner_types = match inner_types {
         Ok(types) => types,
@@ -89,15 +283,17 @@ pub fn cookie_collection(item: TokenStream) -> TokenStream {
     // Generate the implementation for FromRequest
     let expanded = quote! {
         impl actix_web::FromRequest for #collection_struct<'static> {
-            type Error = Box<dyn std::error::Error>;
+            type Error = #error_type;
             type Future = std::future::Ready<Result<Self, Self::Error>>;
 
             fn from_request(req: &actix_web::HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
-                match req.extensions().get::<cookiebox::Storage>() {
+                match cookiebox::storage(req) {
                     Some(storage) => {
                         std::future::ready(Ok( #generated_types ))
                     }
-                    None => std::future::ready(Err("Storage not found in request extension".into())),
+                    None => std::future::ready(Err(<#error_type as std::convert::From<&'static str>>::from(
+                        "Storage not found in request extension",
+                    ))),
                 }
             }
         }
@@ -106,13 +302,43 @@ pub fn cookie_collection(item: TokenStream) -> TokenStream {
     expanded.into()
 }
 
+/// Reads the error type from `#[from_request(error = "...")]`, defaulting to `Box<dyn std::error::Error>`
+fn extract_error_type(input: &DeriveInput) -> Result<Type, syn::Error> {
+    let mut error_type = None;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("from_request"))
+    {
+        let parsed_attrs = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+
+        for parsed_attr in parsed_attrs {
+            match parsed_attr {
+                Meta::NameValue(nv) if nv.path.is_ident("error") => {
+                    error_type = Some(string_literal(&nv.value)?.parse::<Type>()?);
+                }
+                meta => {
+                    return Err(syn::Error::new_spanned(
+                        meta,
+                        "Unexpected parameter: expected `error = \"...\"`",
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(error_type.unwrap_or_else(|| syn::parse_quote!(Box<dyn std::error::Error>)))
+}
+
 fn extract_fields_types(
     input: &DeriveInput,
 ) -> Result<(Option<Vec<syn::Ident>>, Vec<&Type>), syn::Error> {
     match &input.data {
         syn::Data::Struct(data_struct) => match &data_struct.fields {
-            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
-                Ok((None, vec![&fields.unnamed[0].ty]))
+            Fields::Unnamed(fields) if !fields.unnamed.is_empty() => {
+                let field_types = fields.unnamed.iter().map(|f| &f.ty).collect();
+                Ok((None, field_types))
             }
             Fields::Named(fields) => {
                 // Unwrap here is okay since Fields::Named require a field name which make a None ident value impossible to represent
@@ -124,10 +350,10 @@ fn extract_fields_types(
                 let field_types = fields.named.iter().map(|f| &f.ty).collect();
                 Ok((Some(field_names), field_types))
             }
-            // Units and unnamed with more than 1 fields
+            // Units and empty tuples
             token => Err(syn::Error::new_spanned(
                 token,
-                "Expected a single unnamed field or multiple named fields",
+                "Expected at least one unnamed or named field",
             )),
         },
         // Enum and union
@@ -136,18 +362,34 @@ fn extract_fields_types(
 }
 
 /// Extracts the inner type (SomeType) from a `Cookie<'c, SomeType>` type.
-fn extract_cookie_inner_type(field_type: &Type) -> Option<&Type> {
-    if let Type::Path(type_path) = field_type {
-        let segment = type_path.path.segments.first()?;
-        if segment.ident == "Cookie" {
-            if let PathArguments::AngleBracketed(generics) = &segment.arguments {
-                if generics.args.len() == 2 {
-                    if let syn::GenericArgument::Type(inner_type) = &generics.args[1] {
-                        return Some(inner_type);
-                    }
-                }
-            }
+fn extract_cookie_inner_type(field_type: &Type) -> Result<&Type, syn::Error> {
+    let expected = || {
+        syn::Error::new_spanned(
+            field_type,
+            "Expected field type to be `Cookie<'c, SomeType>`",
+        )
+    };
+
+    let Type::Path(type_path) = field_type else {
+        return Err(expected());
+    };
+    let segment = type_path.path.segments.first().ok_or_else(expected)?;
+    if segment.ident != "Cookie" {
+        return Err(expected());
+    }
+    let PathArguments::AngleBracketed(generics) = &segment.arguments else {
+        return Err(expected());
+    };
+
+    match generics.args.iter().collect::<Vec<_>>().as_slice() {
+        [syn::GenericArgument::Lifetime(_), syn::GenericArgument::Type(inner_type)] => {
+            Ok(inner_type)
         }
+        // The lifetime is easy to forget since `Cookie<T>` reads naturally
+        [syn::GenericArgument::Type(_)] => Err(syn::Error::new_spanned(
+            generics,
+            "Cookie requires a lifetime and a type parameter: `Cookie<'c, T>`",
+        )),
+        _ => Err(expected()),
     }
-    None
 }
@@ -1,15 +1,22 @@
+use biscotti::time::fmt::rfc2822;
+use biscotti::time::{Timestamp, tz::TimeZone};
 use biscotti::{Expiration, time::SignedDuration};
 use biscotti::{RemovalCookie, ResponseCookie, ResponseCookieId, SameSite};
 use std::borrow::Cow;
+use std::time::{Duration, SystemTime};
 
 /// Simple builder for cookie attributes
 ///
+/// Cookies named with the `__Secure-` or `__Host-` prefix of RFC 6265bis get the attributes browsers require for them when
+/// inserted or removed: `Secure` for both, plus `Path=/` and no `Domain` for `__Host-`. Conflicting attributes are overridden
+/// and logged at the `warn` level.
+///
 /// [Attributes] acts as a facade to [ResponseCookie](https://docs.rs/biscotti/latest/biscotti/struct.ResponseCookie.html) and [RemovalCoolie](https://docs.rs/biscotti/latest/biscotti/struct.RemovalCookie.html)
 ///
 /// ```no_run
 /// use cookiebox::cookiebox_macros::cookie;
 /// use cookiebox::cookies::{CookieName, OutgoingConfig};
-/// use cookiebox::{Attributes, SameSite, Expiration};
+/// use cookiebox::{Attributes, Expiration, Priority, SameSite};
 /// use cookiebox::time::{SignedDuration, civil::date,tz::TimeZone};
 ///
 /// #[cookie(name = "my-cookie")]
@@ -27,12 +34,13 @@ use std::borrow::Cow;
 ///
 ///         Attributes::new()
 ///             .path("/some-path")
-///             // the leading dot is stripped
-///             .domain("..example.com")
+///             // a single leading dot is stripped
+///             .domain(".example.com")
 ///             .same_site(SameSite::Lax)
 ///             .secure(true)
 ///             .http_only(true)
 ///             .partitioned(true)
+///             .priority(Priority::High)
 ///             .expires(Expiration::from(date))
 ///             // max_age take precedence over expires
 ///             .max_age(SignedDuration::from_hours(10))
@@ -41,19 +49,36 @@ use std::borrow::Cow;
 ///     }
 /// }
 /// ```
+#[derive(Clone, Debug, PartialEq)]
 pub struct Attributes<'c> {
     path: Option<Cow<'c, str>>,
     domain: Option<Cow<'c, str>>,
     secure: Option<bool>,
     http_only: Option<bool>,
     partitioned: Option<bool>,
+    priority: Option<Priority>,
     same_site: Option<SameSite>,
     max_age: Option<SignedDuration>,
     expires: Option<Expiration>,
     permanent: bool,
+    inherit: bool,
 }
 impl<'c> Attributes<'c> {
-    /// Create a new [Attributes] instance
+    /// Create a new [Attributes] instance without any attribute set
+    ///
+    /// The attributes left unset are omitted from the `Set-Cookie` header, so the browser applies its own defaults, e.g. the
+    /// path of the request URL for `Path`. Unlike [Attributes::default], which is the same as [Attributes::recommended].
+    ///
+    /// ```
+    /// use cookiebox::Attributes;
+    ///
+    /// let attributes = Attributes::new();
+    ///
+    /// assert_ne!(attributes, Attributes::default());
+    /// assert_eq!(attributes.get_path(), None);
+    /// assert_eq!(attributes.get_same_site(), None);
+    /// assert_eq!(attributes.get_http_only(), None);
+    /// ```
     pub fn new() -> Self {
         Attributes {
             path: None,
@@ -62,11 +87,84 @@ impl<'c> Attributes<'c> {
             domain: None,
             secure: None,
             partitioned: None,
+            priority: None,
             max_age: None,
             expires: None,
             permanent: false,
+            inherit: false,
+        }
+    }
+    /// Create an [Attributes] instance with the recommended attributes, the same as [Attributes::default]
+    ///
+    /// The instance has `path: "/"`, `SameSite: Lax`, and `http_only: true`.
+    ///
+    /// ```
+    /// use cookiebox::{Attributes, SameSite};
+    ///
+    /// let attributes = Attributes::recommended();
+    ///
+    /// assert_eq!(attributes, Attributes::default());
+    /// assert_eq!(attributes.get_path(), Some("/"));
+    /// assert_eq!(attributes.get_same_site(), Some(SameSite::Lax));
+    /// assert_eq!(attributes.get_http_only(), Some(true));
+    /// ```
+    pub fn recommended() -> Self {
+        Attributes {
+            path: Some("/".into()),
+            http_only: Some(true),
+            same_site: Some(SameSite::Lax),
+            ..Attributes::new()
         }
     }
+    /// Create an [Attributes] instance that inherits the default attributes of the middleware
+    ///
+    /// Attributes set on the returned instance override the ones of [CookieMiddleware::default_attributes](crate::CookieMiddleware::default_attributes),
+    /// while unset ones are taken from it. This differs from [Attributes::default], which is used as is.
+    ///
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{CookieName, OutgoingConfig};
+    /// use cookiebox::Attributes;
+    ///
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl OutgoingConfig for MyCookie {
+    ///     type Insert = String;
+    ///
+    ///     fn attributes<'c>() -> Attributes<'c> {
+    ///         // Use the middleware defaults, only scoped to another path
+    ///         Attributes::inherit().path("/admin")
+    ///     }
+    /// }
+    /// ```
+    pub fn inherit() -> Self {
+        Attributes {
+            inherit: true,
+            ..Attributes::new()
+        }
+    }
+    /// Create an [Attributes] instance for a partitioned cookie, also known as CHIPS
+    ///
+    /// Partitioned cookies are kept in a separate jar per top-level site, which lets embedded third-party content keep
+    /// state without cross-site tracking. Browsers only accept them with `Partitioned`, `Secure`, and `Path=/`, and as
+    /// they are meant for cross-site contexts, `SameSite=None`. The instance has all four set, and nothing else.
+    ///
+    /// ```
+    /// use cookiebox::{Attributes, SameSite};
+    ///
+    /// let attributes = Attributes::partitioned_chips().http_only(true);
+    ///
+    /// assert_eq!(attributes.get_partitioned(), Some(true));
+    /// assert_eq!(attributes.get_same_site(), Some(SameSite::None));
+    /// ```
+    pub fn partitioned_chips() -> Self {
+        Attributes::new()
+            .partitioned(true)
+            .secure(true)
+            .same_site(SameSite::None)
+            .path("/")
+    }
     /// Sets the `path` of `self` to `path`
     #[inline]
     pub fn path<T: Into<Cow<'c, str>>>(mut self, path: T) -> Self {
@@ -75,12 +173,67 @@ impl<'c> Attributes<'c> {
     }
     /// Sets the `domain` of `self` to `domain`
     ///
-    /// **Note**: if the Domain starts with a leading `.`, the leading `.` is stripped.
-    #[inline]
+    /// The domain is normalized before being stored, so [Attributes::get_domain] returns the value that is sent:
+    /// - a single leading `.` is stripped, since browsers ignore it, e.g. `.example.com` is stored as `example.com`
+    /// - an empty domain, or one that still starts with a `.` after that, e.g. `..example.com`, is rejected with a `warn` log
+    ///   and the domain is left unset
+    ///
+    /// ```
+    /// use cookiebox::Attributes;
+    ///
+    /// assert_eq!(Attributes::new().domain(".example.com").get_domain(), Some("example.com"));
+    /// assert_eq!(Attributes::new().domain("..example.com").get_domain(), None);
+    /// assert_eq!(Attributes::new().domain("..").get_domain(), None);
+    /// ```
     pub fn domain<T: Into<Cow<'c, str>>>(mut self, domain: T) -> Self {
-        self.domain = Some(domain.into());
+        self.domain = normalize_domain(domain.into());
         self
     }
+    /// Sets the `domain` of `self` to the registrable domain of `host` so the cookie is shared across its subdomains
+    ///
+    /// The registrable domain is resolved with the public suffix `list`, e.g. `app.example.com` resolves to `example.com`.
+    /// A `host` that is itself a public suffix, such as `com` or `co.uk`, is refused since a cookie scoped to it would
+    /// leak to every site under that suffix. `host` is expected without a port, and in its ASCII form for internationalized
+    /// names. IP addresses, hosts that are not valid domain names, and hosts under a suffix missing from `list` are refused
+    /// as well, since they have no registrable domain.
+    ///
+    /// ```no_run
+    /// use cookiebox::Attributes;
+    /// use cookiebox::publicsuffix::List;
+    ///
+    /// let list: List = std::fs::read_to_string("public_suffix_list.dat")
+    ///     .unwrap()
+    ///     .parse()
+    ///     .unwrap();
+    ///
+    /// let attributes = Attributes::default().registrable_domain("app.example.com", &list);
+    /// ```
+    #[cfg(feature = "psl")]
+    pub fn registrable_domain<L: publicsuffix::Psl>(
+        self,
+        host: &str,
+        list: &L,
+    ) -> Result<Self, PublicSuffixError> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+        let ip = host.strip_prefix('[').and_then(|ip| ip.strip_suffix(']'));
+        if ip.unwrap_or(&host).parse::<std::net::IpAddr>().is_ok() {
+            return Err(PublicSuffixError::IpAddress(host));
+        }
+        if !is_domain_name(&host) {
+            return Err(PublicSuffixError::InvalidDomain(host));
+        }
+
+        match list.domain(host.as_bytes()) {
+            // Unknown suffixes only match the default `*` rule of the list
+            Some(domain) if domain.suffix().is_known() => {
+                let domain = String::from_utf8_lossy(domain.as_bytes()).into_owned();
+                Ok(self.domain(domain))
+            }
+            Some(_) => Err(PublicSuffixError::UnknownSuffix(host)),
+            None => Err(PublicSuffixError::PublicSuffix(host)),
+        }
+    }
     /// Sets the `secure` of `self` to `value`
     #[inline]
     pub fn secure<T: Into<Option<bool>>>(mut self, value: T) -> Self {
@@ -101,17 +254,68 @@ impl<'c> Attributes<'c> {
         self
     }
     /// Sets the `max_age` of `self` to `value`
+    ///
+    /// [SignedDuration] comes from [jiff](https://docs.rs/jiff), re-exported as [cookiebox::time](crate::time). Use
+    /// [Attributes::max_age_secs] or [Attributes::max_age_duration] to set it from standard library types.
     #[inline]
     pub fn max_age<T: Into<Option<SignedDuration>>>(mut self, value: T) -> Self {
         self.max_age = value.into();
         self
     }
+    /// Sets the `max_age` of `self` to `secs` seconds
+    ///
+    /// Values beyond the range of [SignedDuration] are saturated.
+    #[inline]
+    pub fn max_age_secs(self, secs: u64) -> Self {
+        self.max_age(SignedDuration::from_secs(
+            i64::try_from(secs).unwrap_or(i64::MAX),
+        ))
+    }
+    /// Sets the `max_age` of `self` to a [std::time::Duration]
+    ///
+    /// Values beyond the range of [SignedDuration] are saturated.
+    /// ```
+    /// use cookiebox::Attributes;
+    /// use std::time::Duration;
+    ///
+    /// let attributes = Attributes::default().max_age_duration(Duration::from_secs(60 * 60));
+    ///
+    /// assert_eq!(attributes, Attributes::default().max_age_secs(3600));
+    /// ```
+    #[inline]
+    pub fn max_age_duration(self, duration: Duration) -> Self {
+        self.max_age(SignedDuration::try_from(duration).unwrap_or(SignedDuration::MAX))
+    }
     /// Sets the `expires` of `self` to `value`
+    ///
+    /// `value` is either a [Zoned](crate::time::Zoned) of [jiff](https://docs.rs/jiff), re-exported as [cookiebox::time](crate::time),
+    /// or an [Expiration]. Use [Attributes::expires_at] to set it from a [SystemTime].
+    ///
+    /// `max_age` takes precedence, so `expires` is left out of the `Set-Cookie` header when both are set.
     #[inline]
     pub fn expires<T: Into<Expiration>>(mut self, value: T) -> Self {
         self.expires = Some(value.into());
         self
     }
+    /// Sets the `expires` of `self` to a [SystemTime], in UTC
+    ///
+    /// Times outside the years -9999 to 9999 supported by [jiff](https://docs.rs/jiff) are saturated.
+    /// ```
+    /// use cookiebox::Attributes;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let attributes = Attributes::default().expires_at(SystemTime::now() + Duration::from_secs(60 * 60));
+    /// ```
+    #[inline]
+    pub fn expires_at(self, time: SystemTime) -> Self {
+        let timestamp = Timestamp::try_from(time).unwrap_or(if time < SystemTime::UNIX_EPOCH {
+            Timestamp::MIN
+        } else {
+            Timestamp::MAX
+        });
+
+        self.expires(timestamp.to_zoned(TimeZone::UTC))
+    }
     /// Sets the `partitioned` of `self` to `value`
     ///
     /// **Note**: Partitioned cookies require the `Secure` attribute. If not set explicitly, the browser will automatically set it to `true`.
@@ -120,30 +324,394 @@ impl<'c> Attributes<'c> {
         self.partitioned = value.into();
         self
     }
+    /// Sets the `priority` of `self` to `value`
+    ///
+    /// The `Priority` attribute is a Chromium extension that tells the browser which cookies to evict last when a domain
+    /// holds too many. Other browsers ignore it.
+    #[inline]
+    pub fn priority<T: Into<Option<Priority>>>(mut self, value: T) -> Self {
+        self.priority = value.into();
+        self
+    }
     /// Sets the `permanent` of `self` to `value`
     #[inline]
     pub fn permanent(mut self, value: bool) -> Self {
         self.permanent = value;
         self
     }
+    /// Returns `self` with the attributes set in `other` applied over it
+    ///
+    /// The precedence rules are:
+    /// - `path`, `domain`, `secure`, `http_only`, `partitioned`, `priority`, `same_site`, `max_age`, and `expires` are taken from `other`
+    ///   when set there, and kept from `self` otherwise. An attribute cannot be unset through `other`.
+    /// - `permanent` is `true` if it is `true` in `other`, or in `self` while `other` sets neither `max_age` nor `expires`.
+    ///   This lets an explicit lifetime in `other` replace a permanent base.
+    ///
+    /// ```
+    /// use cookiebox::{Attributes, SameSite};
+    ///
+    /// let base = Attributes::new()
+    ///     .secure(true)
+    ///     .same_site(SameSite::Strict)
+    ///     .domain("example.com");
+    ///
+    /// let attributes = base.clone().merge(Attributes::new().http_only(false));
+    ///
+    /// assert_eq!(attributes.get_same_site(), Some(SameSite::Strict));
+    /// assert_eq!(attributes.get_http_only(), Some(false));
+    /// ```
+    pub fn merge(self, other: Attributes<'c>) -> Self {
+        let lifetime_set = other.max_age.is_some() || other.expires.is_some();
+
+        Attributes {
+            path: other.path.or(self.path),
+            domain: other.domain.or(self.domain),
+            secure: other.secure.or(self.secure),
+            http_only: other.http_only.or(self.http_only),
+            partitioned: other.partitioned.or(self.partitioned),
+            priority: other.priority.or(self.priority),
+            same_site: other.same_site.or(self.same_site),
+            max_age: other.max_age.or(self.max_age),
+            expires: other.expires.or(self.expires),
+            permanent: other.permanent || (self.permanent && !lifetime_set),
+            inherit: self.inherit,
+        }
+    }
+    /// Returns the `path` of `self`
+    #[inline]
+    pub fn get_path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+    /// Returns the `domain` of `self`
+    #[inline]
+    pub fn get_domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+    /// Returns the `secure` of `self`
+    #[inline]
+    pub fn get_secure(&self) -> Option<bool> {
+        self.secure
+    }
+    /// Returns the `http_only` of `self`
+    #[inline]
+    pub fn get_http_only(&self) -> Option<bool> {
+        self.http_only
+    }
+    /// Returns the `same_site` of `self`
+    #[inline]
+    pub fn get_same_site(&self) -> Option<SameSite> {
+        self.same_site
+    }
+    /// Returns the `max_age` of `self`
+    #[inline]
+    pub fn get_max_age(&self) -> Option<SignedDuration> {
+        self.max_age
+    }
+    /// Returns the `expires` of `self`
+    #[inline]
+    pub fn get_expires(&self) -> Option<&Expiration> {
+        self.expires.as_ref()
+    }
+    /// Returns the `partitioned` of `self`
+    #[inline]
+    pub fn get_partitioned(&self) -> Option<bool> {
+        self.partitioned
+    }
+    /// Returns the `priority` of `self`
+    #[inline]
+    pub fn get_priority(&self) -> Option<Priority> {
+        self.priority
+    }
+    /// Returns the `permanent` of `self`
+    #[inline]
+    pub fn is_permanent(&self) -> bool {
+        self.permanent
+    }
 }
+impl Attributes<'static> {
+    /// Parses a full `Set-Cookie` header value into its name, value, and [Attributes]
+    ///
+    /// The name and value are returned as they appear in the header, they are not percent-decoded and surrounding quotes are kept.
+    /// Attribute names are matched case-insensitively and unknown attributes are ignored, while a known attribute with an invalid
+    /// value is reported as an error.
+    ///
+    /// ```
+    /// use cookiebox::Attributes;
+    ///
+    /// let (name, value, attributes) =
+    ///     Attributes::parse_set_cookie("session=abc123; Path=/; Secure; HttpOnly; SameSite=Lax").unwrap();
+    ///
+    /// assert_eq!(name, "session");
+    /// assert_eq!(value, "abc123");
+    /// ```
+    pub fn parse_set_cookie(
+        header: &str,
+    ) -> Result<(String, String, Attributes<'static>), SetCookieParseError> {
+        let mut parts = header.split(';');
+
+        // `split` always yields at least one item
+        let pair = parts.next().unwrap_or_default();
+        let (name, value) = match pair.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return Err(SetCookieParseError::MissingPair(pair.to_string())),
+        };
+
+        if name.is_empty() {
+            return Err(SetCookieParseError::EmptyName(value.to_string()));
+        }
+
+        let mut attributes = Attributes::new();
+
+        for part in parts {
+            let (key, value) = match part.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (part.trim(), ""),
+            };
+            let invalid =
+                || SetCookieParseError::InvalidAttribute(key.to_string(), value.to_string());
+
+            match key.to_ascii_lowercase().as_str() {
+                "path" => attributes = attributes.path(value.to_string()),
+                "domain" => attributes = attributes.domain(value.to_string()),
+                "secure" => attributes = attributes.secure(true),
+                "httponly" => attributes = attributes.http_only(true),
+                "partitioned" => attributes = attributes.partitioned(true),
+                "samesite" => {
+                    let same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => SameSite::Strict,
+                        "lax" => SameSite::Lax,
+                        "none" => SameSite::None,
+                        _ => return Err(invalid()),
+                    };
+                    attributes = attributes.same_site(same_site)
+                }
+                "priority" => {
+                    let priority = match value.to_ascii_lowercase().as_str() {
+                        "low" => Priority::Low,
+                        "medium" => Priority::Medium,
+                        "high" => Priority::High,
+                        _ => return Err(invalid()),
+                    };
+                    attributes = attributes.priority(priority)
+                }
+                "max-age" => {
+                    let seconds = value.parse::<i64>().map_err(|_| invalid())?;
+                    attributes = attributes.max_age(SignedDuration::from_secs(seconds))
+                }
+                "expires" => {
+                    let date = rfc2822::parse(value).map_err(|_| invalid())?;
+                    attributes = attributes.expires(date)
+                }
+                _ => {}
+            }
+        }
+
+        Ok((name.to_string(), value.to_string(), attributes))
+    }
+}
+
+/// The values of the `Priority` cookie attribute
+///
+/// Browsers that support it treat a cookie without the attribute as [Priority::Medium].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+impl Priority {
+    /// Returns the attribute value as written in the `Set-Cookie` header
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "Low",
+            Priority::Medium => "Medium",
+            Priority::High => "High",
+        }
+    }
+}
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The error returned by [Attributes::parse_set_cookie]
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum SetCookieParseError {
+    #[error("Expected a name-value pair, but no `=` was found in `{0}`")]
+    MissingPair(String),
+    #[error("The name of a cookie cannot be empty, but found an empty name with `{0}` as value")]
+    EmptyName(String),
+    #[error("Invalid value `{1}` for the `{0}` attribute")]
+    InvalidAttribute(String, String),
+}
+
 /// Create [Attributes] with default values - `path: "/"`,  `SameSite: Lax`, and `http_only: true`
+///
+/// This is what [Attributes::recommended] returns, and what [OutgoingConfig::attributes](crate::cookies::OutgoingConfig::attributes) returns unless overridden.
 impl Default for Attributes<'_> {
     fn default() -> Self {
+        Attributes::recommended()
+    }
+}
+
+impl<'c> Attributes<'c> {
+    // Fills the unset attributes from `base` if `self` was created with `inherit`, otherwise returns `self` as is
+    pub(crate) fn inherit_from(self, base: &Attributes<'c>) -> Self {
+        if !self.inherit {
+            return self;
+        }
+
         Attributes {
-            path: Some("/".into()),
-            http_only: Some(true),
-            same_site: Some(SameSite::Lax),
-            domain: None,
-            secure: None,
-            partitioned: None,
-            max_age: None,
-            expires: None,
+            inherit: false,
+            ..base.clone().merge(self)
+        }
+    }
+    // Applies the attributes browsers require for the `__Secure-` and `__Host-` name prefixes, overriding conflicting ones
+    pub(crate) fn enforce_name_prefix(mut self, name: &str) -> Self {
+        if !has_secure_prefix(name) {
+            return self;
+        }
+
+        if self.secure == Some(false) {
+            log::warn!("`{name}` requires the `Secure` attribute, ignoring `secure(false)`");
+        }
+        self.secure = Some(true);
+
+        if has_host_prefix(name) {
+            if self.path.as_deref().is_some_and(|path| path != "/") {
+                log::warn!("`{name}` requires `Path=/`, ignoring the configured path");
+            }
+            if self.domain.is_some() {
+                log::warn!(
+                    "`{name}` cannot have a `Domain` attribute, ignoring the configured domain"
+                );
+            }
+            self.path = Some("/".into());
+            self.domain = None;
+        }
+
+        self
+    }
+}
+
+// The name prefixes of RFC 6265bis are matched case-insensitively by browsers
+fn starts_with_ignore_case(name: &str, prefix: &str) -> bool {
+    name.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+/// Returns whether `name` starts with `__Host-`
+pub(crate) fn has_host_prefix(name: &str) -> bool {
+    starts_with_ignore_case(name, "__Host-")
+}
+
+/// Returns whether `name` starts with `__Secure-` or `__Host-`, both requiring the `Secure` attribute
+pub(crate) fn has_secure_prefix(name: &str) -> bool {
+    starts_with_ignore_case(name, "__Secure-") || has_host_prefix(name)
+}
+
+// Strips a single leading dot, and rejects a domain made of dots only
+fn normalize_domain(domain: Cow<'_, str>) -> Option<Cow<'_, str>> {
+    let domain = match domain {
+        Cow::Borrowed(domain) => Cow::Borrowed(domain.strip_prefix('.').unwrap_or(domain)),
+        Cow::Owned(domain) => match domain.strip_prefix('.') {
+            Some(stripped) => Cow::Owned(stripped.to_string()),
+            None => Cow::Owned(domain),
+        },
+    };
+
+    if domain.is_empty() || domain.starts_with('.') {
+        log::warn!("`{domain}` is not a valid cookie domain, ignoring it");
+        return None;
+    }
+
+    Some(domain)
+}
+
+/// Builds a removal cookie for `name` scoped by the path and domain of `attributes`
+pub(crate) fn removal_cookie<'c>(
+    name: impl Into<Cow<'c, str>>,
+    attributes: &Attributes<'c>,
+) -> ResponseCookie<'c> {
+    let name = name.into();
+    let secure = has_secure_prefix(&name);
+
+    // Sets the domain and path only
+    let removal_cookie: ResponseCookie = RemovalCookie::new(name).set_attributes(attributes).into();
+
+    // Browsers refuse a prefixed cookie without `Secure`, even to remove it
+    if secure {
+        removal_cookie.set_secure(true)
+    } else {
+        removal_cookie
+    }
+}
+
+/// Copies the attributes of a [ResponseCookie](https://docs.rs/biscotti/latest/biscotti/struct.ResponseCookie.html) into [Attributes]
+///
+/// `permanent` has no counterpart on the cookie and is left `false`, the lifetime is carried by `max_age` and `expires` instead.
+/// `priority` is not carried by the cookie either and is left unset.
+/// ```
+/// use cookiebox::{Attributes, ResponseCookie, SameSite};
+///
+/// let response_cookie = ResponseCookie::new("name", "value")
+///     .set_path("/admin")
+///     .set_same_site(SameSite::Strict);
+///
+/// let attributes = Attributes::from(&response_cookie);
+///
+/// assert_eq!(attributes.get_path(), Some("/admin"));
+/// assert_eq!(attributes.get_same_site(), Some(SameSite::Strict));
+/// ```
+impl<'c> From<&ResponseCookie<'_>> for Attributes<'c> {
+    fn from(response_cookie: &ResponseCookie<'_>) -> Self {
+        Attributes {
+            path: response_cookie.path().map(|path| path.to_string().into()),
+            domain: response_cookie
+                .domain()
+                .map(|domain| domain.to_string().into()),
+            secure: response_cookie.secure(),
+            http_only: response_cookie.http_only(),
+            partitioned: response_cookie.partitioned(),
+            priority: None,
+            same_site: response_cookie.same_site(),
+            max_age: response_cookie.max_age(),
+            expires: response_cookie.expires().cloned(),
             permanent: false,
+            inherit: false,
         }
     }
 }
 
+/// The error returned by [Attributes::registrable_domain] when the host has no registrable domain
+#[cfg(feature = "psl")]
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum PublicSuffixError {
+    #[error("`{0}` is a public suffix and cannot be used as a cookie domain")]
+    PublicSuffix(String),
+    #[error("`{0}` is an IP address, which cannot be shared with subdomains")]
+    IpAddress(String),
+    #[error("`{0}` is not a valid domain name")]
+    InvalidDomain(String),
+    #[error("`{0}` is not under a suffix of the public suffix list")]
+    UnknownSuffix(String),
+}
+
+// Whether `host` is made of dot separated labels of ASCII letters, digits, and inner hyphens, per RFC 1123
+#[cfg(feature = "psl")]
+fn is_domain_name(host: &str) -> bool {
+    host.len() <= 253
+        && host.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+}
+
 pub(crate) trait AttributesSetter<'c> {
     fn set_attributes(self, attributes: &Attributes<'c>) -> Self;
 }
@@ -162,15 +730,25 @@ impl<'c> AttributesSetter<'c> for ResponseCookie<'c> {
         } else {
             self = self.set_max_age(attributes.max_age);
 
-            if let Some(expires) = &attributes.expires {
+            // `max_age` takes precedence, so `expires` is only sent without it rather than leaving the choice to the browser
+            if attributes.max_age.is_none()
+                && let Some(expires) = &attributes.expires
+            {
                 self = self.set_expires(expires.clone())
             }
         }
 
-        self.set_secure(attributes.secure)
+        // Browsers drop `SameSite=None` cookies without `Secure`, so it is implied unless explicitly disabled
+        let secure = match (attributes.same_site, attributes.secure) {
+            (Some(SameSite::None), None) => Some(true),
+            (_, secure) => secure,
+        };
+
+        self.set_secure(secure)
             .set_http_only(attributes.http_only)
             .set_same_site(attributes.same_site)
             .set_partitioned(attributes.partitioned)
+        // `priority` cannot be carried by the cookie, it is tracked by the storage instead
     }
 }
 
@@ -197,3 +775,496 @@ impl<'c> AttributesSetter<'c> for ResponseCookieId<'c> {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::attributes::{Attributes, AttributesSetter, Priority, SetCookieParseError};
+    use crate::time::{SignedDuration, civil::date, tz::TimeZone};
+    use crate::{Expiration, SameSite};
+    use biscotti::ResponseCookie;
+
+    #[test]
+    fn getters() {
+        let date = date(2024, 1, 15)
+            .at(0, 0, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap();
+        let attributes = Attributes::new()
+            .path("/some-path")
+            .domain("example.com")
+            .secure(true)
+            .http_only(false)
+            .same_site(SameSite::Strict)
+            .max_age(SignedDuration::from_hours(1))
+            .expires(date.clone())
+            .partitioned(true)
+            .permanent(true);
+
+        assert_eq!(attributes.get_path(), Some("/some-path"));
+        assert_eq!(attributes.get_domain(), Some("example.com"));
+        assert_eq!(attributes.get_secure(), Some(true));
+        assert_eq!(attributes.get_http_only(), Some(false));
+        assert_eq!(attributes.get_same_site(), Some(SameSite::Strict));
+        assert_eq!(
+            attributes.get_max_age(),
+            Some(SignedDuration::from_hours(1))
+        );
+        assert_eq!(
+            attributes.get_expires().and_then(Expiration::datetime),
+            Some(&date)
+        );
+        assert_eq!(attributes.get_partitioned(), Some(true));
+        assert!(attributes.is_permanent());
+
+        let attributes = Attributes::new();
+
+        assert_eq!(attributes.get_path(), None);
+        assert_eq!(attributes.get_domain(), None);
+        assert_eq!(attributes.get_same_site(), None);
+        assert_eq!(attributes.get_expires(), None);
+        assert!(!attributes.is_permanent());
+    }
+    #[test]
+    fn same_site_none_implies_secure() {
+        let header = ResponseCookie::new("name", "value")
+            .set_attributes(&Attributes::default().same_site(SameSite::None))
+            .to_string();
+
+        assert!(header.contains("SameSite=None"));
+        assert!(header.contains("; Secure"));
+
+        // explicitly disabled
+        let response_cookie = ResponseCookie::new("name", "value").set_attributes(
+            &Attributes::default()
+                .same_site(SameSite::None)
+                .secure(false),
+        );
+
+        assert_eq!(response_cookie.secure(), Some(false));
+
+        // other values are left as is
+        let response_cookie = ResponseCookie::new("name", "value")
+            .set_attributes(&Attributes::default().same_site(SameSite::Strict));
+
+        assert_eq!(response_cookie.secure(), None);
+    }
+    #[test]
+    fn partitioned_chips() {
+        let header = ResponseCookie::new("name", "value")
+            .set_attributes(&Attributes::partitioned_chips())
+            .to_string();
+
+        assert_eq!(
+            header,
+            "name=value; SameSite=None; Partitioned; Secure; Path=/"
+        );
+    }
+    #[test]
+    fn max_age_takes_precedence_over_expires() {
+        let date = date(2024, 1, 15)
+            .at(0, 0, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap();
+        let attributes = Attributes::new()
+            .expires(date)
+            .max_age(SignedDuration::from_hours(1));
+
+        let header = ResponseCookie::new("name", "value")
+            .set_attributes(&attributes)
+            .to_string();
+
+        assert!(header.contains("Max-Age=3600"));
+        assert!(!header.contains("Expires"));
+
+        // expires is sent on its own
+        let header = ResponseCookie::new("name", "value")
+            .set_attributes(&attributes.max_age(None))
+            .to_string();
+
+        assert!(header.contains("Expires=Mon, 15 Jan 2024 00:00:00 GMT"));
+    }
+    #[test]
+    fn from_response_cookie() {
+        let attributes = Attributes::new()
+            .path("/some-path")
+            .domain("example.com")
+            .secure(true)
+            .http_only(true)
+            .partitioned(true)
+            .same_site(SameSite::None)
+            .max_age(SignedDuration::from_hours(1));
+        let response_cookie = ResponseCookie::new("name", "value").set_attributes(&attributes);
+
+        assert_eq!(Attributes::from(&response_cookie), attributes);
+
+        let attributes = Attributes::new().expires(Expiration::Session);
+        let response_cookie = ResponseCookie::new("name", "value").set_attributes(&attributes);
+
+        assert_eq!(Attributes::from(&response_cookie), attributes);
+
+        // permanent is carried as max_age and expires
+        let response_cookie =
+            ResponseCookie::new("name", "value").set_attributes(&Attributes::new().permanent(true));
+        let attributes = Attributes::from(&response_cookie);
+
+        assert!(!attributes.is_permanent());
+        assert!(attributes.get_max_age().is_some());
+        assert!(attributes.get_expires().is_some());
+    }
+    #[test]
+    fn merge() {
+        let base = Attributes::new()
+            .secure(true)
+            .same_site(SameSite::Strict)
+            .domain("example.com")
+            .http_only(true);
+
+        // set fields override, unset fields are kept
+        let attributes = base
+            .clone()
+            .merge(Attributes::new().http_only(false).path("/admin"));
+
+        assert_eq!(
+            attributes,
+            Attributes::new()
+                .secure(true)
+                .same_site(SameSite::Strict)
+                .domain("example.com")
+                .http_only(false)
+                .path("/admin")
+        );
+
+        // merging empty attributes is a no-op
+        assert_eq!(base.clone().merge(Attributes::new()), base);
+
+        // permanent from either side
+        assert!(
+            base.clone()
+                .merge(Attributes::new().permanent(true))
+                .is_permanent()
+        );
+        assert!(
+            base.clone()
+                .permanent(true)
+                .merge(Attributes::new())
+                .is_permanent()
+        );
+
+        // an explicit lifetime replaces a permanent base
+        let attributes = base
+            .permanent(true)
+            .merge(Attributes::new().max_age(SignedDuration::from_hours(1)));
+
+        assert!(!attributes.is_permanent());
+        assert_eq!(
+            attributes.get_max_age(),
+            Some(SignedDuration::from_hours(1))
+        );
+    }
+    #[test]
+    fn std_time() {
+        assert_eq!(
+            Attributes::new().max_age_secs(90).get_max_age(),
+            Some(SignedDuration::from_secs(90))
+        );
+        assert_eq!(
+            Attributes::new().max_age_secs(u64::MAX).get_max_age(),
+            Some(SignedDuration::from_secs(i64::MAX))
+        );
+        assert_eq!(
+            Attributes::new()
+                .max_age_duration(std::time::Duration::from_millis(1500))
+                .get_max_age(),
+            Some(SignedDuration::from_millis(1500))
+        );
+        assert_eq!(
+            Attributes::new()
+                .max_age_duration(std::time::Duration::MAX)
+                .get_max_age(),
+            Some(SignedDuration::MAX)
+        );
+
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_705_276_800);
+        let expected = date(2024, 1, 15)
+            .at(0, 0, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap();
+
+        assert_eq!(
+            Attributes::new()
+                .expires_at(time)
+                .get_expires()
+                .and_then(Expiration::datetime),
+            Some(&expected)
+        );
+
+        // out of range times are saturated
+        let far_future =
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(u64::from(u32::MAX) * 100);
+        assert_eq!(
+            Attributes::new()
+                .expires_at(far_future)
+                .get_expires()
+                .and_then(Expiration::datetime)
+                .map(|zoned| zoned.timestamp()),
+            Some(biscotti::time::Timestamp::MAX)
+        );
+    }
+    #[test]
+    fn domain_normalization() {
+        let domain = |domain: &'static str| Attributes::new().domain(domain);
+
+        assert_eq!(domain("example.com").get_domain(), Some("example.com"));
+        assert_eq!(domain(".example.com").get_domain(), Some("example.com"));
+        // only a single leading dot is stripped, a domain left with one is rejected
+        assert_eq!(domain("..example.com").get_domain(), None);
+
+        // owned domains are normalized the same way
+        assert_eq!(
+            Attributes::new()
+                .domain(".example.com".to_string())
+                .get_domain(),
+            Some("example.com")
+        );
+
+        // empty and all-dot domains are rejected as well
+        assert_eq!(domain("").get_domain(), None);
+        assert_eq!(domain(".").get_domain(), None);
+        assert_eq!(domain("...").get_domain(), None);
+        assert_eq!(
+            domain("...").domain("example.com").get_domain(),
+            Some("example.com")
+        );
+    }
+    #[test]
+    fn constructors() {
+        let empty = Attributes::new();
+
+        assert_eq!(empty.get_path(), None);
+        assert_eq!(empty.get_domain(), None);
+        assert_eq!(empty.get_secure(), None);
+        assert_eq!(empty.get_http_only(), None);
+        assert_eq!(empty.get_same_site(), None);
+        assert_eq!(empty.get_max_age(), None);
+        assert_eq!(empty.get_expires(), None);
+        assert_eq!(empty.get_partitioned(), None);
+        assert!(!empty.is_permanent());
+
+        let default = Attributes::default();
+
+        assert_eq!(default.get_path(), Some("/"));
+        assert_eq!(default.get_domain(), None);
+        assert_eq!(default.get_secure(), None);
+        assert_eq!(default.get_http_only(), Some(true));
+        assert_eq!(default.get_same_site(), Some(SameSite::Lax));
+        assert_eq!(default.get_max_age(), None);
+        assert_eq!(default.get_expires(), None);
+        assert_eq!(default.get_partitioned(), None);
+        assert!(!default.is_permanent());
+
+        assert_ne!(Attributes::new(), default);
+        assert_eq!(Attributes::recommended(), default);
+        assert_eq!(
+            default,
+            Attributes::new()
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Lax)
+        );
+        // Only the inheritance flag tells them apart
+        assert_ne!(Attributes::inherit(), empty);
+        assert_eq!(Attributes::inherit().inherit_from(&empty), empty);
+    }
+    #[test]
+    fn compare_and_debug() {
+        let attributes = Attributes::default().secure(true);
+
+        assert_eq!(
+            attributes.clone(),
+            Attributes::new()
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Lax)
+                .secure(true)
+        );
+        assert_ne!(attributes, Attributes::default());
+        assert_ne!(Attributes::default().permanent(true), Attributes::default());
+
+        let debug = format!("{:?}", attributes.permanent(true));
+
+        assert!(debug.starts_with("Attributes {"));
+        assert!(debug.contains("secure: Some(true)"));
+        assert!(debug.contains("permanent: true"));
+    }
+    #[test]
+    fn parse_set_cookie_with_every_attribute() {
+        let header = "Type%20A=%22id%22; HttpOnly; SameSite=None; Partitioned; Secure; Path=/some-path; \
+            Domain=example.com; Max-Age=36000; Expires=Mon, 15 Jan 2024 00:00:00 GMT; Priority=high";
+
+        let (name, value, attributes) = Attributes::parse_set_cookie(header).unwrap();
+
+        let date = date(2024, 1, 15)
+            .at(0, 0, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap();
+
+        assert_eq!(name, "Type%20A");
+        assert_eq!(value, "%22id%22");
+        assert_eq!(attributes.path.as_deref(), Some("/some-path"));
+        assert_eq!(attributes.domain.as_deref(), Some("example.com"));
+        assert_eq!(attributes.http_only, Some(true));
+        assert_eq!(attributes.secure, Some(true));
+        assert_eq!(attributes.partitioned, Some(true));
+        assert_eq!(attributes.same_site, Some(SameSite::None));
+        assert_eq!(attributes.priority, Some(Priority::High));
+        assert_eq!(attributes.max_age, Some(SignedDuration::from_hours(10)));
+        assert_eq!(
+            attributes.expires.as_ref().and_then(Expiration::datetime),
+            Some(&date)
+        );
+        assert!(!attributes.permanent);
+    }
+    #[test]
+    fn parse_set_cookie_round_trips_emitted_header() {
+        let response_cookie = ResponseCookie::new("name", "value").set_attributes(
+            &Attributes::default()
+                .domain("example.com")
+                .max_age(SignedDuration::from_hours(1)),
+        );
+        let header = response_cookie.to_string();
+
+        let (name, value, attributes) = Attributes::parse_set_cookie(&header).unwrap();
+        let parsed_cookie = ResponseCookie::new(name, value).set_attributes(&attributes);
+
+        assert_eq!(parsed_cookie.to_string(), header);
+    }
+    #[test]
+    fn parse_set_cookie_edge_cases() {
+        // quoted values are kept as is and attribute names are case insensitive
+        let (name, value, attributes) =
+            Attributes::parse_set_cookie(r#"name="quoted value"; path=/; SECURE; unknown=1"#)
+                .unwrap();
+        assert_eq!(name, "name");
+        assert_eq!(value, r#""quoted value""#);
+        assert_eq!(attributes.path.as_deref(), Some("/"));
+        assert_eq!(attributes.secure, Some(true));
+
+        // missing value
+        let (name, value, _) = Attributes::parse_set_cookie("name=; Path=/").unwrap();
+        assert_eq!(name, "name");
+        assert_eq!(value, "");
+
+        assert_eq!(
+            Attributes::parse_set_cookie("name; Path=/").err(),
+            Some(SetCookieParseError::MissingPair("name".to_string()))
+        );
+        assert_eq!(
+            Attributes::parse_set_cookie("=value").err(),
+            Some(SetCookieParseError::EmptyName("value".to_string()))
+        );
+        assert_eq!(
+            Attributes::parse_set_cookie("name=value; Max-Age=soon").err(),
+            Some(SetCookieParseError::InvalidAttribute(
+                "Max-Age".to_string(),
+                "soon".to_string()
+            ))
+        );
+        assert_eq!(
+            Attributes::parse_set_cookie("name=value; SameSite=Loose").err(),
+            Some(SetCookieParseError::InvalidAttribute(
+                "SameSite".to_string(),
+                "Loose".to_string()
+            ))
+        );
+        assert_eq!(
+            Attributes::parse_set_cookie("name=value; Priority=Urgent").err(),
+            Some(SetCookieParseError::InvalidAttribute(
+                "Priority".to_string(),
+                "Urgent".to_string()
+            ))
+        );
+    }
+    #[test]
+    fn priority() {
+        let attributes = Attributes::new().priority(Priority::High);
+        assert_eq!(attributes.get_priority(), Some(Priority::High));
+        assert_eq!(Priority::High.to_string(), "High");
+
+        // taken from `other` when set there
+        let merged = attributes.clone().merge(Attributes::new());
+        assert_eq!(merged.get_priority(), Some(Priority::High));
+        let merged = attributes.merge(Attributes::new().priority(Priority::Low));
+        assert_eq!(merged.get_priority(), Some(Priority::Low));
+
+        assert_eq!(Attributes::default().get_priority(), None);
+    }
+    #[cfg(feature = "psl")]
+    #[test]
+    fn registrable_domain() {
+        use crate::attributes::AttributesSetter;
+        use crate::attributes::{Attributes, PublicSuffixError};
+        use biscotti::ResponseCookie;
+        use publicsuffix::List;
+
+        let list: List = "// ===BEGIN ICANN DOMAINS===\ncom\nuk\nco.uk\n"
+            .parse()
+            .unwrap();
+
+        let attributes = Attributes::new()
+            .registrable_domain("app.example.com", &list)
+            .unwrap();
+        let response_cookie = ResponseCookie::new("name", "value").set_attributes(&attributes);
+        assert_eq!(response_cookie.domain(), Some("example.com"));
+
+        let attributes = Attributes::new()
+            .registrable_domain("api.example.co.uk", &list)
+            .unwrap();
+        let response_cookie = ResponseCookie::new("name", "value").set_attributes(&attributes);
+        assert_eq!(response_cookie.domain(), Some("example.co.uk"));
+
+        assert_eq!(
+            Attributes::new().registrable_domain("com", &list).err(),
+            Some(PublicSuffixError::PublicSuffix("com".to_string()))
+        );
+        assert_eq!(
+            Attributes::new().registrable_domain("co.uk", &list).err(),
+            Some(PublicSuffixError::PublicSuffix("co.uk".to_string()))
+        );
+
+        // hosts without a registrable domain
+        let error = |host| Attributes::new().registrable_domain(host, &list).err();
+
+        assert_eq!(
+            error("127.0.0.1"),
+            Some(PublicSuffixError::IpAddress("127.0.0.1".to_string()))
+        );
+        assert_eq!(
+            error("[::1]"),
+            Some(PublicSuffixError::IpAddress("[::1]".to_string()))
+        );
+        assert_eq!(
+            error("app.example.internal"),
+            Some(PublicSuffixError::UnknownSuffix(
+                "app.example.internal".to_string()
+            ))
+        );
+        assert_eq!(
+            error("app..example.com"),
+            Some(PublicSuffixError::InvalidDomain(
+                "app..example.com".to_string()
+            ))
+        );
+        assert_eq!(
+            error("example.com:8080"),
+            Some(PublicSuffixError::InvalidDomain(
+                "example.com:8080".to_string()
+            ))
+        );
+        assert_eq!(
+            error("-app.example.com"),
+            Some(PublicSuffixError::InvalidDomain(
+                "-app.example.com".to_string()
+            ))
+        );
+    }
+}
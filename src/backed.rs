@@ -0,0 +1,384 @@
+//! Resolve server-side data from a typed id cookie
+use crate::cookies::{Cookie, IncomingConfig};
+use crate::middleware::e500;
+use actix_web::{FromRequest, HttpMessage, HttpRequest, dev::Payload, web};
+use anyhow::anyhow;
+use std::any::type_name;
+use std::future::Future;
+use std::pin::Pin;
+
+/// Loads the data a cookie type points to, e.g. a session record keyed by the cookie value
+///
+/// The cookie value is read through [IncomingConfig], so `Get` is the id type handed to [AsyncCookieBacked::load].
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{CookieName, IncomingConfig};
+/// use cookiebox::{AsyncCookieBacked, CookieBacked};
+/// use actix_web::{HttpRequest, HttpResponse};
+///
+/// #[cookie(name = "session-id")]
+/// pub struct SessionId;
+///
+/// impl IncomingConfig for SessionId {
+///     type Get = String;
+/// }
+///
+/// #[derive(Clone)]
+/// pub struct Session {
+///     user: String,
+/// }
+///
+/// impl AsyncCookieBacked for SessionId {
+///     type Data = Session;
+///     type Error = actix_web::Error;
+///
+///     async fn load(_req: &HttpRequest, id: &String) -> Result<Session, actix_web::Error> {
+///         // Look up the record in your store
+///         Ok(Session { user: id.clone() })
+///     }
+/// }
+///
+/// async fn whoami(session: CookieBacked<SessionId>) -> HttpResponse {
+///     HttpResponse::Ok().body(session.into_inner().user)
+/// }
+/// ```
+pub trait AsyncCookieBacked: IncomingConfig + 'static {
+    /// The data resolved from the cookie id
+    type Data: Clone + 'static;
+    /// The error returned when the data cannot be loaded
+    type Error: Into<actix_web::Error>;
+
+    /// Loads the data for `id`. The request is provided to reach application data such as a database pool
+    fn load(
+        req: &HttpRequest,
+        id: &Self::Get,
+    ) -> impl Future<Output = Result<Self::Data, Self::Error>>;
+
+    /// Decides whether loaded data is cached for the rest of the request. This can be overwriting
+    ///
+    /// When `true`, extracting [CookieBacked] more than once in the same request calls [AsyncCookieBacked::load] only once.
+    fn cache() -> bool {
+        true
+    }
+}
+
+/// Extractor that reads the id cookie `T` and resolves its data with [AsyncCookieBacked::load]
+///
+/// A missing or malformed id cookie fails the extraction with the corresponding [CookieBoxError](crate::cookies::CookieBoxError).
+pub struct CookieBacked<T: AsyncCookieBacked>(T::Data);
+
+impl<T: AsyncCookieBacked> CookieBacked<T> {
+    /// Returns the resolved data
+    pub fn into_inner(self) -> T::Data {
+        self.0
+    }
+}
+
+impl<T: AsyncCookieBacked> std::ops::Deref for CookieBacked<T> {
+    type Target = T::Data;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// Keeps the loaded data in the request extensions, keyed by the cookie type
+struct CachedData<T: AsyncCookieBacked>(T::Data);
+
+impl<T: AsyncCookieBacked> FromRequest for CookieBacked<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+
+        Box::pin(async move {
+            if let Some(cached) = req.extensions().get::<CachedData<T>>() {
+                return Ok(CookieBacked(cached.0.clone()));
+            }
+
+            let storage = crate::storage(&req)
+                .ok_or_else(|| e500(anyhow!("Storage not found in request extension")))?;
+
+            let id = Cookie::<T>::new(&storage).get()?;
+            let data = T::load(&req, &id).await.map_err(Into::into)?;
+
+            if T::cache() {
+                req.extensions_mut().insert(CachedData::<T>(data.clone()));
+            }
+
+            Ok(CookieBacked(data))
+        })
+    }
+}
+
+/// An async store holding sessions, e.g. backed by Redis, looked up by the cookie types implementing [SessionBacked]
+///
+/// Unlike [AsyncCookieBacked], which is implemented by the cookie type, the store is a value registered as application data
+/// with [App::app_data](actix_web::App::app_data) wrapped in [web::Data], so it can hold a connection pool.
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{CookieName, IncomingConfig};
+/// use cookiebox::{CookieMiddleware, Processor, ProcessorConfig, SessionBacked, SessionCookie, SessionStore};
+/// use actix_web::{web, App, HttpResponse};
+/// use std::collections::HashMap;
+/// use std::sync::RwLock;
+///
+/// #[cookie(name = "session-id")]
+/// pub struct SessionId;
+///
+/// impl IncomingConfig for SessionId {
+///     type Get = String;
+/// }
+///
+/// impl SessionBacked for SessionId {
+///     type Store = MemoryStore;
+/// }
+///
+/// #[derive(Default)]
+/// pub struct MemoryStore(RwLock<HashMap<String, String>>);
+///
+/// impl SessionStore for MemoryStore {
+///     type Id = String;
+///     type Session = String;
+///     type Error = actix_web::Error;
+///
+///     async fn load(&self, id: &String) -> Result<String, actix_web::Error> {
+///         self.0
+///             .read()
+///             .unwrap()
+///             .get(id)
+///             .cloned()
+///             .ok_or_else(|| actix_web::error::ErrorUnauthorized("unknown session"))
+///     }
+/// }
+///
+/// async fn whoami(session: SessionCookie<SessionId>) -> HttpResponse {
+///     HttpResponse::Ok().body(session.into_inner())
+/// }
+///
+/// let processor: Processor = ProcessorConfig::default().into();
+/// let store = web::Data::new(MemoryStore::default());
+///
+/// let app = App::new()
+///     .app_data(store.clone())
+///     .wrap(CookieMiddleware::new(processor))
+///     .route("/whoami", web::get().to(whoami));
+/// ```
+pub trait SessionStore: 'static {
+    /// The id read from the session cookie
+    type Id;
+    /// The session loaded for an id
+    type Session: Clone + 'static;
+    /// The error returned when the session cannot be loaded, e.g. when the id is unknown
+    type Error: Into<actix_web::Error>;
+
+    /// Loads the session for `id`
+    fn load(&self, id: &Self::Id) -> impl Future<Output = Result<Self::Session, Self::Error>>;
+}
+
+/// Marks a cookie type whose value is the id of a session held by the store `Store`
+///
+/// [AsyncCookieBacked] is implemented for such a cookie type, loading the session from the `web::Data<Store>` registered as
+/// application data, so the session is extracted with [SessionCookie]. A store that is not registered fails the extraction
+/// with `500 Internal Server Error`.
+pub trait SessionBacked: IncomingConfig + 'static {
+    /// The store the session is loaded from
+    type Store: SessionStore<Id = Self::Get>;
+}
+
+impl<T: SessionBacked> AsyncCookieBacked for T {
+    type Data = <T::Store as SessionStore>::Session;
+    type Error = actix_web::Error;
+
+    async fn load(req: &HttpRequest, id: &T::Get) -> Result<Self::Data, Self::Error> {
+        let store = req.app_data::<web::Data<T::Store>>().ok_or_else(|| {
+            e500(anyhow!(
+                "`{}` not found in app data, register it with `App::app_data(web::Data::new(...))`",
+                type_name::<T::Store>()
+            ))
+        })?;
+
+        store.load(id).await.map_err(Into::into)
+    }
+}
+
+/// Extractor that reads the id cookie `T` and loads its session from the store of `T` before the handler runs
+///
+/// This is [CookieBacked] for a [SessionBacked] cookie type, so the session is cached for the rest of the request.
+pub type SessionCookie<T> = CookieBacked<T>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cookies::{CookieBoxError, CookieName};
+    use crate::storage::Storage;
+    use actix_web::test::TestRequest;
+    use biscotti::RequestCookie;
+    use std::cell::Cell;
+
+    thread_local! {
+        static LOADS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct SessionId;
+    impl CookieName for SessionId {
+        const COOKIE_NAME: &'static str = "session-id";
+    }
+    impl IncomingConfig for SessionId {
+        type Get = String;
+    }
+    impl AsyncCookieBacked for SessionId {
+        type Data = String;
+        type Error = actix_web::Error;
+
+        async fn load(_req: &HttpRequest, id: &String) -> Result<String, actix_web::Error> {
+            LOADS.with(|loads| loads.set(loads.get() + 1));
+            match id.as_str() {
+                "known" => Ok("some user".to_string()),
+                _ => Err(actix_web::error::ErrorUnauthorized("unknown session")),
+            }
+        }
+    }
+
+    fn request_with_session(value: &str) -> HttpRequest {
+        let storage = Storage::new();
+        storage
+            .request_storage
+            .borrow_mut()
+            .append(RequestCookie::new("session-id", value.to_string()));
+
+        let req = TestRequest::default().to_http_request();
+        req.extensions_mut().insert(storage);
+        req
+    }
+
+    #[actix_web::test]
+    async fn load_and_cache() {
+        LOADS.with(|loads| loads.set(0));
+        let req = request_with_session(r#""known""#);
+
+        let first = CookieBacked::<SessionId>::extract(&req).await.unwrap();
+        let second = CookieBacked::<SessionId>::extract(&req).await.unwrap();
+
+        assert_eq!(first.into_inner(), "some user");
+        assert_eq!(*second, "some user");
+        assert_eq!(LOADS.with(Cell::get), 1);
+    }
+
+    #[actix_web::test]
+    async fn load_error() {
+        let req = request_with_session(r#""unknown""#);
+
+        let error = CookieBacked::<SessionId>::extract(&req)
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.as_response_error().status_code(),
+            actix_web::http::StatusCode::UNAUTHORIZED
+        );
+    }
+
+    struct StoredSessionId;
+    impl CookieName for StoredSessionId {
+        const COOKIE_NAME: &'static str = "session-id";
+    }
+    impl IncomingConfig for StoredSessionId {
+        type Get = String;
+    }
+    impl SessionBacked for StoredSessionId {
+        type Store = MemoryStore;
+    }
+
+    #[derive(Default)]
+    struct MemoryStore(std::sync::RwLock<std::collections::HashMap<String, String>>);
+
+    impl SessionStore for MemoryStore {
+        type Id = String;
+        type Session = String;
+        type Error = actix_web::Error;
+
+        async fn load(&self, id: &String) -> Result<String, actix_web::Error> {
+            // Yield once, like a network round trip would
+            actix_web::rt::task::yield_now().await;
+
+            self.0
+                .read()
+                .unwrap()
+                .get(id)
+                .cloned()
+                .ok_or_else(|| actix_web::error::ErrorUnauthorized("unknown session"))
+        }
+    }
+
+    fn request_with_store(value: &str) -> HttpRequest {
+        let store = MemoryStore::default();
+        store
+            .0
+            .write()
+            .unwrap()
+            .insert("known".to_string(), "some user".to_string());
+
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("session-id", value.to_string()));
+
+        let req = TestRequest::default()
+            .app_data(web::Data::new(store))
+            .to_http_request();
+        req.extensions_mut().insert(storage);
+        req
+    }
+
+    #[actix_web::test]
+    async fn session_cookie() {
+        let req = request_with_store(r#""known""#);
+        let session = SessionCookie::<StoredSessionId>::extract(&req)
+            .await
+            .unwrap();
+
+        assert_eq!(*session, "some user");
+
+        let req = request_with_store(r#""unknown""#);
+        let error = SessionCookie::<StoredSessionId>::extract(&req)
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.as_response_error().status_code(),
+            actix_web::http::StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[actix_web::test]
+    async fn session_cookie_without_store() {
+        let req = request_with_session(r#""known""#);
+        let error = SessionCookie::<StoredSessionId>::extract(&req)
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.as_response_error().status_code(),
+            actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[actix_web::test]
+    async fn missing_id_cookie() {
+        let req = TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Storage::new());
+
+        let error = CookieBacked::<SessionId>::extract(&req)
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.as_error::<CookieBoxError>(),
+            Some(&CookieBoxError::NotFound("session-id".to_string()))
+        );
+    }
+}
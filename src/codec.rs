@@ -0,0 +1,234 @@
+//! The formats a cookie value is written in
+use std::any::type_name;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::cookies::CookieBoxError;
+
+/// The format a cookie value is written in
+///
+/// [Codec::Json] is the default. The codec of a cookie type is set with [OutgoingConfig::CODEC](crate::cookies::OutgoingConfig::CODEC) for `insert` and
+/// [IncomingConfig::CODEC](crate::cookies::IncomingConfig::CODEC) for `get`, so both should be set to the same codec.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Codec {
+    /// The value is written as JSON text
+    #[default]
+    Json,
+    /// The value is written as MessagePack, encoded as unpadded base64url. This is available with the `messagepack` feature.
+    ///
+    /// Structured values are smaller than their JSON form once percent-encoded, which helps staying under the 4 KB limit browsers
+    /// put on a cookie. [OutgoingConfig::serialize](crate::cookies::OutgoingConfig::serialize) is still applied first, the [Value] it returns is what gets encoded.
+    ///
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{Codec, CookieName, IncomingConfig, OutgoingConfig};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Cart {
+    ///     items: Vec<(u32, u16)>,
+    /// }
+    ///
+    /// #[cookie(name = "cart")]
+    /// pub struct CartCookie;
+    ///
+    /// impl IncomingConfig for CartCookie {
+    ///     type Get = Cart;
+    ///     const CODEC: Codec = Codec::MessagePack;
+    /// }
+    /// impl OutgoingConfig for CartCookie {
+    ///     type Insert = Cart;
+    ///     const CODEC: Codec = Codec::MessagePack;
+    /// }
+    /// ```
+    #[cfg(feature = "messagepack")]
+    MessagePack,
+}
+
+impl Codec {
+    // Encodes the serialized value of the cookie `name` into the text stored in the cookie
+    pub(crate) fn encode(self, name: &str, value: &Value) -> Result<String, CookieBoxError> {
+        #[cfg(not(feature = "messagepack"))]
+        let _ = name;
+
+        match self {
+            Codec::Json => Ok(value.to_string()),
+            #[cfg(feature = "messagepack")]
+            Codec::MessagePack => {
+                use base64::Engine;
+                use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+                rmp_serde::to_vec(value)
+                    .map(|bytes| URL_SAFE_NO_PAD.encode(bytes))
+                    .map_err(|e| CookieBoxError::Serialization(name.to_string(), e.into()))
+            }
+        }
+    }
+    // Decodes the text stored in a cookie into `T`
+    pub(crate) fn decode<T: DeserializeOwned>(self, value: &str) -> Result<T, CookieBoxError> {
+        let deserialization_error = |e: Box<dyn std::error::Error + Send + Sync>| {
+            CookieBoxError::Deserialization(value.to_string(), type_name::<T>().to_string(), e)
+        };
+
+        match self {
+            Codec::Json => serde_json::from_str(value).map_err(|e| deserialization_error(e.into())),
+            #[cfg(feature = "messagepack")]
+            Codec::MessagePack => {
+                use base64::Engine;
+                use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+                let bytes = URL_SAFE_NO_PAD
+                    .decode(value)
+                    .map_err(|e| deserialization_error(e.into()))?;
+
+                rmp_serde::from_slice(&bytes).map_err(|e| deserialization_error(e.into()))
+            }
+        }
+    }
+    // Decodes the text stored in a cookie into `T`, which may borrow from `buffer`
+    pub(crate) fn decode_borrowed<'v, T: Deserialize<'v>>(
+        self,
+        value: &str,
+        buffer: &'v mut Vec<u8>,
+    ) -> Result<T, CookieBoxError> {
+        let deserialization_error = |e: Box<dyn std::error::Error + Send + Sync>| {
+            CookieBoxError::Deserialization(value.to_string(), type_name::<T>().to_string(), e)
+        };
+
+        buffer.clear();
+
+        match self {
+            Codec::Json => {
+                buffer.extend_from_slice(value.as_bytes());
+                serde_json::from_slice(buffer).map_err(|e| deserialization_error(e.into()))
+            }
+            #[cfg(feature = "messagepack")]
+            Codec::MessagePack => {
+                use base64::Engine;
+                use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+                URL_SAFE_NO_PAD
+                    .decode_vec(value, buffer)
+                    .map_err(|e| deserialization_error(e.into()))?;
+
+                rmp_serde::from_slice(buffer).map_err(|e| deserialization_error(e.into()))
+            }
+        }
+    }
+}
+
+/// How the middleware encodes the value of a cookie in the `Set-Cookie` header
+///
+/// Set per cookie type with [OutgoingConfig::encode_value](crate::cookies::OutgoingConfig::encode_value).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValueEncoding {
+    /// The value is percent-encoded when [ProcessorConfig::percent_encode](biscotti::ProcessorConfig::percent_encode) is set,
+    /// which is the default
+    #[default]
+    PercentEncoded,
+    /// The value is written as is, e.g. `"id"` instead of `%22id%22`
+    ///
+    /// This is meant for clients that read the cookie without percent-decoding it. Keep in mind that:
+    /// - the value must be valid in a cookie per RFC 6265, so it cannot hold whitespace, `;`, `,`, `\`, inner `"`, or non-ASCII
+    ///   characters. `insert` returns [CookieBoxError::InvalidRawValue] for such a value instead of queuing it, since a `;` would
+    ///   otherwise be read by the client as the start of another attribute
+    /// - the [Processor](crate::Processor) still percent-decodes incoming cookies, so a `%` in the value is not read back as written
+    /// - signed and encrypted cookies are unaffected, their value is always safe to send as is
+    /// - the name is still percent-encoded, and sliding expiration refreshes use the default encoding
+    Raw,
+}
+
+impl ValueEncoding {
+    // Whether `value` can be sent in this encoding, i.e. is made of cookie-octets, optionally wrapped in double quotes, when raw
+    pub(crate) fn accepts(self, value: &str) -> bool {
+        match self {
+            ValueEncoding::PercentEncoded => true,
+            ValueEncoding::Raw => {
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
+
+                value.bytes().all(
+                    |b| matches!(b, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "messagepack"))]
+mod tests {
+    use super::Codec;
+    use crate::cookiebox_macros::cookie;
+    use crate::cookies::{Cookie, CookieBoxError, CookieName, IncomingConfig, OutgoingConfig};
+    use crate::{RequestCookie, Storage};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    pub struct Cart {
+        items: Vec<(u32, u16)>,
+        coupon: Option<String>,
+    }
+
+    #[cookie(name = "cart")]
+    pub struct CartCookie;
+
+    impl IncomingConfig for CartCookie {
+        type Get = Cart;
+        const CODEC: Codec = Codec::MessagePack;
+    }
+    impl OutgoingConfig for CartCookie {
+        type Insert = Cart;
+        const CODEC: Codec = Codec::MessagePack;
+    }
+
+    fn cart() -> Cart {
+        Cart {
+            items: vec![(1042, 2), (7, 1)],
+            coupon: Some("WINTER".to_string()),
+        }
+    }
+
+    #[test]
+    fn messagepack_round_trip() {
+        let storage = Storage::new();
+        Cookie::<CartCookie>::new(&storage).insert(cart()).unwrap();
+
+        let value = storage.response_cookies()[0].value().to_string();
+        let json = serde_json::to_string(&cart()).unwrap();
+
+        assert!(value.len() < json.len());
+        assert!(
+            value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        );
+
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("cart", value));
+
+        assert_eq!(Cookie::<CartCookie>::new(&storage).get(), Ok(cart()));
+    }
+    #[test]
+    fn messagepack_invalid_value() {
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("cart", r#"{"items":[]}"#));
+
+        assert!(matches!(
+            Cookie::<CartCookie>::new(&storage).get(),
+            Err(CookieBoxError::Deserialization(value, ..)) if value == r#"{"items":[]}"#
+        ));
+
+        // the base64 error is kept as the source
+        let error = Cookie::<CartCookie>::new(&storage).get().unwrap_err();
+        assert!(
+            std::error::Error::source(&error)
+                .and_then(|e| e.downcast_ref::<base64::DecodeError>())
+                .is_some()
+        );
+    }
+}
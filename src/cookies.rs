@@ -1,20 +1,135 @@
 //! cookiebox's core functionality  
-use crate::attributes::{Attributes, AttributesSetter};
-use crate::storage::Storage;
-use biscotti::{RemovalCookie, ResponseCookie, ResponseCookieId};
-use serde::Serialize;
-use serde::de::DeserializeOwned;
-use serde_json::{Value, json};
+use crate::attributes::{Attributes, AttributesSetter, removal_cookie};
+pub use crate::codec::{Codec, ValueEncoding};
+use crate::spans::{OperationSpan, Outcome};
+use crate::storage::{EmitOptions, Storage};
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+use biscotti::{ResponseCookie, ResponseCookieId};
+use serde::de::{DeserializeOwned, DeserializeSeed};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::any::type_name;
+use subtle::ConstantTimeEq;
 use thiserror::Error;
 
 /// The error returned by [IncomingConfig] get methods
-#[derive(Error, Debug, PartialEq)]
+///
+/// [CookieBoxError] implements [ResponseError] so it can be propagated from handlers with `?`:
+/// - `NotFound` responds with `400 Bad Request`
+/// - `Deserialization` responds with `400 Bad Request`
+/// - `Serialization` responds with `500 Internal Server Error`
+/// - `TooLarge` responds with `500 Internal Server Error`
+/// - `InvalidRawValue` responds with `500 Internal Server Error`
+/// - `Invalid` responds with `400 Bad Request`
+///
+/// To respond differently, map the error into your own [ResponseError] type instead.
+///
+/// The `Deserialization` and `Serialization` variants carry the underlying error as their [source](std::error::Error::source),
+/// e.g. a [serde_json::Error] for the default codec. Two errors are equal when their variant and fields are, the sources are
+/// not compared.
+///
+/// The value held by `Deserialization` was sent by the client and may be a secret, so the [Display](std::fmt::Display) and
+/// [Debug](std::fmt::Debug) output only show its length. Match on the variant to read it.
+#[derive(Error)]
 pub enum CookieBoxError {
     #[error("`{0}` does not exist")]
     NotFound(String),
-    #[error("Failed to deserialize `{0}` to type `{1}`")]
-    Deserialization(String, String),
+    #[error("Failed to deserialize a value of {len} bytes to type `{1}`", len = .0.len())]
+    Deserialization(
+        String,
+        String,
+        #[source] Box<dyn std::error::Error + Send + Sync>,
+    ),
+    #[error("Failed to serialize the `{0}` cookie: {1}")]
+    Serialization(String, #[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("The `{0}` cookie holds an invalid value: {1}")]
+    Invalid(String, String),
+    #[error("The `{name}` cookie is {size} bytes, which exceeds the limit of {limit} bytes")]
+    TooLarge {
+        name: String,
+        size: usize,
+        limit: usize,
+    },
+    #[error("The `{0}` cookie holds characters that cannot be sent without percent-encoding")]
+    InvalidRawValue(String),
+}
+
+// Redacts the `Deserialization` value, like the `Debug` output of `Storage`
+impl std::fmt::Debug for CookieBoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CookieBoxError::NotFound(name) => f.debug_tuple("NotFound").field(name).finish(),
+            CookieBoxError::Deserialization(value, type_name, source) => f
+                .debug_tuple("Deserialization")
+                .field(&format_args!("<redacted, {} bytes>", value.len()))
+                .field(type_name)
+                .field(source)
+                .finish(),
+            CookieBoxError::Serialization(name, source) => f
+                .debug_tuple("Serialization")
+                .field(name)
+                .field(source)
+                .finish(),
+            CookieBoxError::Invalid(name, message) => {
+                f.debug_tuple("Invalid").field(name).field(message).finish()
+            }
+            CookieBoxError::TooLarge { name, size, limit } => f
+                .debug_struct("TooLarge")
+                .field("name", name)
+                .field("size", size)
+                .field("limit", limit)
+                .finish(),
+            CookieBoxError::InvalidRawValue(name) => {
+                f.debug_tuple("InvalidRawValue").field(name).finish()
+            }
+        }
+    }
+}
+
+// The sources are `dyn Error`, which has no equality
+impl PartialEq for CookieBoxError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CookieBoxError::NotFound(a), CookieBoxError::NotFound(b)) => a == b,
+            (CookieBoxError::InvalidRawValue(a), CookieBoxError::InvalidRawValue(b)) => a == b,
+            (CookieBoxError::Serialization(a, _), CookieBoxError::Serialization(b, _)) => a == b,
+            (
+                CookieBoxError::Invalid(name_a, message_a),
+                CookieBoxError::Invalid(name_b, message_b),
+            ) => name_a == name_b && message_a == message_b,
+            (
+                CookieBoxError::TooLarge {
+                    name: name_a,
+                    size: size_a,
+                    limit: limit_a,
+                },
+                CookieBoxError::TooLarge {
+                    name: name_b,
+                    size: size_b,
+                    limit: limit_b,
+                },
+            ) => name_a == name_b && size_a == size_b && limit_a == limit_b,
+            (
+                CookieBoxError::Deserialization(value_a, type_a, _),
+                CookieBoxError::Deserialization(value_b, type_b, _),
+            ) => value_a == value_b && type_a == type_b,
+            _ => false,
+        }
+    }
+}
+
+impl ResponseError for CookieBoxError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CookieBoxError::NotFound(_) => StatusCode::BAD_REQUEST,
+            CookieBoxError::Deserialization(..) => StatusCode::BAD_REQUEST,
+            CookieBoxError::Serialization(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            CookieBoxError::Invalid(..) => StatusCode::BAD_REQUEST,
+            CookieBoxError::TooLarge { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            CookieBoxError::InvalidRawValue(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
 }
 
 /// Base struct for cookie generic types
@@ -24,6 +139,16 @@ pub struct Cookie<'c, T> {
     _marker: std::marker::PhantomData<T>,
 }
 
+/// Shows the name and the attributes set on the instance, never a value
+impl<T: CookieName> std::fmt::Debug for Cookie<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cookie")
+            .field("name", &T::COOKIE_NAME)
+            .field("attributes", &self.attributes)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<'c, T> Cookie<'c, T> {
     /// Create a cookie instance for any generic type parameter
     pub fn new(storage: &Storage<'c>) -> Self {
@@ -33,12 +158,74 @@ impl<'c, T> Cookie<'c, T> {
             _marker: std::marker::PhantomData,
         }
     }
+    /// Sets the attributes used by `insert`, `remove`, and `discard` for this cookie instance, overriding [OutgoingConfig::attributes]
+    ///
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, OutgoingConfig};
+    /// use cookiebox::{Attributes, Storage};
+    ///
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl OutgoingConfig for MyCookie {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// fn insert(storage: &Storage) -> Result<(), CookieBoxError> {
+    ///     let cookie = Cookie::<MyCookie>::new(storage).with_attributes(Attributes::default().path("/admin"));
+    ///     cookie.insert("cookie value".to_string())
+    /// }
+    /// ```
+    pub fn with_attributes(mut self, attributes: Attributes<'c>) -> Self {
+        self.attributes = Some(attributes);
+        self
+    }
+}
+impl<T: CookieName> Cookie<'_, T> {
+    /// Returns the name of the cookie, the same as [CookieName::COOKIE_NAME]
+    ///
+    /// This is handy for logging and error messages in code that is generic over the cookie instance.
+    /// ```
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{Cookie, CookieName};
+    /// use cookiebox::Storage;
+    ///
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// let storage = Storage::new();
+    /// let cookie = Cookie::<MyCookie>::new(&storage);
+    ///
+    /// assert_eq!(cookie.name(), "my-cookie");
+    /// ```
+    pub fn name(&self) -> &'static str {
+        T::COOKIE_NAME
+    }
+    // Returns `Some(Some(value))` for a pending write, `Some(None)` for a pending removal, and `None` if nothing is queued
+    fn pending_value(&self) -> Option<Option<String>> {
+        let response_storage = self.storage.response_storage.borrow();
+        let pending = response_storage
+            .iter()
+            .find(|cookie| cookie.name() == T::COOKIE_NAME)?;
+
+        let is_removal = pending
+            .expires()
+            .and_then(|expires| expires.datetime())
+            .is_some_and(|datetime| datetime.timestamp() <= biscotti::time::Timestamp::UNIX_EPOCH);
+
+        Some((!is_removal).then(|| pending.value().to_string()))
+    }
 }
 /// Provide methods to `get` data from a cookie instance for any generic type parameter that implements [IncomingConfig]
 impl<T: IncomingConfig> Cookie<'_, T> {
     /// Retrieves the data from the [Storage] request collection using the cookie name specified by [CookieName].
     ///
     /// The deserialized date is returned as the associated type defined by the `Get` type from [IncomingConfig].
+    /// `Get` is owned, so [Cow](std::borrow::Cow) fields are supported as `Cow<'static, str>` and always come back as
+    /// `Cow::Owned`. Use `get_borrowed` to deserialize into types that borrow from the value instead.
+    ///
+    /// Whether a value inserted in the same request is returned instead is decided by the [ReadPolicy].
     /// # Example
     /// ```no_run
     /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
@@ -63,6 +250,29 @@ impl<T: IncomingConfig> Cookie<'_, T> {
     /// }
     /// ```
     pub fn get(&self) -> Result<T::Get, CookieBoxError> {
+        let span = OperationSpan::cookie("get", T::COOKIE_NAME);
+        let result = self.read();
+
+        span.end(match &result {
+            Ok(_) => Outcome::Ok,
+            Err(CookieBoxError::NotFound(_)) => Outcome::NotFound,
+            Err(_) => Outcome::Error,
+        });
+
+        result
+    }
+
+    fn read(&self) -> Result<T::Get, CookieBoxError> {
+        let read_policy = T::read_policy().unwrap_or(self.storage.read_policy);
+
+        if read_policy == ReadPolicy::Writes {
+            match self.pending_value() {
+                Some(Some(value)) => return Self::decode(&value),
+                Some(None) => return Err(CookieBoxError::NotFound(T::COOKIE_NAME.to_string())),
+                None => {}
+            }
+        }
+
         let data = &self
             .storage
             .request_storage
@@ -70,19 +280,104 @@ impl<T: IncomingConfig> Cookie<'_, T> {
             .get(T::COOKIE_NAME)
             .ok_or(CookieBoxError::NotFound(T::COOKIE_NAME.to_string()))?;
 
-        let data = serde_json::from_str(data.value()).map_err(|_| {
-            CookieBoxError::Deserialization(
-                data.value().to_string(),
-                type_name::<T::Get>().to_string(),
-            )
-        })?;
-        Ok(data)
+        let value = Self::decode(data.value())?;
+
+        if let Some(attributes) = T::sliding_expiration() {
+            let attributes = attributes.enforce_name_prefix(T::COOKIE_NAME);
+            let refresh_cookie = ResponseCookie::new(T::COOKIE_NAME, data.value().to_string())
+                .set_attributes(&attributes);
+
+            // The refresh is applied in the response phase unless the cookie is written to in the meantime
+            let options = EmitOptions {
+                priority: attributes.get_priority(),
+                ..EmitOptions::default()
+            };
+            self.storage.queue_refresh_cookie(refresh_cookie, options);
+        }
+
+        Ok(value)
+    }
+
+    /// Retrieves the data from the [Storage] request collection like `get`, treating an absent cookie as `Ok(None)`.
+    ///
+    /// Only a failed deserialization is returned as an error.
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up a generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl IncomingConfig for MyCookie {
+    ///     type Get = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn get_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     match cookie.0.get_optional() {
+    ///         Ok(Some(value)) => HttpResponse::Ok().body(value),
+    ///         Ok(None) => HttpResponse::Ok().body("first visit"),
+    ///         Err(_) => HttpResponse::BadRequest().finish(),
+    ///     }
+    /// }
+    /// ```
+    pub fn get_optional(&self) -> Result<Option<T::Get>, CookieBoxError> {
+        match self.get() {
+            Ok(value) => Ok(Some(value)),
+            Err(CookieBoxError::NotFound(_)) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Returns `true` if the cookie name specified by [CookieName] exists in the [Storage] request collection.
+    ///
+    /// The value is not deserialized, so malformed data does not affect the result.
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up a generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl IncomingConfig for MyCookie {
+    ///     type Get = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn has_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     if cookie.0.contains() {
+    ///         // Do expensive work
+    ///     }
+    ///     HttpResponse::Ok().finish()
+    /// }
+    /// ```
+    pub fn contains(&self) -> bool {
+        self.storage
+            .request_storage
+            .borrow()
+            .get(T::COOKIE_NAME)
+            .is_some()
     }
 
     /// Retrieves a list of data items from the [Storage] request collection with the same name using the cookie name specified by [CookieName].
     ///
     /// Each item in the list is of the associated type `Get` from the [IncomingConfig].
     ///
+    /// Browsers send same-named cookies set at different paths or domains in the `Cookie` header without their attributes,
+    /// so the items cannot be told apart by where they were set. Use [Discriminated] as the cookie data type to carry that information in the value.
+    ///
     /// # Example
     /// ```no_run
     /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
@@ -117,320 +412,2373 @@ impl<T: IncomingConfig> Cookie<'_, T> {
         let mut result = Vec::new();
 
         for value in data.values() {
-            let data = serde_json::from_str(value).map_err(|_| {
-                CookieBoxError::Deserialization(
-                    value.to_string(),
-                    type_name::<T::Get>().to_string(),
-                )
-            })?;
-            result.push(data);
+            result.push(Self::decode(value)?);
         }
 
         Ok(result)
     }
-}
 
-/// Provide methods to `insert` and `remove` a cookie instance for any generic type parameter that implements [OutgoingConfig]
-impl<T: OutgoingConfig> Cookie<'_, T> {
-    /// Add a cookie to the [Storage] response collection which later attached to the HTTP response using the `Set-Cookie` header.
+    /// Retrieves the data items with the same name like `get_all`, deserializing each one lazily as the iterator advances.
+    ///
+    /// This avoids deserializing every item when only some are needed, e.g. to find the first one matching a predicate.
+    /// The raw values are copied when the iterator is created, so the [Storage] is not borrowed while iterating.
+    /// An absent cookie yields no items rather than `NotFound`.
     ///
     /// # Example
     /// ```no_run
     /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
-    /// use cookiebox::cookies::{Cookie, CookieName, OutgoingConfig};
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
     /// use actix_web::{HttpResponse, HttpMessage};
     ///
     /// // Set up generic cookie type
     /// #[cookie(name = "my-cookie")]
     /// pub struct MyCookie;
     ///
-    /// impl OutgoingConfig for MyCookie {
-    ///     type Insert = String;
+    /// impl IncomingConfig for MyCookie {
+    ///     type Get = String;
     /// }
-    ///  
+    ///
     /// // Use macro to implement `FromRequest` for cookie collection struct
     /// #[derive(FromRequest)]
     /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
     ///
-    /// async fn insert_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
-    ///     cookie.0.insert("cookie value".to_string());
+    /// async fn find_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     // stops deserializing at the first match
+    ///     let admin = cookie.0.get_all_iter().find(|value| value.as_deref() == Ok("admin"));
     ///     HttpResponse::Ok().finish()
     /// }
     /// ```
-    pub fn insert(&self, value: T::Insert) {
-        let data = T::serialize(value);
-
-        let response_cookie = ResponseCookie::new(T::COOKIE_NAME, data.to_string());
-
-        let attributes = match &self.attributes {
-            Some(attributes) => attributes,
-            None => &T::attributes(),
+    pub fn get_all_iter(&self) -> impl Iterator<Item = Result<T::Get, CookieBoxError>> + use<T> {
+        let values: Vec<String> = match self
+            .storage
+            .request_storage
+            .borrow()
+            .get_all(T::COOKIE_NAME)
+        {
+            Some(data) => data.values().map(str::to_string).collect(),
+            None => Vec::new(),
         };
 
-        let response_cookie = response_cookie.set_attributes(attributes);
+        values.into_iter().map(|value| Self::decode(&value))
+    }
+    // Deserializes `value` and checks it with `T::validate`
+    fn decode(value: &str) -> Result<T::Get, CookieBoxError> {
+        let data = T::deserialize(value)?;
+        Self::validate(&data)?;
 
-        self.storage
-            .response_storage
-            .borrow_mut()
-            .insert(response_cookie);
+        Ok(data)
     }
-    /// Add a removal cookie to the [Storage] response collection, which later attached to the HTTP response using the `Set-Cookie` header.
+    // Checks `data` with `T::validate`, reporting a failed check as `Invalid`
+    fn validate(data: &T::Get) -> Result<(), CookieBoxError> {
+        T::validate(data).map_err(|error| match error {
+            CookieBoxError::Invalid(..) => error,
+            error => CookieBoxError::Invalid(T::COOKIE_NAME.to_string(), error.to_string()),
+        })
+    }
+
+    /// Retrieves the raw bytes of the data from the [Storage] request collection using the cookie name specified by [CookieName].
     ///
-    /// Cookie removal is determined by name, path, and domain
+    /// The value is returned as it was received after being percent-decoded, decrypted, or verified by the middleware,
+    /// without being deserialized. This is meant for cookies that carry encoded binary data rather than JSON text.
     ///
     /// # Example
     /// ```no_run
     /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
-    /// use cookiebox::cookies::{Cookie, CookieName, OutgoingConfig};
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
     /// use actix_web::{HttpResponse, HttpMessage};
     ///
     /// // Set up generic cookie type
     /// #[cookie(name = "my-cookie")]
     /// pub struct MyCookie;
     ///
-    /// impl OutgoingConfig for MyCookie {
-    ///     type Insert = String;
+    /// impl IncomingConfig for MyCookie {
+    ///     type Get = String;
     /// }
-    ///  
+    ///
     /// // Use macro to implement `FromRequest` for cookie collection struct
     /// #[derive(FromRequest)]
     /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
     ///
-    /// async fn remove_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
-    ///     cookie.0.remove();
+    /// async fn get_cookie_bytes(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     cookie.0.get_bytes();
     ///     HttpResponse::Ok().finish()
     /// }
     /// ```
-    pub fn remove(&self) {
-        let attributes = match &self.attributes {
-            Some(attributes) => attributes,
-            None => &T::attributes(),
-        };
-
-        let removal_cookie = RemovalCookie::new(T::COOKIE_NAME);
-
-        // Sets the domain and path only
-        let removal_cookie = removal_cookie.set_attributes(attributes);
-
-        // Inserting the removal cookie will replace any cookie with the same name, path, and domain
-        self.storage
-            .response_storage
-            .borrow_mut()
-            .insert(removal_cookie);
+    pub fn get_bytes(&self) -> Result<Vec<u8>, CookieBoxError> {
+        self.get_raw().map(String::into_bytes)
     }
-    /// Discard a cookie from the response collection [Storage] only
+
+    /// Retrieves the raw string of the data from the [Storage] request collection using the cookie name specified by [CookieName].
     ///
-    /// Discarding a cookie is determined by name, path, and domain
+    /// The value is returned as it was received after being percent-decoded, decrypted, or verified by the middleware,
+    /// without being deserialized. This is meant for passthrough, hashing, or debugging an encoding mismatch.
     ///
     /// # Example
     /// ```no_run
     /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
-    /// use cookiebox::cookies::{Cookie, CookieName, OutgoingConfig};
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
     /// use actix_web::{HttpResponse, HttpMessage};
     ///
     /// // Set up generic cookie type
     /// #[cookie(name = "my-cookie")]
     /// pub struct MyCookie;
     ///
-    /// impl OutgoingConfig for MyCookie {
-    ///     type Insert = String;
+    /// impl IncomingConfig for MyCookie {
+    ///     type Get = String;
     /// }
-    ///  
+    ///
     /// // Use macro to implement `FromRequest` for cookie collection struct
     /// #[derive(FromRequest)]
     /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
     ///
-    /// async fn discard_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
-    ///     cookie.0.insert("Stephanie".to_string());
-    ///     cookie.0.discard();
-    ///     HttpResponse::Ok().finish()
+    /// async fn get_raw_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     match cookie.0.get_raw() {
+    ///         Ok(raw) => HttpResponse::Ok().body(raw),
+    ///         Err(_) => HttpResponse::NotFound().finish(),
+    ///     }
     /// }
     /// ```
-    pub fn discard(&self) {
-        let discard_id = ResponseCookieId::new(T::COOKIE_NAME);
-
-        let attributes = match &self.attributes {
-            Some(attributes) => attributes,
-            None => &T::attributes(),
-        };
-
-        // This sets the path and domain only
-        let discard_id = discard_id.set_attributes(attributes);
+    pub fn get_raw(&self) -> Result<String, CookieBoxError> {
+        let data = self
+            .storage
+            .request_storage
+            .borrow()
+            .get(T::COOKIE_NAME)
+            .ok_or(CookieBoxError::NotFound(T::COOKIE_NAME.to_string()))?;
 
-        self.storage
-            .response_storage
-            .borrow_mut()
-            .discard(discard_id);
+        Ok(data.value().to_string())
     }
-}
 
-/// Provide internal customization for `insert` and `remove` methods in [Cookie].
-///
-/// The `insert` and `remove` will be available when types that implement this trait is used as generic parameters for `Cookie`.
-/// ```no_run
-/// use cookiebox::cookiebox_macros::cookie;
-/// use cookiebox::cookies::{CookieName, OutgoingConfig};
-///
-/// // Define a generic cookie type
-/// #[cookie(name = "__my-cookie")]
-/// pub struct MyCookie;
-///
-/// impl OutgoingConfig for MyCookie {
-///    // Configure the insert type
-///    type Insert = String;
-///    
-///    // The default serialization is used here, if customization is needed, implement the `serialize` method.
-///    
-///    // The default attributes is used here which consists of http-only: true, SameSite: Lax, and
-///    // path: "/"
-/// }
-/// ```
-pub trait OutgoingConfig: CookieName {
-    /// The serialization type when inserting a cookie to storage
-    type Insert: Serialize;
-
-    /// Provides default serialization for a cookie. This can be overwriting
-    fn serialize(values: Self::Insert) -> Value {
-        json!(values)
-    }
+    /// Passes the raw string of the data from the [Storage] request collection to `f` without copying it, and returns the result of `f`
+    ///
+    /// This is the allocation-free counterpart of `get_raw`, meant for hot paths that only inspect large values. The value
+    /// is borrowed for the duration of `f`, so `f` cannot keep it, and must not add request cookies to the same [Storage].
+    /// Returns `NotFound` when the cookie is missing.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl IncomingConfig for MyCookie {
+    ///     type Get = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn cookie_size(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     match cookie.0.with_value(|value| value.len()) {
+    ///         Ok(size) => HttpResponse::Ok().body(size.to_string()),
+    ///         Err(_) => HttpResponse::NotFound().finish(),
+    ///     }
+    /// }
+    /// ```
+    pub fn with_value<R>(&self, f: impl FnOnce(&str) -> R) -> Result<R, CookieBoxError> {
+        let request_storage = self.storage.request_storage.borrow();
+        let data = request_storage
+            .get(T::COOKIE_NAME)
+            .ok_or(CookieBoxError::NotFound(T::COOKIE_NAME.to_string()))?;
 
-    /// Provides preset attributes for a cookie. This can be overwriting
-    fn attributes<'c>() -> Attributes<'c> {
-        Attributes::default()
+        Ok(f(data.value()))
     }
-}
 
-/// Provide internal customization for `get` and `get_all` methods in [Cookie].
-///
-/// The `get` and `get_all` will be available when types that implement this trait is used as generic parameters for `Cookie`.
-/// ```no_run
-/// use cookiebox::cookiebox_macros::cookie;
-/// use cookiebox::cookies::{CookieName, IncomingConfig};
-/// // Define a generic cookie type struct
-/// #[cookie(name = "__my-cookie")]
-/// pub struct MyCookie;
+    /// Compares the raw value of the cookie in the [Storage] request collection against `expected` in constant time
+    ///
+    /// This is meant for checks on secrets such as CSRF double-submit tokens, where comparing with `==` would return as soon
+    /// as a byte differs and leak how much of a guess was right through the response time. The comparison takes the same
+    /// time for any values of the same length, only the length itself is not hidden.
+    ///
+    /// The raw stored value is compared like `get_raw` returns it, before deserialization, so `expected` must be in the stored
+    /// form. A value written by `insert` with the default serialization is JSON, so a string is stored with its quotes, e.g.
+    /// `"token"`, while one written by `insert_raw` is stored as is.
+    /// Returns `false` when the cookie is missing.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
+    /// use actix_web::{web, HttpResponse, HttpMessage};
+    /// use serde::Deserialize;
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "csrf-token")]
+    /// pub struct CsrfToken;
+    ///
+    /// impl IncomingConfig for CsrfToken {
+    ///     type Get = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, CsrfToken>);
+    ///
+    /// #[derive(Deserialize)]
+    /// pub struct Form {
+    ///     csrf_token: String,
+    /// }
+    ///
+    /// async fn submit(cookie: CookieCollection<'_>, form: web::Form<Form>) -> HttpResponse {
+    ///     // The token was written by `insert`, so the stored value is a JSON string, quotes included
+    ///     let expected = serde_json::to_string(&form.csrf_token).unwrap();
+    ///     if !cookie.0.value_equals(&expected) {
+    ///         return HttpResponse::Forbidden().finish();
+    ///     }
+    ///     HttpResponse::Ok().finish()
+    /// }
+    /// ```
+    pub fn value_equals(&self, expected: &str) -> bool {
+        match self.storage.request_storage.borrow().get(T::COOKIE_NAME) {
+            Some(cookie) => cookie.value().as_bytes().ct_eq(expected.as_bytes()).into(),
+            None => false,
+        }
+    }
+
+    /// Retrieves the data from the [Storage] request collection like `get`, driving the deserialization with `seed`.
+    ///
+    /// This is meant for deserialization that needs runtime context, such as a registry to resolve ids.
+    /// The raw value is always read as JSON, [IncomingConfig::deserialize] is not used.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    /// use serde::de::{Deserialize, DeserializeSeed, Deserializer};
+    /// use std::collections::HashMap;
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl IncomingConfig for MyCookie {
+    ///     type Get = u32;
+    /// }
+    ///
+    /// // Resolves the stored id to a name
+    /// struct Registry<'r>(&'r HashMap<u32, String>);
+    ///
+    /// impl<'de> DeserializeSeed<'de> for Registry<'_> {
+    ///     type Value = Option<String>;
+    ///
+    ///     fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+    ///         let id = u32::deserialize(deserializer)?;
+    ///         Ok(self.0.get(&id).cloned())
+    ///     }
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn get_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     let registry = HashMap::from([(1, "Stephanie".to_string())]);
+    ///     cookie.0.get_seed(Registry(&registry));
+    ///     HttpResponse::Ok().finish()
+    /// }
+    /// ```
+    pub fn get_seed<S, V>(&self, seed: S) -> Result<V, CookieBoxError>
+    where
+        S: for<'de> DeserializeSeed<'de, Value = V>,
+    {
+        let data = self
+            .storage
+            .request_storage
+            .borrow()
+            .get(T::COOKIE_NAME)
+            .ok_or(CookieBoxError::NotFound(T::COOKIE_NAME.to_string()))?;
+
+        let mut deserializer = serde_json::Deserializer::from_str(data.value());
+
+        seed.deserialize(&mut deserializer)
+            .and_then(|value| deserializer.end().map(|_| value))
+            .map_err(|e| {
+                CookieBoxError::Deserialization(
+                    data.value().to_string(),
+                    type_name::<V>().to_string(),
+                    e.into(),
+                )
+            })
+    }
+    /// Retrieves the data from the [Storage] request collection like `get`, deserialized into `V` which may borrow from `buffer`
+    ///
+    /// The value is copied into `buffer`, or decoded into it for binary codecs, so [Cow](std::borrow::Cow) fields marked with
+    /// `#[serde(borrow)]` come back as `Cow::Borrowed` whenever the value needs no unescaping. The buffer can be reused
+    /// across calls to save the allocations of string-heavy values. `V` is decoded with [IncomingConfig::CODEC], so
+    /// an overridden [IncomingConfig::deserialize] and [IncomingConfig::validate] are not applied, and the [ReadPolicy]
+    /// is ignored. Returns `NotFound` when the cookie is missing.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    /// use serde::Deserialize;
+    /// use std::borrow::Cow;
+    /// use std::collections::HashMap;
+    ///
+    /// #[derive(Deserialize)]
+    /// pub struct Preferences<'a> {
+    ///     #[serde(borrow)]
+    ///     theme: Cow<'a, str>,
+    /// }
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "preferences")]
+    /// pub struct PreferencesCookie;
+    ///
+    /// impl IncomingConfig for PreferencesCookie {
+    ///     // `get` needs a type that owns its data
+    ///     type Get = HashMap<String, String>;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, PreferencesCookie>);
+    ///
+    /// async fn get_theme(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     let mut buffer = Vec::new();
+    ///     match cookie.0.get_borrowed::<Preferences>(&mut buffer) {
+    ///         Ok(preferences) => HttpResponse::Ok().body(preferences.theme.into_owned()),
+    ///         Err(_) => HttpResponse::NotFound().finish(),
+    ///     }
+    /// }
+    /// ```
+    pub fn get_borrowed<'v, V: Deserialize<'v>>(
+        &self,
+        buffer: &'v mut Vec<u8>,
+    ) -> Result<V, CookieBoxError> {
+        let request_storage = self.storage.request_storage.borrow();
+        let data = request_storage
+            .get(T::COOKIE_NAME)
+            .ok_or(CookieBoxError::NotFound(T::COOKIE_NAME.to_string()))?;
+
+        T::CODEC.decode_borrowed(data.value(), buffer)
+    }
+}
+
+/// Provide methods to `insert` and `remove` a cookie instance for any generic type parameter that implements [OutgoingConfig]
+impl<'c, T: OutgoingConfig> Cookie<'c, T> {
+    /// Add a cookie to the [Storage] response collection which later attached to the HTTP response using the `Set-Cookie` header.
+    ///
+    /// Queued cookies are identified by their name, path, and domain. Inserting again with the same scope replaces the queued
+    /// cookie, value and attributes alike, while inserting with another path or domain queues a distinct cookie, and both
+    /// are emitted.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl OutgoingConfig for MyCookie {
+    ///     type Insert = String;
+    /// }
+    ///  
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn insert_cookie(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     cookie.0.insert("cookie value".to_string())?;
+    ///     Ok(HttpResponse::Ok().finish())
+    /// }
+    /// ```
+    pub fn insert(&self, value: T::Insert) -> Result<(), CookieBoxError> {
+        let attributes = &self.configured_attributes();
+
+        self.insert_with_attributes(value, attributes)
+    }
+    /// Add a cookie to the [Storage] response collection like `insert`, using `attributes` instead of the configured ones.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, OutgoingConfig};
+    /// use cookiebox::time::SignedDuration;
+    /// use cookiebox::Attributes;
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl OutgoingConfig for MyCookie {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn insert_cookie(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     // Remember the user for a week
+    ///     let attributes = MyCookie::attributes().max_age(SignedDuration::from_hours(24 * 7));
+    ///     cookie.0.insert_with("cookie value".to_string(), attributes)?;
+    ///     Ok(HttpResponse::Ok().finish())
+    /// }
+    /// ```
+    pub fn insert_with(
+        &self,
+        value: T::Insert,
+        attributes: Attributes<'c>,
+    ) -> Result<(), CookieBoxError> {
+        let attributes = attributes
+            .inherit_from(&self.storage.default_attributes)
+            .enforce_name_prefix(T::COOKIE_NAME);
+
+        self.insert_with_attributes(value, &attributes)
+    }
+    // Resolves the attributes used by `insert`, `remove`, and `discard`, filling inheriting attributes from the middleware default
+    fn configured_attributes(&self) -> Attributes<'c> {
+        let attributes = match &self.attributes {
+            Some(attributes) => attributes.clone(),
+            None => T::attributes_for_env(&self.storage.env),
+        };
+
+        attributes
+            .inherit_from(&self.storage.default_attributes)
+            .enforce_name_prefix(T::COOKIE_NAME)
+    }
+    // Builds a removal cookie scoped by `attributes`
+    fn removal_cookie(attributes: &Attributes<'c>) -> ResponseCookie<'c> {
+        removal_cookie(T::COOKIE_NAME, attributes)
+    }
+    fn insert_with_attributes(
+        &self,
+        value: T::Insert,
+        attributes: &Attributes<'c>,
+    ) -> Result<(), CookieBoxError> {
+        let data = Self::serialize_value(value)?;
+
+        self.insert_serialized(data, attributes)
+    }
+    // Serializes `value` for the response collection, recording a failure on the insert span
+    fn serialize_value(value: T::Insert) -> Result<String, CookieBoxError> {
+        T::serialize(value)
+            .and_then(|data| T::CODEC.encode(T::COOKIE_NAME, &data))
+            .inspect_err(|_| {
+                OperationSpan::cookie("insert", T::COOKIE_NAME).end(Outcome::Error);
+            })
+    }
+    // Queues the serialized value, unless its name and value exceed `T::MAX_SIZE`
+    fn insert_serialized(
+        &self,
+        data: String,
+        attributes: &Attributes<'c>,
+    ) -> Result<(), CookieBoxError> {
+        let response_cookie = ResponseCookie::new(T::COOKIE_NAME, data).set_attributes(attributes);
+
+        if let Some(limit) = T::MAX_SIZE {
+            let size = T::COOKIE_NAME.len() + response_cookie.value().len();
+            if size > limit {
+                OperationSpan::cookie("insert", T::COOKIE_NAME).end(Outcome::Error);
+                return Err(CookieBoxError::TooLarge {
+                    name: T::COOKIE_NAME.to_string(),
+                    size,
+                    limit,
+                });
+            }
+        }
+
+        self.queue(response_cookie, attributes)
+    }
+    // Queues the cookie, unless its value cannot be sent in the encoding of `T`
+    fn queue(
+        &self,
+        response_cookie: ResponseCookie<'c>,
+        attributes: &Attributes<'c>,
+    ) -> Result<(), CookieBoxError> {
+        let span = OperationSpan::cookie("insert", T::COOKIE_NAME);
+        let options = EmitOptions {
+            priority: attributes.get_priority(),
+            encoding: T::encode_value(),
+        };
+
+        if !options.encoding.accepts(response_cookie.value()) {
+            span.end(Outcome::Error);
+            return Err(CookieBoxError::InvalidRawValue(T::COOKIE_NAME.to_string()));
+        }
+
+        self.storage.queue_response_cookie(response_cookie, options);
+
+        span.end(Outcome::Ok);
+        Ok(())
+    }
+    /// Returns a copy of the cookie queued in the [Storage] response collection, e.g. by `insert` or `remove`
+    ///
+    /// The cookie is looked up by the name, path, and domain resolved like `insert`, and is returned as the middleware would
+    /// emit it, before being processed by the [Processor](biscotti::Processor). A queued removal is returned as a cookie
+    /// with an empty value that expires in the past.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl OutgoingConfig for MyCookie {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn preview_cookie(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     cookie.0.insert("Stephanie".to_string())?;
+    ///     match cookie.0.peek() {
+    ///         Some(response_cookie) => Ok(HttpResponse::Ok().body(response_cookie.to_string())),
+    ///         None => Ok(HttpResponse::NoContent().finish()),
+    ///     }
+    /// }
+    /// ```
+    pub fn peek(&self) -> Option<ResponseCookie<'c>> {
+        let id =
+            ResponseCookieId::new(T::COOKIE_NAME).set_attributes(&self.configured_attributes());
+
+        self.storage.response_storage.borrow().get(id).cloned()
+    }
+    /// Add a cookie to the [Storage] response collection like `insert`, storing `raw` verbatim instead of the serialized value
+    ///
+    /// [OutgoingConfig::serialize] is bypassed, so the caller is responsible for `raw` being in the format `get` expects. The
+    /// processor still percent-encodes, signs, or encrypts the value as configured.
+    ///
+    /// Returns [CookieBoxError::InvalidRawValue] when `raw` cannot be sent in the [ValueEncoding] of the cookie type.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "token")]
+    /// pub struct Token;
+    ///
+    /// impl OutgoingConfig for Token {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, Token>);
+    ///
+    /// async fn insert_token(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     // A token produced by another library, stored without the JSON quotes
+    ///     cookie.0.insert_raw("eyJhbGciOiJIUzI1NiJ9.e30.ZRrHA1JJJW8opsbCGfG_HACGpVUMN_a9IV7pAx_Zmeo")?;
+    ///     Ok(HttpResponse::Ok().finish())
+    /// }
+    /// ```
+    pub fn insert_raw(&self, raw: impl Into<String>) -> Result<(), CookieBoxError> {
+        let attributes = self.configured_attributes();
+
+        self.queue(
+            ResponseCookie::new(T::COOKIE_NAME, raw.into()).set_attributes(&attributes),
+            &attributes,
+        )
+    }
+    /// Add a cookie to the [Storage] response collection like `insert`, unless the serialized value equals the one sent by the client
+    ///
+    /// Returns whether the cookie was inserted. This avoids emitting a `Set-Cookie` header, and signing or encrypting it, when the
+    /// value did not change. The comparison is made against the raw request value, so it is not affected by attributes.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "theme")]
+    /// pub struct Theme;
+    ///
+    /// impl OutgoingConfig for Theme {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, Theme>);
+    ///
+    /// async fn set_theme(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     cookie.0.insert_if_changed("dark".to_string())?;
+    ///     Ok(HttpResponse::Ok().finish())
+    /// }
+    /// ```
+    pub fn insert_if_changed(&self, value: T::Insert) -> Result<bool, CookieBoxError> {
+        let data = Self::serialize_value(value)?;
+
+        let unchanged = self
+            .storage
+            .request_storage
+            .borrow()
+            .get(T::COOKIE_NAME)
+            .is_some_and(|cookie| cookie.value() == data);
+
+        if unchanged {
+            return Ok(false);
+        }
+
+        self.insert_serialized(data, &self.configured_attributes())?;
+        Ok(true)
+    }
+    /// Add a cookie holding a [random_token](crate::random_token) of `bytes` random bytes to the [Storage] response collection like `insert`
+    ///
+    /// Returns the token, e.g. to store it server-side as a session id. This is available with the `random` feature.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "__Host-session")]
+    /// pub struct SessionId;
+    ///
+    /// impl OutgoingConfig for SessionId {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, SessionId>);
+    ///
+    /// async fn login(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     let session_id = cookie.0.insert_random(32)?;
+    ///     Ok(HttpResponse::Ok().body(session_id))
+    /// }
+    /// ```
+    #[cfg(feature = "random")]
+    pub fn insert_random(&self, bytes: usize) -> Result<String, CookieBoxError>
+    where
+        T: OutgoingConfig<Insert = String>,
+    {
+        let token = crate::random_token(bytes);
+        self.insert(token.clone())?;
+        Ok(token)
+    }
+    /// Add a removal cookie to the [Storage] response collection, which later attached to the HTTP response using the `Set-Cookie` header.
+    ///
+    /// Cookie removal is determined by name, path, and domain
+    ///
+    /// Returns `true` if the request carried a cookie named `T::COOKIE_NAME`, `false` otherwise.
+    /// The removal cookie is emitted either way.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieName, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl OutgoingConfig for MyCookie {
+    ///     type Insert = String;
+    /// }
+    ///  
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn remove_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     if cookie.0.remove() {
+    ///         // The cookie was present, e.g. record the logout
+    ///     }
+    ///     HttpResponse::Ok().finish()
+    /// }
+    /// ```
+    pub fn remove(&self) -> bool {
+        let attributes = &self.configured_attributes();
+
+        self.remove_with_attributes(attributes)
+    }
+    /// Add a removal cookie to the [Storage] response collection like `remove`, scoped by `attributes` instead of the configured ones
+    ///
+    /// Browsers only remove a cookie when the name, path, and domain of the removal cookie all match the ones the cookie was
+    /// set with. Use this to target a cookie that was inserted with a path or domain computed at request time, e.g. with
+    /// `insert_with`. Like `insert_with`, `attributes` inherit from the middleware default and honor the name prefix.
+    ///
+    /// Returns `true` if the request carried a cookie named `T::COOKIE_NAME`, `false` otherwise.
+    ///
+    /// In debug builds, a `warn` is logged when the path or domain of `attributes` differs from the ones `insert` uses, set
+    /// with `with_attributes` or resolved from [OutgoingConfig::attributes_for_env], and no cookie was queued at that scope
+    /// in the same request, since the browser keeps a cookie removed at the wrong scope. The request does not carry the
+    /// scope a cookie was set with, so a cookie inserted at a computed scope in an earlier request is reported as well,
+    /// and the warning can be ignored then.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieName, OutgoingConfig};
+    /// use cookiebox::Attributes;
+    /// use actix_web::{web, HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "workspace-session")]
+    /// pub struct WorkspaceSession;
+    ///
+    /// impl OutgoingConfig for WorkspaceSession {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, WorkspaceSession>);
+    ///
+    /// async fn logout(cookie: CookieCollection<'_>, workspace: web::Path<String>) -> HttpResponse {
+    ///     // The session was set at the path of the workspace on login
+    ///     let attributes = WorkspaceSession::attributes().path(format!("/workspaces/{workspace}"));
+    ///     cookie.0.remove_with(attributes);
+    ///     HttpResponse::Ok().finish()
+    /// }
+    /// ```
+    pub fn remove_with(&self, attributes: Attributes<'c>) -> bool {
+        let attributes = attributes
+            .inherit_from(&self.storage.default_attributes)
+            .enforce_name_prefix(T::COOKIE_NAME);
+
+        self.remove_with_attributes(&attributes)
+    }
+    fn remove_with_attributes(&self, attributes: &Attributes<'c>) -> bool {
+        #[cfg(debug_assertions)]
+        if let Some(message) = self.removal_scope_mismatch(attributes) {
+            log::warn!("{message}");
+        }
+
+        let span = OperationSpan::cookie("remove", T::COOKIE_NAME);
+        let was_present = self
+            .storage
+            .request_storage
+            .borrow()
+            .get(T::COOKIE_NAME)
+            .is_some();
+
+        let removal_cookie = Self::removal_cookie(attributes);
+
+        // Inserting the removal cookie will replace any cookie with the same name, path, and domain
+        self.storage
+            .queue_response_cookie(removal_cookie, EmitOptions::default());
+
+        span.end(Outcome::Ok);
+
+        was_present
+    }
+    // Describes why a removal scoped by `attributes` is unlikely to match the cookie set by `insert`, if it is
+    #[cfg(debug_assertions)]
+    fn removal_scope_mismatch(&self, attributes: &Attributes<'c>) -> Option<String> {
+        let removal_id = ResponseCookieId::new(T::COOKIE_NAME).set_attributes(attributes);
+
+        let insert_id =
+            ResponseCookieId::new(T::COOKIE_NAME).set_attributes(&self.configured_attributes());
+
+        // A cookie queued at the same scope in this request is matched by the removal
+        let queued = self
+            .storage
+            .response_storage
+            .borrow()
+            .get(removal_id.clone())
+            .is_some();
+
+        if removal_id == insert_id || queued {
+            return None;
+        }
+
+        Some(format!(
+            "`{}` is removed with path {:?} and domain {:?}, but inserted with path {:?} and domain {:?}, so the browser may keep it",
+            T::COOKIE_NAME,
+            removal_id.path(),
+            removal_id.domain(),
+            insert_id.path(),
+            insert_id.domain(),
+        ))
+    }
+    /// Add a removal cookie for each of `paths` to the [Storage] response collection
+    ///
+    /// The domain is taken from the attributes used by `remove`, while the path of each removal cookie is overridden.
+    /// This clears copies of the cookie left at paths it was set at over time, regardless of its current path.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieName, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl OutgoingConfig for MyCookie {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn remove_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
+    ///     cookie.0.remove_paths(&["/", "/admin", "/api"]);
+    ///     HttpResponse::Ok().finish()
+    /// }
+    /// ```
+    pub fn remove_paths(&self, paths: &[&str]) {
+        let attributes = &self.configured_attributes();
+
+        for path in paths {
+            let removal_cookie = Self::removal_cookie(attributes).set_path(path.to_string());
+
+            self.storage
+                .queue_response_cookie(removal_cookie, EmitOptions::default());
+        }
+    }
+    /// Discard a cookie from the response collection [Storage] only
+    ///
+    /// Discarding a cookie is determined by name, path, and domain
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl OutgoingConfig for MyCookie {
+    ///     type Insert = String;
+    /// }
+    ///  
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
+    ///
+    /// async fn discard_cookie(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     cookie.0.insert("Stephanie".to_string())?;
+    ///     cookie.0.discard();
+    ///     Ok(HttpResponse::Ok().finish())
+    /// }
+    /// ```
+    pub fn discard(&self) {
+        let discard_id = ResponseCookieId::new(T::COOKIE_NAME);
+
+        let attributes = &self.configured_attributes();
+
+        // This sets the path and domain only
+        let discard_id = discard_id.set_attributes(attributes);
+
+        self.storage
+            .response_storage
+            .borrow_mut()
+            .discard(discard_id);
+    }
+    /// Discard the pending cookie from the response collection [Storage] like `discard`, only if `predicate` returns `true` for it
+    ///
+    /// The pending cookie is looked up like `peek`, and is passed to `predicate` before being processed by the
+    /// [Processor](biscotti::Processor). Returns whether the cookie was discarded, `false` when no cookie is pending.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "csrf-token")]
+    /// pub struct CsrfToken;
+    ///
+    /// impl OutgoingConfig for CsrfToken {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, CsrfToken>);
+    ///
+    /// async fn submit(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     // Insert a tentative token, then keep the current one if the request did not need a rotation
+    ///     cookie.0.insert("tentative".to_string())?;
+    ///     let rotate = false;
+    ///     cookie.0.discard_if(|_| !rotate);
+    ///     Ok(HttpResponse::Ok().finish())
+    /// }
+    /// ```
+    pub fn discard_if(&self, predicate: impl FnOnce(&ResponseCookie<'c>) -> bool) -> bool {
+        // The storage is not borrowed while `predicate` runs, so it can use other cookies
+        let discard = self.peek().is_some_and(|cookie| predicate(&cookie));
+
+        if discard {
+            self.discard();
+        }
+
+        discard
+    }
+}
+
+/// Provide methods to read and write a cookie instance in one step for any generic type parameter that implements both
+/// [IncomingConfig] and [OutgoingConfig] with the same `Get` and `Insert` type
+impl<'c, T> Cookie<'c, T>
+where
+    T: IncomingConfig + OutgoingConfig<Insert = <T as IncomingConfig>::Get>,
+{
+    /// Reads the current value, passes it to `f`, and inserts the returned value
+    ///
+    /// The closure receives `None` when the cookie is missing and also when it fails to deserialize, so a malformed
+    /// cookie is overwritten rather than reported.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, IncomingConfig, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "visits")]
+    /// pub struct Visits;
+    ///
+    /// impl IncomingConfig for Visits {
+    ///     type Get = u32;
+    /// }
+    /// impl OutgoingConfig for Visits {
+    ///     type Insert = u32;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, Visits>);
+    ///
+    /// async fn count_visit(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     cookie.0.update(|visits| visits.unwrap_or(0) + 1)?;
+    ///     Ok(HttpResponse::Ok().finish())
+    /// }
+    /// ```
+    pub fn update<F>(&self, f: F) -> Result<(), CookieBoxError>
+    where
+        F: FnOnce(Option<<T as IncomingConfig>::Get>) -> T::Insert,
+    {
+        let current = self.get().ok();
+        self.insert(f(current))
+    }
+    /// Retrieves the data like `get`, or inserts the value returned by `f` and returns it when the cookie is missing
+    ///
+    /// `f` is only called when `get` returns `NotFound`, the inserted value then reaches the client with the response.
+    /// Other errors, such as a malformed cookie, are returned as is without inserting anything.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, IncomingConfig, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "theme")]
+    /// pub struct Theme;
+    ///
+    /// impl IncomingConfig for Theme {
+    ///     type Get = String;
+    /// }
+    /// impl OutgoingConfig for Theme {
+    ///     type Insert = String;
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, Theme>);
+    ///
+    /// async fn index(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     // First visits are given the light theme
+    ///     let theme = cookie.0.get_or_insert_with(|| "light".to_string())?;
+    ///     Ok(HttpResponse::Ok().body(theme))
+    /// }
+    /// ```
+    pub fn get_or_insert_with<F>(&self, f: F) -> Result<<T as IncomingConfig>::Get, CookieBoxError>
+    where
+        F: FnOnce() -> T::Insert,
+        <T as IncomingConfig>::Get: Clone,
+    {
+        match self.get() {
+            Err(CookieBoxError::NotFound(_)) => {
+                let value = f();
+                self.insert(value.clone())?;
+                Ok(value)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Provide a method to upgrade cookies written in a previous format for any generic type parameter that implements [LegacyFormat]
+impl<'c, T> Cookie<'c, T>
+where
+    T: LegacyFormat + OutgoingConfig<Insert = <T as IncomingConfig>::Get>,
+    <T as IncomingConfig>::Get: Clone,
+{
+    /// Retrieves the data like `get`, falling back to [LegacyFormat::deserialize_legacy] when the value is not in the current format
+    ///
+    /// A value read in the legacy format is inserted back, so the client receives it in the current format. A value that cannot
+    /// be read in either format returns the error of `get`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::{cookie, FromRequest};
+    /// use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, IncomingConfig, LegacyFormat, OutgoingConfig};
+    /// use actix_web::{HttpResponse, HttpMessage};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Clone)]
+    /// pub struct Preferences {
+    ///     theme: String,
+    /// }
+    ///
+    /// // Set up generic cookie type
+    /// #[cookie(name = "preferences")]
+    /// pub struct PreferencesCookie;
+    ///
+    /// impl IncomingConfig for PreferencesCookie {
+    ///     type Get = Preferences;
+    /// }
+    /// impl OutgoingConfig for PreferencesCookie {
+    ///     type Insert = Preferences;
+    /// }
+    /// impl LegacyFormat for PreferencesCookie {
+    ///     // The theme used to be stored as a plain string
+    ///     fn deserialize_legacy(value: &str) -> Result<Preferences, CookieBoxError> {
+    ///         serde_json::from_str(value)
+    ///             .map(|theme| Preferences { theme })
+    ///             .map_err(|e| CookieBoxError::Deserialization(value.to_string(), "String".to_string(), e.into()))
+    ///     }
+    /// }
+    ///
+    /// // Use macro to implement `FromRequest` for cookie collection struct
+    /// #[derive(FromRequest)]
+    /// pub struct CookieCollection<'c>(Cookie<'c, PreferencesCookie>);
+    ///
+    /// async fn get_preferences(cookie: CookieCollection<'_>) -> Result<HttpResponse, CookieBoxError> {
+    ///     cookie.0.get_migrating()?;
+    ///     Ok(HttpResponse::Ok().finish())
+    /// }
+    /// ```
+    pub fn get_migrating(&self) -> Result<<T as IncomingConfig>::Get, CookieBoxError> {
+        match self.get() {
+            Err(CookieBoxError::Deserialization(value, type_name, error)) => {
+                match T::deserialize_legacy(&value) {
+                    Ok(data) => {
+                        Self::validate(&data)?;
+                        self.insert(data.clone())?;
+                        Ok(data)
+                    }
+                    Err(_) => Err(CookieBoxError::Deserialization(value, type_name, error)),
+                }
+            }
+            result => result,
+        }
+    }
+}
+
+/// A cookie value tagged with a discriminator, so same-named cookies returned by `get_all` can be attributed
+///
+/// Request cookies do not carry their path or domain, so a value set at `/admin` and one set at `/` look the same when read back.
+/// Using [Discriminated] as both the `Insert` and `Get` type stores the discriminator along with the value.
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{CookieName, Discriminated, IncomingConfig, OutgoingConfig};
+///
+/// #[cookie(name = "__my-cookie")]
+/// pub struct MyCookie;
+///
+/// impl IncomingConfig for MyCookie {
+///     type Get = Discriminated<String>;
+/// }
+/// impl OutgoingConfig for MyCookie {
+///     type Insert = Discriminated<String>;
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Discriminated<V> {
+    pub discriminator: String,
+    pub value: V,
+}
+
+impl<V> Discriminated<V> {
+    /// Create a [Discriminated] value
+    pub fn new(discriminator: impl Into<String>, value: V) -> Self {
+        Discriminated {
+            discriminator: discriminator.into(),
+            value,
+        }
+    }
+}
+
+/// The size in bytes of the largest cookie browsers are guaranteed to store, a limit for [OutgoingConfig::MAX_SIZE]
+pub const DEFAULT_MAX_COOKIE_SIZE: usize = 4096;
+
+/// Provide internal customization for `insert` and `remove` methods in [Cookie].
+///
+/// The `insert` and `remove` will be available when types that implement this trait is used as generic parameters for `Cookie`.
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{CookieName, OutgoingConfig};
+///
+/// // Define a generic cookie type
+/// #[cookie(name = "__my-cookie")]
+/// pub struct MyCookie;
+///
+/// impl OutgoingConfig for MyCookie {
+///    // Configure the insert type
+///    type Insert = String;
+///    
+///    // The default serialization is used here, if customization is needed, implement the `serialize` method.
+///    
+///    // The default attributes is used here which consists of http-only: true, SameSite: Lax, and
+///    // path: "/"
+/// }
+/// ```
+/// # Extension points
+/// `Insert` is required. `serialize`, `attributes`, and `attributes_for_env` are meant to be overridden.
+/// If `serialize` is overridden, [IncomingConfig::deserialize] should be overridden to read the same format back.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be written as a cookie",
+    label = "`{Self}` does not implement `OutgoingConfig`",
+    note = "implement `OutgoingConfig` for `{Self}` to enable `insert`, `remove`, and `discard`"
+)]
+pub trait OutgoingConfig: CookieName {
+    /// The serialization type when inserting a cookie to storage
+    type Insert: Serialize;
+
+    /// The format the cookie value is written in, [Codec::Json] by default
+    ///
+    /// This should match [IncomingConfig::CODEC] when the cookie type implements both traits.
+    const CODEC: Codec = Codec::Json;
+
+    /// The maximum size in bytes of the cookie written by `insert`, `None` by default
+    ///
+    /// Browsers silently drop cookies larger than about 4096 bytes, which is [DEFAULT_MAX_COOKIE_SIZE]. When set, `insert` and
+    /// the methods built on it return [CookieBoxError::TooLarge] instead of queuing a cookie whose name and value add up to
+    /// more than the limit, like browsers count it. Attributes do not count toward the limit. The size is measured before
+    /// the [Processor](crate::Processor) runs, and signing or encrypting grows the value, so leave room for it in the limit.
+    /// `insert_raw` is not checked.
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{CookieName, DEFAULT_MAX_COOKIE_SIZE, OutgoingConfig};
+    /// use std::collections::HashMap;
+    ///
+    /// #[cookie(name = "preferences")]
+    /// pub struct Preferences;
+    ///
+    /// impl OutgoingConfig for Preferences {
+    ///     type Insert = HashMap<String, String>;
+    ///
+    ///     const MAX_SIZE: Option<usize> = Some(DEFAULT_MAX_COOKIE_SIZE);
+    /// }
+    /// ```
+    const MAX_SIZE: Option<usize> = None;
+
+    /// Provides default serialization for a cookie. This can be overwriting
+    ///
+    /// Return [CookieBoxError::Serialization] when the value cannot be represented, `insert` then returns the error instead
+    /// of queuing the cookie. Implementations written against the infallible signature migrate by wrapping the value in `Ok`.
+    fn serialize(values: Self::Insert) -> Result<Value, CookieBoxError> {
+        serde_json::to_value(values)
+            .map_err(|e| CookieBoxError::Serialization(Self::COOKIE_NAME.to_string(), e.into()))
+    }
+
+    /// Provides preset attributes for a cookie. This can be overwriting
+    ///
+    /// Defaults to [Attributes::default], with the `same_site` and `max_age` set in `#[cookie(...)]` if any.
+    /// Return [Attributes::inherit] to build on the default attributes of the middleware instead of [Attributes::default].
+    fn attributes<'c>() -> Attributes<'c> {
+        Self::__cookiebox_default_attributes()
+    }
+
+    /// Provides attributes for a cookie based on the [CookieEnv] of the current request. This can be overwriting
+    ///
+    /// This is what `insert`, `remove`, and `discard` use to resolve attributes, it defaults to `attributes` and ignores `env`.
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{CookieEnv, CookieName, OutgoingConfig};
+    /// use cookiebox::Attributes;
+    ///
+    /// #[cookie(name = "__my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl OutgoingConfig for MyCookie {
+    ///     type Insert = String;
+    ///
+    ///     // Only mark the cookie secure when the request came in over https
+    ///     fn attributes_for_env<'c>(env: &CookieEnv) -> Attributes<'c> {
+    ///         Self::attributes().secure(env.is_secure())
+    ///     }
+    /// }
+    /// ```
+    fn attributes_for_env<'c>(env: &CookieEnv) -> Attributes<'c> {
+        let _ = env;
+        Self::attributes()
+    }
+    /// Provides the encoding of the value in the `Set-Cookie` header, [ValueEncoding::PercentEncoded] by default. This can be overwriting
+    ///
+    /// See [ValueEncoding::Raw] for the caveats of sending the value as is.
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{CookieName, OutgoingConfig, ValueEncoding};
+    ///
+    /// #[cookie(name = "theme")]
+    /// pub struct Theme;
+    ///
+    /// impl OutgoingConfig for Theme {
+    ///     type Insert = String;
+    ///
+    ///     // Sent as `theme="dark"` rather than `theme=%22dark%22`
+    ///     fn encode_value() -> ValueEncoding {
+    ///         ValueEncoding::Raw
+    ///     }
+    /// }
+    /// ```
+    fn encode_value() -> ValueEncoding {
+        ValueEncoding::PercentEncoded
+    }
+}
+
+/// Runtime context of the current request used to resolve attributes in [OutgoingConfig::attributes_for_env]
+///
+/// [CookieEnv] is populated by the middleware for every request.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CookieEnv {
+    is_secure: bool,
+    host: String,
+    path: String,
+    base_path: String,
+}
+
+impl CookieEnv {
+    pub(crate) fn new(
+        is_secure: bool,
+        host: &str,
+        path: impl Into<String>,
+        base_path: impl Into<String>,
+    ) -> Self {
+        // The port follows the last `:`, unless it is part of a bracketed IPv6 address
+        let host = match host.rsplit_once(':') {
+            Some((hostname, port))
+                if !port.contains(']') && port.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                hostname
+            }
+            _ => host,
+        };
+
+        CookieEnv {
+            is_secure,
+            host: host.to_string(),
+            path: path.into(),
+            base_path: base_path.into(),
+        }
+    }
+    /// Returns `true` if the request was made over https
+    pub fn is_secure(&self) -> bool {
+        self.is_secure
+    }
+    /// Returns the host of the request without the port, e.g. `example.com` for `example.com:8080`
+    ///
+    /// The host is sent by the client, so check it against the hosts the application serves before using it as `Domain`.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+    /// Returns the full path of the request, e.g. `/api/users/42`
+    ///
+    /// A cookie scoped to it is only sent back for this path and the ones below it, so prefer [CookieEnv::base_path] for
+    /// cookies shared by the application.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Returns the path the middleware is mounted at, e.g. `/api` for a middleware wrapping `web::scope("/api")`
+    ///
+    /// This is `/` for a middleware wrapping the whole [App](actix_web::App).
+    pub fn base_path(&self) -> &str {
+        &self.base_path
+    }
+}
+
+/// Provide internal customization for `get` and `get_all` methods in [Cookie].
+///
+/// The `get` and `get_all` will be available when types that implement this trait is used as generic parameters for `Cookie`.
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{CookieName, IncomingConfig};
+/// // Define a generic cookie type struct
+/// #[cookie(name = "__my-cookie")]
+/// pub struct MyCookie;
+///
+/// impl IncomingConfig for MyCookie {
+///     // Configure the get return type
+///     type Get = String;
+///
+///     // The default deserialization is used here, if customization is needed, implement the `deserialize` method.
+/// }
+/// ```
+/// # Extension points
+/// `Get` is required and must be [DeserializeOwned]. `deserialize`, `validate`, `sliding_expiration`, and `read_policy` are meant to be overridden.
+/// If `deserialize` is overridden, [OutgoingConfig::serialize] should be overridden to write the same format.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be read as a cookie",
+    label = "`{Self}` does not implement `IncomingConfig`",
+    note = "implement `IncomingConfig` for `{Self}` to enable `get` and `get_all`"
+)]
+pub trait IncomingConfig: CookieName {
+    /// The deserialization type when getting a cookie from storage
+    type Get: DeserializeOwned;
+
+    /// The format the cookie value is read in, [Codec::Json] by default
+    ///
+    /// This should match [OutgoingConfig::CODEC] when the cookie type implements both traits.
+    const CODEC: Codec = Codec::Json;
+
+    /// Provides default deserialization for a cookie. This can be overwriting
+    fn deserialize(value: &str) -> Result<Self::Get, CookieBoxError> {
+        Self::CODEC.decode(value)
+    }
+
+    /// Checks a deserialized value before `get` returns it. This can be overwriting
+    ///
+    /// This is meant for values that are well-formed but semantically invalid, e.g. a token whose payload has expired.
+    /// `get`, `get_all`, and the methods built on them return [CookieBoxError::Invalid] when the check fails, other errors
+    /// returned here are converted into it. Every value is accepted by default.
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{CookieBoxError, CookieName, IncomingConfig};
+    ///
+    /// #[cookie(name = "page-size")]
+    /// pub struct PageSize;
+    ///
+    /// impl IncomingConfig for PageSize {
+    ///     type Get = u32;
+    ///
+    ///     fn validate(value: &u32) -> Result<(), CookieBoxError> {
+    ///         match value {
+    ///             10..=100 => Ok(()),
+    ///             _ => Err(CookieBoxError::Invalid(
+    ///                 Self::COOKIE_NAME.to_string(),
+    ///                 format!("{value} is not between 10 and 100"),
+    ///             )),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    fn validate(value: &Self::Get) -> Result<(), CookieBoxError> {
+        let _ = value;
+        Ok(())
+    }
+
+    /// Provides sliding expiration for a cookie. This can be overwriting
+    ///
+    /// When `Some`, every successful `get` re-emits the cookie with the returned attributes, so the `max_age` set in them
+    /// is refreshed on each read. The attributes should have the same path and domain the cookie was set with.
+    /// The refresh is skipped if the cookie is inserted or removed during the same request.
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{CookieName, IncomingConfig, OutgoingConfig};
+    /// use cookiebox::Attributes;
+    /// use cookiebox::time::SignedDuration;
+    ///
+    /// #[cookie(name = "__session")]
+    /// pub struct Session;
+    ///
+    /// impl OutgoingConfig for Session {
+    ///     type Insert = String;
+    /// }
+    /// impl IncomingConfig for Session {
+    ///     type Get = String;
+    ///
+    ///     fn sliding_expiration<'c>() -> Option<Attributes<'c>> {
+    ///         Some(Self::attributes().max_age(SignedDuration::from_mins(30)))
+    ///     }
+    /// }
+    /// ```
+    fn sliding_expiration<'c>() -> Option<Attributes<'c>> {
+        None
+    }
+
+    /// Overrides the [ReadPolicy] of the [Storage] for this cookie
+    ///
+    /// Like the storage-wide policy, it only affects `get` and the methods built on it, see [ReadPolicy].
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{CookieName, IncomingConfig, ReadPolicy};
+    ///
+    /// #[cookie(name = "my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// impl IncomingConfig for MyCookie {
+    ///     type Get = String;
+    ///
+    ///     // `get` returns the value inserted in the same request, if any
+    ///     fn read_policy() -> Option<ReadPolicy> {
+    ///         Some(ReadPolicy::Writes)
+    ///     }
+    /// }
+    /// ```
+    fn read_policy() -> Option<ReadPolicy> {
+        None
+    }
+}
+
+/// Decides what `get` returns when a cookie is both read and written in the same request
 ///
-/// impl IncomingConfig for MyCookie {
-///     // Configure the get return type
-///     type Get = String;
-/// }
-/// ```
-pub trait IncomingConfig: CookieName {
-    /// The deserialization type when getting a cookie from storage
-    type Get: DeserializeOwned;
+/// The policy is set for every cookie with [CookieMiddleware::read_policy](crate::CookieMiddleware::read_policy) and
+/// can be overridden per cookie type with [IncomingConfig::read_policy].
+///
+/// It applies to [Cookie::get] and the methods built on it: [Cookie::get_optional], [Cookie::update],
+/// [Cookie::get_or_insert_with] and [Cookie::get_migrating]. The raw and multi-value readers, `contains`, `get_all`,
+/// `get_all_iter`, `get_bytes`, `get_raw`, `with_value`, `value_equals`, `get_seed` and `get_borrowed`, always read the
+/// cookies sent by the client.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadPolicy {
+    /// Reads reflect the cookies sent by the client, writes queued in the same request are ignored
+    #[default]
+    Client,
+    /// Reads reflect writes queued in the same request first, then fall back to the cookies sent by the client
+    ///
+    /// A pending removal reads as `NotFound`. If the cookie is queued at several paths, which one is read is unspecified.
+    Writes,
+}
+
+/// Reads values of a cookie type written in a previous format, to migrate them with [Cookie::get_migrating]
+///
+/// [IncomingConfig::deserialize] reads the current format, while `deserialize_legacy` reads the one it replaces. Once the
+/// old cookies have expired, the implementation can be removed.
+pub trait LegacyFormat: IncomingConfig {
+    /// Deserializes a raw value written in the previous format
+    fn deserialize_legacy(value: &str) -> Result<Self::Get, CookieBoxError>;
+}
+
+/// This is the base implementation of a cookie type
+///
+/// This is either implemented manually or with macro `#[Cookie(name = "...")]`
+///
+/// [IncomingConfig] and [OutgoingConfig] require it, so a cookie type always has a name.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a cookie type",
+    label = "`{Self}` does not implement `CookieName`",
+    note = "add `#[cookie(name = \"...\")]` to `{Self}` or implement `CookieName` manually"
+)]
+pub trait CookieName {
+    const COOKIE_NAME: &'static str;
+
+    /// The attributes set with `same_site` and `max_age` in `#[cookie(...)]`, which [OutgoingConfig::attributes] defaults to
+    ///
+    /// This lives on [CookieName] rather than [OutgoingConfig] so the macro can override it without clashing with an
+    /// `attributes` written by hand, which always takes precedence.
+    #[doc(hidden)]
+    fn __cookiebox_default_attributes<'c>() -> Attributes<'c> {
+        Attributes::default()
+    }
 }
 
-/// This is the base implementation of a cookie type
-///
-/// This is either implemented manually or with macro `#[Cookie(name = "...")]`
-pub trait CookieName {
-    const COOKIE_NAME: &'static str;
-}
+/// Marks a cookie type that must be signed by the [Processor](crate::Processor)
+///
+/// This is implemented with macro `#[cookie(name = "...", signed)]` and checked with [CookieMiddleware::require_signed](crate::CookieMiddleware::require_signed).
+pub trait RequiresSigning: CookieName {}
+
+/// Marks a cookie type that must be encrypted by the [Processor](crate::Processor)
+///
+/// This is implemented with macro `#[cookie(name = "...", encrypted)]` and checked with [CookieMiddleware::require_encrypted](crate::CookieMiddleware::require_encrypted).
+pub trait RequiresEncryption: CookieName {}
+
+#[cfg(test)]
+mod tests {
+    use crate::attributes::AttributesSetter;
+    use crate::cookiebox_macros::cookie;
+    use crate::cookies::{
+        Cookie, CookieBoxError, CookieEnv, CookieName, Discriminated, IncomingConfig, LegacyFormat,
+        OutgoingConfig, ReadPolicy, ValueEncoding,
+    };
+    use crate::test_util::assert_cookie;
+    use crate::time::{SignedDuration, Zoned, civil::date, tz::TimeZone};
+    use crate::{Attributes, SameSite, Storage};
+    use biscotti::{RequestCookie, ResponseCookie, ResponseCookieId};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use std::borrow::Cow;
+    use std::rc::Rc;
+
+    // Cookie types
+    #[cookie(name = "type_a")]
+    pub struct TypeA;
+    #[cookie(name = "type_b")]
+    pub struct TypeB;
+    #[cookie(name = "type_c")]
+    pub struct TypeC;
+    #[cookie(name = "type_d")]
+    pub struct TypeD;
+    #[cookie(name = "type_e")]
+    pub struct TypeE;
+    #[cookie(name = "type_f")]
+    pub struct TypeF;
+    #[cookie(name = "type_g")]
+    pub struct TypeG;
+    #[cookie(name = "type_h")]
+    pub struct TypeH;
+    #[cookie(name = "type_i")]
+    pub struct TypeI;
+    #[cookie(name = "__Host-type_j")]
+    pub struct TypeJ;
+    #[cookie(name = "__Secure-type_k")]
+    pub struct TypeK;
+    #[cookie(name = "type_l")]
+    pub struct TypeL;
+    #[cookie(name = "type_m")]
+    pub struct TypeM;
+    #[cookie(name = "type_n")]
+    pub struct TypeN;
+    #[cookie(name = "type_o")]
+    pub struct TypeO;
+    #[cookie(name = "type_p")]
+    pub struct TypeP;
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+    pub struct GetType {
+        name: String,
+    }
+
+    // read and write for type a
+    impl OutgoingConfig for TypeA {
+        type Insert = GetType;
+    }
+    impl IncomingConfig for TypeA {
+        type Get = GetType;
+    }
+
+    // write only for type l, JSON object keys must be strings so the default serialization fails
+    impl OutgoingConfig for TypeL {
+        type Insert = std::collections::HashMap<(i32, i32), i32>;
+    }
+
+    // write only for type m, with a size limit
+    impl OutgoingConfig for TypeM {
+        type Insert = String;
+
+        const MAX_SIZE: Option<usize> = Some(64);
+    }
+
+    // write only for type p, sent without percent-encoding
+    impl OutgoingConfig for TypeP {
+        type Insert = String;
+
+        fn encode_value() -> ValueEncoding {
+            ValueEncoding::Raw
+        }
+    }
+
+    // read only for type n, with a validation hook
+    impl IncomingConfig for TypeN {
+        type Get = i32;
+
+        fn validate(value: &i32) -> Result<(), CookieBoxError> {
+            match value {
+                0.. => Ok(()),
+                // other errors are converted into `Invalid`
+                -9 => Err(CookieBoxError::NotFound("other".to_string())),
+                _ => Err(CookieBoxError::Invalid(
+                    Self::COOKIE_NAME.to_string(),
+                    "must not be negative".to_string(),
+                )),
+            }
+        }
+    }
+
+    // borrowing target for `get_borrowed`
+    #[derive(Deserialize, Debug, PartialEq)]
+    pub struct CowType<'a> {
+        #[serde(borrow)]
+        name: Cow<'a, str>,
+    }
+
+    // read only for type o, `get` requires an owned type, so the `Cow` cannot borrow
+    #[derive(Deserialize, Debug, PartialEq)]
+    pub struct OwnedCowType {
+        name: Cow<'static, str>,
+    }
+    impl IncomingConfig for TypeO {
+        type Get = OwnedCowType;
+    }
+
+    // read and write for type i, which used to be stored as a plain string
+    impl OutgoingConfig for TypeI {
+        type Insert = GetType;
+    }
+    impl IncomingConfig for TypeI {
+        type Get = GetType;
+    }
+    impl LegacyFormat for TypeI {
+        fn deserialize_legacy(value: &str) -> Result<GetType, CookieBoxError> {
+            serde_json::from_str(value)
+                .map(|name| GetType { name })
+                .map_err(|e| {
+                    CookieBoxError::Deserialization(
+                        value.to_string(),
+                        "String".to_string(),
+                        e.into(),
+                    )
+                })
+        }
+    }
+
+    // write for the prefixed types j and k, with attributes that conflict with their prefix
+    impl OutgoingConfig for TypeJ {
+        type Insert = String;
+
+        fn attributes<'c>() -> Attributes<'c> {
+            Attributes::default().path("/admin").domain("example.com")
+        }
+    }
+    impl OutgoingConfig for TypeK {
+        type Insert = String;
+
+        fn attributes<'c>() -> Attributes<'c> {
+            Attributes::default().path("/admin").secure(false)
+        }
+    }
+
+    // read and write for type b
+    impl OutgoingConfig for TypeB {
+        type Insert = (String, i32);
+
+        fn serialize(values: Self::Insert) -> Result<serde_json::Value, CookieBoxError> {
+            Ok(json!({
+                "name": format!("{} is {}", values.0, values.1)
+            }))
+        }
+    }
+    impl IncomingConfig for TypeB {
+        type Get = GetType;
+    }
+
+    // read and write for type c
+    impl OutgoingConfig for TypeC {
+        type Insert = GetType;
+
+        fn attributes<'c>() -> Attributes<'c> {
+            // Expiration has an internal From impl for Into<Option<Zoned>
+            let date = date(2024, 1, 15)
+                .at(0, 0, 0, 0)
+                .to_zoned(TimeZone::UTC)
+                .unwrap();
+
+            Attributes::new()
+                .path("/some-path")
+                .domain("..example.com")
+                .same_site(SameSite::Lax)
+                .secure(true)
+                .http_only(true)
+                .partitioned(true)
+                .expires(date)
+                .max_age(SignedDuration::from_hours(10))
+        }
+    }
+    impl IncomingConfig for TypeC {
+        type Get = GetType;
+    }
+
+    // read and write for type d
+    impl OutgoingConfig for TypeD {
+        type Insert = GetType;
+
+        fn attributes<'c>() -> Attributes<'c> {
+            Attributes::new().permanent(true)
+        }
+    }
+    impl IncomingConfig for TypeD {
+        type Get = GetType;
+    }
+
+    // read only for type e
+    impl IncomingConfig for TypeE {
+        type Get = GetType;
+
+        fn deserialize(value: &str) -> Result<Self::Get, CookieBoxError> {
+            match value.split_once('=') {
+                Some(("name", name)) => Ok(GetType {
+                    name: name.to_string(),
+                }),
+                _ => Err(CookieBoxError::Deserialization(
+                    value.to_string(),
+                    "GetType".to_string(),
+                    "expected `name=<value>`".into(),
+                )),
+            }
+        }
+    }
+
+    // write only for type f
+    impl OutgoingConfig for TypeF {
+        type Insert = GetType;
+
+        fn attributes_for_env<'c>(env: &CookieEnv) -> Attributes<'c> {
+            Attributes::new()
+                .path(env.base_path().to_string())
+                .domain(env.host().to_string())
+                .secure(env.is_secure())
+        }
+    }
+
+    // read and write for type g
+    impl OutgoingConfig for TypeG {
+        type Insert = Discriminated<String>;
+    }
+    impl IncomingConfig for TypeG {
+        type Get = Discriminated<String>;
+    }
+
+    // read and write for type h, reads reflect writes
+    impl OutgoingConfig for TypeH {
+        type Insert = GetType;
+    }
+    impl IncomingConfig for TypeH {
+        type Get = GetType;
+
+        fn read_policy() -> Option<ReadPolicy> {
+            Some(ReadPolicy::Writes)
+        }
+    }
+
+    #[test]
+    fn get() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
+        let get_type_value = GetType {
+            name: "some value".to_string(),
+        };
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        let typed_request_value = cookie.get();
+
+        assert!(typed_request_value.is_ok());
+        assert_eq!(typed_request_value, Ok(get_type_value));
+    }
+    #[test]
+    fn get_optional() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(cookie.get_optional(), Ok(None));
+
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        assert_eq!(
+            cookie.get_optional(),
+            Ok(Some(GetType {
+                name: "some value".to_string(),
+            }))
+        );
+    }
+    #[test]
+    fn get_after_insert_read_policy() {
+        // Set up
+        // Initialize storage
+        let mut storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "client" }"#);
+        let client_value = GetType {
+            name: "client".to_string(),
+        };
+        let written_value = GetType {
+            name: "written".to_string(),
+        };
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Reads reflect the client by default
+        let cookie = Cookie::<TypeA>::new(&storage);
+        cookie.insert(written_value.clone()).unwrap();
+
+        assert_eq!(cookie.get(), Ok(client_value.clone()));
+
+        // Reads reflect writes when set on the storage
+        storage.read_policy = ReadPolicy::Writes;
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(cookie.get(), Ok(written_value));
+
+        // A pending removal reads as not found
+        cookie.remove();
+
+        assert_eq!(
+            cookie.get(),
+            Err(CookieBoxError::NotFound("type_a".to_string()))
+        );
+
+        // Without a pending write, reads fall back to the client
+        storage
+            .response_storage
+            .borrow_mut()
+            .discard(ResponseCookieId::new("type_a").set_path("/"));
+
+        assert_eq!(cookie.get(), Ok(client_value));
+    }
+    #[test]
+    fn get_after_insert_cookie_read_policy() {
+        // Set up
+        // Initialize storage with the default read policy
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_h", r#"{ "name": "client" }"#);
+        let written_value = GetType {
+            name: "written".to_string(),
+        };
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // The cookie type overrides the storage policy
+        let cookie = Cookie::<TypeH>::new(&storage);
+        cookie.insert(written_value.clone()).unwrap();
+
+        assert_eq!(cookie.get(), Ok(written_value));
+    }
+    #[test]
+    fn update_present() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        cookie
+            .update(|current| GetType {
+                name: format!("{} updated", current.expect("the cookie is present").name),
+            })
+            .unwrap();
+
+        let response_cookie = assert_cookie::<TypeA>(&storage);
+
+        assert_eq!(
+            response_cookie.value(),
+            json!({ "name": "some value updated" }).to_string()
+        );
+    }
+    #[test]
+    fn update_absent() {
+        // Set up
+        // Initialize storage with a malformed cookie, which reads the same as an absent one
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", "not json");
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
 
-#[cfg(test)]
-mod tests {
-    use crate::cookiebox_macros::cookie;
-    use crate::cookies::{Cookie, CookieName, IncomingConfig, OutgoingConfig};
-    use crate::time::{SignedDuration, Zoned, civil::date, tz::TimeZone};
-    use crate::{Attributes, Expiration, SameSite, Storage};
-    use biscotti::{RequestCookie, ResponseCookie};
-    use serde::{Deserialize, Serialize};
-    use serde_json::json;
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
 
-    // Cookie types
-    #[cookie(name = "type_a")]
-    pub struct TypeA;
-    #[cookie(name = "type_b")]
-    pub struct TypeB;
-    #[cookie(name = "type_c")]
-    pub struct TypeC;
-    #[cookie(name = "type_d")]
-    pub struct TypeD;
+        cookie
+            .update(|current| {
+                assert!(current.is_none());
+                GetType {
+                    name: "fresh".to_string(),
+                }
+            })
+            .unwrap();
 
-    #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
-    pub struct GetType {
-        name: String,
+        let response_cookie = assert_cookie::<TypeA>(&storage);
+
+        assert_eq!(
+            response_cookie.value(),
+            json!({ "name": "fresh" }).to_string()
+        );
     }
+    #[test]
+    fn get_or_insert_with_present() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
 
-    // read and write for type a
-    impl OutgoingConfig for TypeA {
-        type Insert = GetType;
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        let value = cookie.get_or_insert_with(|| panic!("the cookie is present"));
+
+        assert_eq!(
+            value,
+            Ok(GetType {
+                name: "some value".to_string()
+            })
+        );
+        assert!(storage.response_cookies().is_empty());
+
+        // a malformed cookie is reported rather than overwritten
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("type_a", "not json"));
+
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert!(matches!(
+            cookie.get_or_insert_with(|| panic!("the cookie is present")),
+            Err(CookieBoxError::Deserialization(..))
+        ));
+        assert!(storage.response_cookies().is_empty());
     }
-    impl IncomingConfig for TypeA {
-        type Get = GetType;
+    #[test]
+    fn get_or_insert_with_absent() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        let value = cookie.get_or_insert_with(|| GetType {
+            name: "fresh".to_string(),
+        });
+
+        assert_eq!(
+            value,
+            Ok(GetType {
+                name: "fresh".to_string()
+            })
+        );
+
+        let response_cookie = assert_cookie::<TypeA>(&storage);
+
+        assert_eq!(
+            response_cookie.value(),
+            json!({ "name": "fresh" }).to_string()
+        );
     }
+    #[cfg(feature = "random")]
+    #[test]
+    fn insert_random() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
 
-    // read and write for type b
-    impl OutgoingConfig for TypeB {
-        type Insert = (String, i32);
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeK>::new(&storage);
 
-        fn serialize(values: Self::Insert) -> serde_json::Value {
-            json!({
-                "name": format!("{} is {}", values.0, values.1)
+        let token = cookie.insert_random(16).unwrap();
+
+        assert_eq!(token.len(), 22);
+        assert_eq!(
+            assert_cookie::<TypeK>(&storage).value(),
+            format!("\"{token}\"")
+        );
+        assert_ne!(cookie.insert_random(16).unwrap(), token);
+    }
+    #[test]
+    fn host_prefix_attributes() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeJ>::new(&storage);
+
+        cookie.insert("value".to_string()).unwrap();
+
+        let response_cookie = assert_cookie::<TypeJ>(&storage);
+
+        assert_eq!(response_cookie.secure(), Some(true));
+        assert_eq!(response_cookie.path(), Some("/"));
+        assert_eq!(response_cookie.domain(), None);
+        assert_eq!(response_cookie.http_only(), Some(true));
+
+        // The removal replaces the inserted cookie and is marked secure
+        cookie.remove();
+
+        let response_cookies = storage.response_cookies();
+
+        assert_eq!(response_cookies.len(), 1);
+        assert_eq!(response_cookies[0].value(), "");
+        assert_eq!(response_cookies[0].secure(), Some(true));
+        assert_eq!(response_cookies[0].path(), Some("/"));
+    }
+    #[test]
+    fn secure_prefix_attributes() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeK>::new(&storage);
+
+        cookie
+            .insert_with("value".to_string(), Attributes::new().path("/api"))
+            .unwrap();
+
+        let response_cookie = &storage.response_cookies()[0];
+
+        // Only `Secure` is required, the path is kept
+        assert_eq!(response_cookie.secure(), Some(true));
+        assert_eq!(response_cookie.path(), Some("/api"));
+
+        let storage = Storage::new();
+        let cookie = Cookie::<TypeK>::new(&storage);
+
+        cookie.insert("value".to_string()).unwrap();
+
+        assert_eq!(assert_cookie::<TypeK>(&storage).secure(), Some(true));
+    }
+    #[test]
+    fn peek() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeC>::new(&storage);
+
+        assert!(cookie.peek().is_none());
+
+        cookie
+            .insert(GetType {
+                name: "Stephanie".to_string(),
             })
-        }
+            .unwrap();
+
+        let response_cookie = cookie.peek().expect("Cookie is not queued");
+
+        assert_eq!(
+            response_cookie.name_value(),
+            ("type_c", r#"{"name":"Stephanie"}"#)
+        );
+
+        // The lookup uses the instance attributes, which scope the cookie elsewhere
+        let scoped_cookie =
+            Cookie::<TypeC>::new(&storage).with_attributes(Attributes::new().path("/instance"));
+
+        assert!(scoped_cookie.peek().is_none());
+
+        cookie.remove();
+
+        assert_eq!(
+            cookie.peek().map(|c| c.value().to_string()),
+            Some(String::new())
+        );
     }
-    impl IncomingConfig for TypeB {
-        type Get = GetType;
+    #[test]
+    fn insert_raw() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        cookie.insert_raw("abc.123").unwrap();
+
+        let response_cookie = assert_cookie::<TypeA>(&storage);
+
+        // Not serialized, so the value is not quoted
+        assert_eq!(response_cookie.value(), "abc.123");
+        assert_eq!(
+            response_cookie.to_string(),
+            "type_a=abc.123; HttpOnly; SameSite=Lax; Path=/"
+        );
     }
+    #[test]
+    fn insert_raw_encoding() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
 
-    // read and write for type c
-    impl OutgoingConfig for TypeC {
-        type Insert = GetType;
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeP>::new(&storage);
+
+        // Quoted cookie-octets are valid as is
+        cookie.insert("id".to_string()).unwrap();
+        assert_eq!(assert_cookie::<TypeP>(&storage).value(), r#""id""#);
+
+        // A `;` would let the value inject attributes
+        let storage = Storage::new();
+        let cookie = Cookie::<TypeP>::new(&storage);
+
+        assert_eq!(
+            cookie.insert("x; Domain=evil.example; Path=/".to_string()),
+            Err(CookieBoxError::InvalidRawValue("type_p".to_string()))
+        );
+        assert_eq!(
+            cookie.insert_raw("x; Domain=evil.example"),
+            Err(CookieBoxError::InvalidRawValue("type_p".to_string()))
+        );
+        assert_eq!(
+            cookie.insert("a \"quoted\" word".to_string()),
+            Err(CookieBoxError::InvalidRawValue("type_p".to_string()))
+        );
+        assert!(storage.response_cookies().is_empty());
+    }
+    #[test]
+    fn insert_if_changed() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{"name":"some value"}"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        // unchanged value
+        let inserted = cookie.insert_if_changed(GetType {
+            name: "some value".to_string(),
+        });
+
+        assert_eq!(inserted, Ok(false));
+        assert!(storage.response_cookies().is_empty());
+
+        // changed value
+        let inserted = cookie.insert_if_changed(GetType {
+            name: "another value".to_string(),
+        });
+
+        assert_eq!(inserted, Ok(true));
+        assert_eq!(
+            assert_cookie::<TypeA>(&storage).value(),
+            r#"{"name":"another value"}"#
+        );
+
+        // no request cookie
+        let storage = Storage::new();
+        let cookie = Cookie::<TypeA>::new(&storage);
+        let inserted = cookie.insert_if_changed(GetType {
+            name: "some value".to_string(),
+        });
+
+        assert_eq!(inserted, Ok(true));
+        assert_eq!(storage.response_cookies().len(), 1);
+    }
+    #[test]
+    fn get_migrating() {
+        // Set up
+        // Initialize storage with a cookie in the legacy format
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_i", r#""some value""#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeI>::new(&storage);
+        let expected = GetType {
+            name: "some value".to_string(),
+        };
+
+        assert_eq!(cookie.get_migrating(), Ok(expected));
+
+        // The value is re-emitted in the current format
+        let response_cookie = assert_cookie::<TypeI>(&storage);
+
+        assert_eq!(response_cookie.value(), r#"{"name":"some value"}"#);
+
+        // A value in the current format is not re-emitted
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_i", r#"{ "name": "some value" }"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        let cookie = Cookie::<TypeI>::new(&storage);
+
+        assert!(cookie.get_migrating().is_ok());
+        assert!(storage.response_cookies().is_empty());
+
+        // A value in neither format returns the error of `get`
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_i", "42");
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        let cookie = Cookie::<TypeI>::new(&storage);
+
+        assert_eq!(cookie.get_migrating(), cookie.get());
+        assert!(storage.response_cookies().is_empty());
+    }
+    #[test]
+    fn get_seed() {
+        use serde::de::{DeserializeSeed, Deserializer};
+
+        // A seed that injects a prefix known only at runtime
+        struct Prefixed<'p>(&'p str);
+
+        impl<'de> DeserializeSeed<'de> for Prefixed<'_> {
+            type Value = GetType;
+
+            fn deserialize<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<GetType, D::Error> {
+                let value = GetType::deserialize(deserializer)?;
+                Ok(GetType {
+                    name: format!("{}{}", self.0, value.name),
+                })
+            }
+        }
+
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(
+            cookie.get_seed(Prefixed("context: ")),
+            Ok(GetType {
+                name: "context: some value".to_string()
+            })
+        );
+
+        // Trailing data is rejected like `get`
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "some value" } trailing"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert!(matches!(
+            cookie.get_seed(Prefixed("context: ")),
+            Err(CookieBoxError::Deserialization(..))
+        ));
+    }
+    #[test]
+    fn get_cow() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_o", r#"{ "name": "some value" }"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeO>::new(&storage);
+
+        let value = cookie.get().unwrap();
+        assert_eq!(value.name, "some value");
+        assert!(matches!(value.name, Cow::Owned(_)));
+
+        let mut buffer = Vec::new();
+        let value: CowType = cookie.get_borrowed(&mut buffer).unwrap();
+        assert_eq!(value.name, "some value");
+        assert!(matches!(value.name, Cow::Borrowed(_)));
+
+        // Escaped values cannot be borrowed
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_o", r#"{ "name": "some \"value\"" }"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
 
-        fn attributes<'c>() -> Attributes<'c> {
-            // Expiration has an internal From impl for Into<Option<Zoned>
-            let date = date(2024, 1, 15)
-                .at(0, 0, 0, 0)
-                .to_zoned(TimeZone::UTC)
-                .unwrap();
+        let cookie = Cookie::<TypeO>::new(&storage);
+        let value: CowType = cookie.get_borrowed(&mut buffer).unwrap();
+        assert_eq!(value.name, r#"some "value""#);
+        assert!(matches!(value.name, Cow::Owned(_)));
 
-            Attributes::new()
-                .path("/some-path")
-                .domain("..example.com")
-                .same_site(SameSite::Lax)
-                .secure(true)
-                .http_only(true)
-                .partitioned(true)
-                .expires(date)
-                .max_age(SignedDuration::from_hours(10))
-        }
-    }
-    impl IncomingConfig for TypeC {
-        type Get = GetType;
+        let cookie = Cookie::<TypeA>::new(&storage);
+        assert_eq!(
+            cookie.get_borrowed::<CowType>(&mut buffer),
+            Err(CookieBoxError::NotFound("type_a".to_string()))
+        );
     }
+    #[test]
+    fn get_optional_deserialization_error() {
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", "not json");
 
-    // read and write for type d
-    impl OutgoingConfig for TypeD {
-        type Insert = GetType;
+        storage.request_storage.borrow_mut().append(incoming_cookie);
 
-        fn attributes<'c>() -> Attributes<'c> {
-            Attributes::new().permanent(true)
-        }
-    }
-    impl IncomingConfig for TypeD {
-        type Get = GetType;
-    }
+        let cookie = Cookie::<TypeA>::new(&storage);
 
+        assert!(matches!(
+            cookie.get_optional(),
+            Err(CookieBoxError::Deserialization(..))
+        ));
+    }
     #[test]
-    fn get() {
+    fn contains() {
         // Set up
         // Initialize storage
         let storage = Storage::new();
-        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
-        let get_type_value = GetType {
-            name: "some value".to_string(),
-        };
 
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert!(!cookie.contains());
+
+        // Malformed data is still reported as present
+        let incoming_cookie = RequestCookie::new("type_a", "not json");
         storage.request_storage.borrow_mut().append(incoming_cookie);
 
+        assert!(cookie.contains());
+    }
+    #[test]
+    fn get_cookie_with_validation() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("type_n", "42"));
+
         // Use generic type parameter to create a cookie instance
-        let cookie = Cookie::<TypeA>::new(&storage);
+        let cookie = Cookie::<TypeN>::new(&storage);
 
-        let typed_request_value = cookie.get();
+        assert_eq!(cookie.get(), Ok(42));
 
-        assert_eq!(typed_request_value.is_ok(), true);
-        assert_eq!(typed_request_value, Ok(get_type_value));
+        // well-formed but invalid values
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("type_n", "-1"));
+        let cookie = Cookie::<TypeN>::new(&storage);
+
+        let invalid =
+            || CookieBoxError::Invalid("type_n".to_string(), "must not be negative".to_string());
+        assert_eq!(cookie.get(), Err(invalid()));
+        assert_eq!(
+            invalid().to_string(),
+            "The `type_n` cookie holds an invalid value: must not be negative"
+        );
+
+        // get_all checks every value
+        storage.append_request_cookie(RequestCookie::new("type_n", "7"));
+        assert_eq!(cookie.get_all(), Err(invalid()));
+        assert_eq!(cookie.get_all_iter().collect::<Vec<_>>()[1], Ok(7));
+
+        // other errors are reported as invalid
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("type_n", "-9"));
+        assert_eq!(
+            Cookie::<TypeN>::new(&storage).get(),
+            Err(CookieBoxError::Invalid(
+                "type_n".to_string(),
+                "`other` does not exist".to_string()
+            ))
+        );
     }
     #[test]
     fn get_all() {
@@ -460,10 +2808,341 @@ mod tests {
         // Use generic type parameter to create a cookie instance
         let cookie = Cookie::<TypeA>::new(&storage);
 
-        let typed_request_value = cookie.get_all();
-
-        assert_eq!(typed_request_value.is_ok(), true);
-        assert_eq!(typed_request_value, Ok(get_type_values));
+        let typed_request_value = cookie.get_all();
+
+        assert!(typed_request_value.is_ok());
+        assert_eq!(typed_request_value, Ok(get_type_values));
+    }
+    #[test]
+    fn get_with_custom_deserialize_impl() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie_a = RequestCookie::new("type_e", "name=some value 1");
+        let incoming_cookie_b = RequestCookie::new("type_e", "name=some value 2");
+
+        storage
+            .request_storage
+            .borrow_mut()
+            .append(incoming_cookie_a);
+        storage
+            .request_storage
+            .borrow_mut()
+            .append(incoming_cookie_b);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeE>::new(&storage);
+
+        assert_eq!(
+            cookie.get(),
+            Ok(GetType {
+                name: "some value 1".to_string(),
+            })
+        );
+        assert_eq!(
+            cookie.get_all(),
+            Ok(vec![
+                GetType {
+                    name: "some value 1".to_string(),
+                },
+                GetType {
+                    name: "some value 2".to_string(),
+                },
+            ])
+        );
+    }
+    #[test]
+    fn get_with_custom_deserialize_impl_error() {
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_e", r#"{ "name": "some value" }"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        let cookie = Cookie::<TypeE>::new(&storage);
+
+        assert_eq!(
+            cookie.get(),
+            Err(CookieBoxError::Deserialization(
+                r#"{ "name": "some value" }"#.to_string(),
+                "GetType".to_string(),
+                "expected `name=<value>`".into()
+            ))
+        );
+    }
+    #[test]
+    fn get_deserialization_error_source() {
+        use std::error::Error;
+
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": 1 }"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        let cookie = Cookie::<TypeA>::new(&storage);
+        let error = cookie.get().unwrap_err();
+
+        let source = error
+            .source()
+            .and_then(|e| e.downcast_ref::<serde_json::Error>())
+            .expect("the serde_json error is the source");
+        assert_eq!(source.classify(), serde_json::error::Category::Data);
+        assert!(error.to_string().starts_with("Failed to deserialize"));
+    }
+    #[test]
+    fn deserialization_error_redacts_value() {
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("type_a", "s3cr3t-token"));
+
+        let error = Cookie::<TypeA>::new(&storage).get().unwrap_err();
+
+        assert!(
+            matches!(&error, CookieBoxError::Deserialization(value, ..) if value == "s3cr3t-token")
+        );
+        assert!(error.to_string().contains("a value of 12 bytes"));
+        assert!(!error.to_string().contains("s3cr3t"));
+        assert!(format!("{error:?}").contains("<redacted, 12 bytes>"));
+        assert!(!format!("{error:?}").contains("s3cr3t"));
+    }
+    #[test]
+    fn get_all_iter() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        for value in [
+            r#"{ "name": "some value 1" }"#,
+            "not json",
+            r#"{ "name": "some value 2" }"#,
+        ] {
+            storage
+                .request_storage
+                .borrow_mut()
+                .append(RequestCookie::new("type_a", value));
+        }
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+        let mut values = cookie.get_all_iter();
+
+        assert_eq!(
+            values.next(),
+            Some(Ok(GetType {
+                name: "some value 1".to_string()
+            }))
+        );
+        assert!(matches!(
+            values.next(),
+            Some(Err(CookieBoxError::Deserialization(..)))
+        ));
+        assert_eq!(
+            values.next(),
+            Some(Ok(GetType {
+                name: "some value 2".to_string()
+            }))
+        );
+        assert_eq!(values.next(), None);
+
+        // The eager version stops at the first error
+        assert!(cookie.get_all().is_err());
+
+        // Lazy iteration yields the same values as the eager version
+        let storage = Storage::new();
+        for value in [
+            r#"{ "name": "some value 1" }"#,
+            r#"{ "name": "some value 2" }"#,
+        ] {
+            storage
+                .request_storage
+                .borrow_mut()
+                .append(RequestCookie::new("type_a", value));
+        }
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(
+            cookie.get_all_iter().collect::<Result<Vec<_>, _>>(),
+            cookie.get_all()
+        );
+
+        // An absent cookie yields no items
+        let storage = Storage::new();
+        let cookie = Cookie::<TypeB>::new(&storage);
+
+        assert_eq!(cookie.get_all_iter().count(), 0);
+    }
+    #[test]
+    fn get_all_cannot_attribute_paths() {
+        // Set up
+        // Initialize storage with the same cookie set at `/` and `/admin`, the header carries no path
+        let storage = Storage::new();
+        let incoming_cookie_a = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
+        let incoming_cookie_b = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
+
+        storage
+            .request_storage
+            .borrow_mut()
+            .append(incoming_cookie_a);
+        storage
+            .request_storage
+            .borrow_mut()
+            .append(incoming_cookie_b);
+
+        let cookie = Cookie::<TypeA>::new(&storage);
+        let values = cookie.get_all().unwrap();
+
+        assert_eq!(values[0], values[1]);
+    }
+    #[test]
+    fn get_all_with_discriminated_values() {
+        // Set up
+        // Initialize storage with the same cookie set at `/` and `/admin` with a discriminator
+        let storage = Storage::new();
+        let incoming_cookie_a = RequestCookie::new(
+            "type_g",
+            r#"{ "discriminator": "/", "value": "some value" }"#,
+        );
+        let incoming_cookie_b = RequestCookie::new(
+            "type_g",
+            r#"{ "discriminator": "/admin", "value": "some value" }"#,
+        );
+
+        storage
+            .request_storage
+            .borrow_mut()
+            .append(incoming_cookie_a);
+        storage
+            .request_storage
+            .borrow_mut()
+            .append(incoming_cookie_b);
+
+        let cookie = Cookie::<TypeG>::new(&storage);
+
+        assert_eq!(
+            cookie.get_all(),
+            Ok(vec![
+                Discriminated::new("/", "some value".to_string()),
+                Discriminated::new("/admin", "some value".to_string()),
+            ])
+        );
+    }
+    #[test]
+    fn insert_discriminated_value() {
+        let storage = Storage::new();
+        let cookie = Cookie::<TypeG>::new(&storage);
+
+        cookie
+            .insert(Discriminated::new("/admin", "some value".to_string()))
+            .unwrap();
+
+        let binding = storage.response_storage.borrow();
+        let response_cookie = binding.get(ResponseCookieId::new("type_g").set_path("/"));
+
+        assert_eq!(
+            response_cookie.unwrap().value(),
+            r#"{"discriminator":"/admin","value":"some value"}"#
+        );
+    }
+    #[test]
+    fn get_bytes() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", "3q2+7w==");
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(cookie.get_bytes(), Ok(b"3q2+7w==".to_vec()));
+        // The value is not valid JSON, so the typed get fails
+        assert!(cookie.get().is_err());
+    }
+    #[test]
+    fn value_equals() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("type_a", "f3a9c1e07b"));
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert!(cookie.value_equals("f3a9c1e07b"));
+        assert!(!cookie.value_equals("f3a9c1e07c"));
+        assert!(!cookie.value_equals("f3a9c1e07"));
+        assert!(!cookie.value_equals(""));
+
+        // missing cookie
+        assert!(!Cookie::<TypeB>::new(&storage).value_equals(""));
+    }
+    #[test]
+    fn with_value() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(
+            cookie.with_value(|value| value == r#"{ "name": "some value" }"#),
+            Ok(true)
+        );
+        assert_eq!(cookie.with_value(str::len), Ok(24));
+
+        // missing cookie
+        assert_eq!(
+            Cookie::<TypeB>::new(&storage).with_value(|_| panic!("the closure is not called")),
+            Err::<(), _>(CookieBoxError::NotFound("type_b".to_string()))
+        );
+    }
+    #[test]
+    fn get_raw() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", r#"{ "name": "some value" }"#);
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(
+            cookie.get_raw(),
+            Ok(r#"{ "name": "some value" }"#.to_string())
+        );
+
+        // Values that fail to deserialize are returned as well
+        let storage = Storage::new();
+        let incoming_cookie = RequestCookie::new("type_a", "not json");
+
+        storage.request_storage.borrow_mut().append(incoming_cookie);
+
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(cookie.get_raw(), Ok("not json".to_string()));
+
+        let storage = Storage::new();
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(
+            cookie.get_raw(),
+            Err(CookieBoxError::NotFound("type_a".to_string()))
+        );
+    }
+    #[test]
+    fn get_bytes_not_found() {
+        let storage = Storage::new();
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        assert_eq!(
+            cookie.get_bytes(),
+            Err(CookieBoxError::NotFound("type_a".to_string()))
+        );
     }
     #[test]
     fn insert_cookie() {
@@ -480,12 +3159,12 @@ mod tests {
         // Use generic type parameter to create a cookie instance
         let cookie = Cookie::<TypeA>::new(&storage);
 
-        cookie.insert(get_type_value);
+        cookie.insert(get_type_value).unwrap();
 
         let binding = storage.response_storage.borrow();
         let response_cookie = binding.get(outgoing_cookie_id);
 
-        assert_eq!(response_cookie.is_some(), true);
+        assert!(response_cookie.is_some());
         assert_eq!(
             response_cookie.unwrap().name_value(),
             ("type_a", r#"{"name":"some value "}"#)
@@ -504,61 +3183,116 @@ mod tests {
         // Use generic type parameter to create a cookie instance
         let cookie = Cookie::<TypeB>::new(&storage);
 
-        cookie.insert(get_type_value);
+        cookie.insert(get_type_value).unwrap();
 
         let binding = storage.response_storage.borrow();
         let response_cookie = binding.get(outgoing_cookie_id);
 
-        assert_eq!(response_cookie.is_some(), true);
+        assert!(response_cookie.is_some());
         assert_eq!(
             response_cookie.unwrap().name_value(),
             ("type_b", r#"{"name":"some value is 32"}"#)
         );
     }
     #[test]
+    fn insert_cookie_with_failing_serialize() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeL>::new(&storage);
+
+        let result = cookie.insert([((1, 2), 3)].into());
+
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to serialize the `type_l` cookie: key must be a string"
+        );
+        // the serde error is kept as the source
+        let source = std::error::Error::source(&error).unwrap();
+        assert!(source.is::<serde_json::Error>());
+        assert!(storage.response_cookies().is_empty());
+    }
+    #[test]
+    fn insert_cookie_too_large() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeM>::new(&storage);
+
+        // the name and value fit in the limit
+        cookie.insert("small".to_string()).unwrap();
+        assert_eq!(storage.response_cookies().len(), 1);
+
+        // the attributes do not count toward the limit
+        let fits = "x".repeat(50);
+        assert!(
+            ResponseCookie::new("type_m", format!(r#""{fits}""#))
+                .set_attributes(&TypeM::attributes())
+                .to_string()
+                .len()
+                > 64
+        );
+        Cookie::<TypeM>::new(&Storage::new()).insert(fits).unwrap();
+
+        let large = "x".repeat(100);
+        let size = "type_m".len() + large.len() + 2;
+
+        let result = cookie.insert(large);
+
+        assert_eq!(
+            result,
+            Err(CookieBoxError::TooLarge {
+                name: "type_m".to_string(),
+                size,
+                limit: 64
+            })
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!("The `type_m` cookie is {size} bytes, which exceeds the limit of 64 bytes")
+        );
+
+        // the previously queued cookie is kept
+        assert_eq!(storage.response_cookies()[0].value(), r#""small""#);
+    }
+    #[test]
     fn insert_cookie_with_custom_attributes() {
         // Set up
         // Initialize storage
         let storage = Storage::new();
         let outgoing_cookie = ResponseCookie::new("type_c", r#"{ "name": "some value" }"#);
-        // The id determined by name path and domain
-        let outgoing_cookie_id = outgoing_cookie
-            .id()
-            .set_path("/some-path")
-            .set_domain("..example.com");
+        // The id determined by name path and domain, `..example.com` is rejected so there is no domain
+        let outgoing_cookie_id = outgoing_cookie.id().set_path("/some-path");
         let get_type_value = GetType {
             name: "some value".to_string(),
         };
 
-        // Expiration cookie set up
-        let date = date(2024, 1, 15)
-            .at(0, 0, 0, 0)
-            .to_zoned(TimeZone::UTC)
-            .unwrap();
-
         // Use generic type parameter to create a cookie instance
         let cookie = Cookie::<TypeC>::new(&storage);
 
-        cookie.insert(get_type_value);
+        cookie.insert(get_type_value).unwrap();
 
         let binding = storage.response_storage.borrow();
         let response_cookie = binding.get(outgoing_cookie_id);
 
-        assert_eq!(response_cookie.is_some(), true);
+        assert!(response_cookie.is_some());
         assert_eq!(
             response_cookie.unwrap().name_value(),
             ("type_c", r#"{"name":"some value"}"#)
         );
         assert_eq!(response_cookie.unwrap().path(), Some("/some-path"));
-        assert_eq!(response_cookie.unwrap().domain(), Some(".example.com"));
+        assert_eq!(response_cookie.unwrap().domain(), None);
         assert_eq!(response_cookie.unwrap().same_site(), Some(SameSite::Lax));
         assert_eq!(response_cookie.unwrap().http_only(), Some(true));
         assert_eq!(response_cookie.unwrap().secure(), Some(true));
         assert_eq!(response_cookie.unwrap().partitioned(), Some(true));
-        assert_eq!(
-            response_cookie.unwrap().expires(),
-            Some(&Expiration::from(date))
-        );
+        // max_age takes precedence, so expires is not sent
+        assert_eq!(response_cookie.unwrap().expires(), None);
         assert_eq!(
             response_cookie.unwrap().max_age(),
             Some(SignedDuration::from_hours(10))
@@ -570,49 +3304,165 @@ mod tests {
         // Initialize storage
         let storage = Storage::new();
         let outgoing_cookie = ResponseCookie::new("type_c", r#"{ "name": "some value" }"#);
-        // The id determined by name path and domain
-        let outgoing_cookie_id = outgoing_cookie
-            .id()
-            .set_path("/some-path")
-            .set_domain("..example.com");
+        // The id determined by name path and domain, `..example.com` is rejected so there is no domain
+        let outgoing_cookie_id = outgoing_cookie.id().set_path("/some-path");
         let get_type_value = GetType {
             name: "some value".to_string(),
         };
 
-        // Expiration cookie set up
-        let date = date(2024, 1, 15)
-            .at(0, 0, 0, 0)
-            .to_zoned(TimeZone::UTC)
-            .unwrap();
-
         // Use generic type parameter to create a cookie instance
         let cookie = Cookie::<TypeC>::new(&storage);
 
-        cookie.insert(get_type_value.clone());
-        cookie.insert(get_type_value);
+        cookie.insert(get_type_value.clone()).unwrap();
+        cookie.insert(get_type_value).unwrap();
 
         let binding = storage.response_storage.borrow();
         let response_cookie = binding.get(outgoing_cookie_id);
 
-        assert_eq!(response_cookie.is_some(), true);
+        assert!(response_cookie.is_some());
         assert_eq!(
             response_cookie.unwrap().name_value(),
             ("type_c", r#"{"name":"some value"}"#)
         );
         assert_eq!(response_cookie.unwrap().path(), Some("/some-path"));
-        assert_eq!(response_cookie.unwrap().domain(), Some(".example.com"));
+        assert_eq!(response_cookie.unwrap().domain(), None);
         assert_eq!(response_cookie.unwrap().same_site(), Some(SameSite::Lax));
         assert_eq!(response_cookie.unwrap().http_only(), Some(true));
         assert_eq!(response_cookie.unwrap().secure(), Some(true));
         assert_eq!(response_cookie.unwrap().partitioned(), Some(true));
+        // max_age takes precedence, so expires is not sent
+        assert_eq!(response_cookie.unwrap().expires(), None);
+        assert_eq!(
+            response_cookie.unwrap().max_age(),
+            Some(SignedDuration::from_hours(10))
+        );
+    }
+    #[test]
+    fn insert_cookie_same_scope_replaces() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let get_type_value = GetType {
+            name: "some value".to_string(),
+        };
+
+        let attributes = Attributes::default().max_age(SignedDuration::from_hours(1));
+        Cookie::<TypeA>::new(&storage)
+            .with_attributes(attributes.clone())
+            .insert(get_type_value.clone())
+            .unwrap();
+        Cookie::<TypeA>::new(&storage)
+            .with_attributes(attributes.max_age(SignedDuration::from_hours(2)))
+            .insert(GetType {
+                name: "other value".to_string(),
+            })
+            .unwrap();
+
+        let response_cookies = storage.response_cookies();
+
+        // The second insert replaces the value and the attributes
+        assert_eq!(response_cookies.len(), 1);
+        assert_eq!(response_cookies[0].value(), r#"{"name":"other value"}"#);
+        assert_eq!(
+            response_cookies[0].max_age(),
+            Some(SignedDuration::from_hours(2))
+        );
+    }
+    #[test]
+    fn insert_cookie_other_scope_coexists() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let get_type_value = GetType {
+            name: "some value".to_string(),
+        };
+
+        Cookie::<TypeA>::new(&storage)
+            .insert(get_type_value.clone())
+            .unwrap();
+        Cookie::<TypeA>::new(&storage)
+            .with_attributes(Attributes::default().path("/admin"))
+            .insert(get_type_value)
+            .unwrap();
+
+        let mut paths: Vec<_> = storage
+            .response_cookies()
+            .iter()
+            .map(|cookie| cookie.path().map(str::to_string))
+            .collect();
+        paths.sort_unstable();
+
+        // Both cookies are queued, one per path
+        assert_eq!(
+            paths,
+            vec![Some("/".to_string()), Some("/admin".to_string())]
+        );
+    }
+    #[test]
+    fn insert_cookie_with_call_attributes() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let get_type_value = GetType {
+            name: "some value".to_string(),
+        };
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        cookie
+            .insert_with(
+                get_type_value,
+                Attributes::default().max_age(SignedDuration::from_hours(24)),
+            )
+            .unwrap();
+
+        let binding = storage.response_storage.borrow();
+        let response_cookie = binding.get(ResponseCookieId::new("type_a").set_path("/"));
+
         assert_eq!(
-            response_cookie.unwrap().expires(),
-            Some(&Expiration::from(date))
+            response_cookie.unwrap().name_value(),
+            ("type_a", r#"{"name":"some value"}"#)
         );
         assert_eq!(
             response_cookie.unwrap().max_age(),
-            Some(SignedDuration::from_hours(10))
+            Some(SignedDuration::from_hours(24))
+        );
+    }
+    #[test]
+    fn insert_and_remove_cookie_with_instance_attributes() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let get_type_value = GetType {
+            name: "some value".to_string(),
+        };
+
+        // Override the attributes of type c which sets `/some-path` and `..example.com` by default
+        let cookie =
+            Cookie::<TypeC>::new(&storage).with_attributes(Attributes::new().path("/instance"));
+
+        cookie.insert(get_type_value).unwrap();
+
+        let binding = storage.response_storage.borrow();
+        let response_cookie = binding.get(ResponseCookieId::new("type_c").set_path("/instance"));
+
+        assert_eq!(binding.iter().count(), 1);
+        assert_eq!(
+            response_cookie.unwrap().name_value(),
+            ("type_c", r#"{"name":"some value"}"#)
         );
+        assert_eq!(response_cookie.unwrap().domain(), None);
+        assert_eq!(response_cookie.unwrap().secure(), None);
+        drop(binding);
+
+        cookie.remove();
+
+        let binding = storage.response_storage.borrow();
+        let response_cookie = binding.get(ResponseCookieId::new("type_c").set_path("/instance"));
+
+        assert_eq!(binding.iter().count(), 1);
+        assert_eq!(response_cookie.unwrap().value(), "");
     }
     #[test]
     fn insert_cookie_with_permanent() {
@@ -629,12 +3479,12 @@ mod tests {
         // Use generic type parameter to create a cookie instance
         let cookie = Cookie::<TypeD>::new(&storage);
 
-        cookie.insert(get_type_value);
+        cookie.insert(get_type_value).unwrap();
 
         let binding = storage.response_storage.borrow();
         let response_cookie = binding.get(outgoing_cookie_id);
 
-        assert_eq!(response_cookie.is_some(), true);
+        assert!(response_cookie.is_some());
         assert_eq!(
             response_cookie.unwrap().name_value(),
             ("type_d", r#"{"name":"some value"}"#)
@@ -645,6 +3495,139 @@ mod tests {
         );
     }
     #[test]
+    fn insert_cookie_with_distinct_paths() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let get_type_value = GetType {
+            name: "some value".to_string(),
+        };
+
+        // Use generic type parameter to create cookie instances scoped to different paths
+        let cookie = Cookie::<TypeA>::new(&storage);
+        let scoped_cookie =
+            Cookie::<TypeA>::new(&storage).with_attributes(Attributes::default().path("/scoped"));
+
+        cookie.insert(get_type_value.clone()).unwrap();
+        scoped_cookie.insert(get_type_value).unwrap();
+
+        let binding = storage.response_storage.borrow();
+
+        assert_eq!(binding.iter().count(), 2);
+        assert!(
+            binding
+                .get(ResponseCookieId::new("type_a").set_path("/"))
+                .is_some()
+        );
+        assert!(
+            binding
+                .get(ResponseCookieId::new("type_a").set_path("/scoped"))
+                .is_some()
+        );
+    }
+    #[test]
+    fn insert_cookie_with_same_scope_replaces_value() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        cookie
+            .insert(GetType {
+                name: "value 1".to_string(),
+            })
+            .unwrap();
+        cookie
+            .insert(GetType {
+                name: "value 2".to_string(),
+            })
+            .unwrap();
+
+        let binding = storage.response_storage.borrow();
+        let response_cookie = binding.get(ResponseCookieId::new("type_a").set_path("/"));
+
+        assert_eq!(binding.iter().count(), 1);
+        assert_eq!(
+            response_cookie.unwrap().name_value(),
+            ("type_a", r#"{"name":"value 2"}"#)
+        );
+    }
+    #[test]
+    fn insert_cookie_asserted_with_test_util() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let get_type_value = GetType {
+            name: "some value".to_string(),
+        };
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeC>::new(&storage);
+
+        cookie.insert(get_type_value).unwrap();
+
+        let response_cookie = assert_cookie::<TypeC>(&storage);
+
+        assert_eq!(response_cookie.path(), Some("/some-path"));
+        assert_eq!(response_cookie.domain(), None);
+        assert_eq!(response_cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(response_cookie.http_only(), Some(true));
+        assert_eq!(response_cookie.secure(), Some(true));
+        assert_eq!(response_cookie.partitioned(), Some(true));
+    }
+    #[test]
+    #[should_panic(expected = "`type_a` is not queued in the response storage")]
+    fn assert_cookie_panics_when_not_queued() {
+        let storage = Storage::new();
+
+        assert_cookie::<TypeA>(&storage);
+    }
+    #[test]
+    fn insert_cookie_with_env_attributes() {
+        for (is_secure, path) in [(true, "/secure"), (false, "/insecure")] {
+            // Set up
+            // Initialize storage with the request env
+            let mut storage = Storage::new();
+            storage.env = Rc::new(CookieEnv::new(
+                is_secure,
+                "example.com:8080",
+                format!("{path}/users"),
+                path,
+            ));
+            let get_type_value = GetType {
+                name: "some value".to_string(),
+            };
+
+            // Use generic type parameter to create a cookie instance
+            let cookie = Cookie::<TypeF>::new(&storage);
+
+            cookie.insert(get_type_value).unwrap();
+
+            let binding = storage.response_storage.borrow();
+            let response_cookie = binding
+                .get(
+                    ResponseCookieId::new("type_f")
+                        .set_path(path)
+                        .set_domain("example.com"),
+                )
+                .unwrap();
+
+            assert_eq!(response_cookie.secure(), Some(is_secure));
+            assert_eq!(response_cookie.domain(), Some("example.com"));
+        }
+    }
+    #[test]
+    fn env_host_without_port() {
+        let host = |host| CookieEnv::new(true, host, "/", "/").host().to_string();
+
+        assert_eq!(host("example.com"), "example.com");
+        assert_eq!(host("example.com:8080"), "example.com");
+        assert_eq!(host("[::1]"), "[::1]");
+        assert_eq!(host("[::1]:8080"), "[::1]");
+    }
+    #[test]
     fn remove_cookie() {
         // Set up
         // Initialize storage
@@ -661,7 +3644,7 @@ mod tests {
         let binding = storage.response_storage.borrow();
         let response_cookie = binding.get(outgoing_cookie_id);
 
-        assert_eq!(response_cookie.is_some(), true);
+        assert!(response_cookie.is_some());
         assert_eq!(response_cookie.unwrap().name_value(), ("type_b", ""));
         assert!(
             response_cookie
@@ -674,6 +3657,148 @@ mod tests {
         );
     }
     #[test]
+    fn remove_cookie_presence() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let outgoing_cookie_id = ResponseCookieId::new("type_b").set_path("/");
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeB>::new(&storage);
+
+        // Absent from the request, the removal cookie is still emitted
+        assert!(!cookie.remove());
+        assert!(
+            storage
+                .response_storage
+                .borrow()
+                .get(outgoing_cookie_id.clone())
+                .is_some()
+        );
+
+        storage
+            .request_storage
+            .borrow_mut()
+            .append(RequestCookie::new("type_b", r#"{ "name": "some value" }"#));
+
+        assert!(cookie.remove());
+    }
+    #[cfg(debug_assertions)]
+    #[test]
+    fn removal_scope_mismatch() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        // The scope `insert` uses
+        assert_eq!(cookie.removal_scope_mismatch(&Attributes::default()), None);
+
+        let admin = Attributes::default().path("/admin");
+        assert_eq!(
+            cookie.removal_scope_mismatch(&admin).as_deref(),
+            Some(
+                r#"`type_a` is removed with path Some("/admin") and domain None, but inserted with path Some("/") and domain None, so the browser may keep it"#
+            )
+        );
+
+        // A cookie queued at the same scope is matched
+        Cookie::<TypeA>::new(&storage)
+            .with_attributes(admin.clone())
+            .insert(GetType {
+                name: "some value".to_string(),
+            })
+            .unwrap();
+        assert_eq!(cookie.removal_scope_mismatch(&admin), None);
+
+        // The scope set with `with_attributes` on the same instance is the one `insert` uses
+        let storage = Storage::new();
+        let cookie = Cookie::<TypeA>::new(&storage).with_attributes(admin.clone());
+        assert_eq!(cookie.removal_scope_mismatch(&admin), None);
+    }
+    #[test]
+    fn remove_cookie_with_attributes() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeB>::new(&storage);
+
+        // inserted at a path computed at request time
+        let attributes = Attributes::default()
+            .path("/workspaces/42")
+            .domain("example.com");
+        cookie
+            .insert_with(("value".to_string(), 1), attributes.clone())
+            .unwrap();
+
+        assert!(!cookie.remove_with(attributes));
+
+        // the removal cookie replaced the inserted one, as they share the name, path, and domain
+        let processor: biscotti::Processor = biscotti::ProcessorConfig::default().into();
+        let headers = storage
+            .response_storage
+            .take()
+            .header_values(&processor)
+            .collect::<Vec<_>>();
+
+        assert_eq!(headers.len(), 1);
+        assert!(headers[0].starts_with("type_b=;"));
+        assert!(headers[0].contains("Path=/workspaces/42;"));
+        assert!(headers[0].contains("Domain=example.com;"));
+    }
+    #[test]
+    fn remove_cookie_paths() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeB>::new(&storage);
+
+        cookie.remove_paths(&["/", "/admin", "/api"]);
+
+        let processor: biscotti::Processor = biscotti::ProcessorConfig::default().into();
+        let headers = storage
+            .response_storage
+            .take()
+            .header_values(&processor)
+            .collect::<Vec<_>>();
+
+        assert_eq!(headers.len(), 3);
+        for path in ["/", "/admin", "/api"] {
+            assert!(headers.iter().any(|header| header.starts_with("type_b=;")
+                && header.contains(&format!("Path={path};"))));
+        }
+    }
+    #[test]
+    fn discard_cookie_if() {
+        // Set up
+        // Initialize storage
+        let storage = Storage::new();
+
+        // Use generic type parameter to create a cookie instance
+        let cookie = Cookie::<TypeA>::new(&storage);
+
+        // no pending cookie
+        assert!(!cookie.discard_if(|_| panic!("the predicate is not called")));
+
+        cookie
+            .insert(GetType {
+                name: "tentative".to_string(),
+            })
+            .unwrap();
+
+        // predicate does not hold
+        assert!(!cookie.discard_if(|pending| pending.value().contains("other")));
+        assert_eq!(storage.response_cookies().len(), 1);
+
+        // predicate holds
+        assert!(cookie.discard_if(|pending| pending.value().contains("tentative")));
+        assert!(storage.response_cookies().is_empty());
+    }
+    #[test]
     fn discard_cookie() {
         // Set up
         // Initialize storage
@@ -690,6 +3815,6 @@ mod tests {
         let binding = storage.response_storage.borrow();
         let response_cookie = binding.get(outgoing_cookie_id);
 
-        assert_eq!(response_cookie.is_some(), false);
+        assert!(response_cookie.is_none());
     }
 }
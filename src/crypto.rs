@@ -0,0 +1,397 @@
+//! Shorthands to build the crypto rules of a [ProcessorConfig]
+use biscotti::config::{CryptoAlgorithm, CryptoRule, FallbackConfig};
+use biscotti::errors::KeyError;
+use biscotti::{Key, Processor, ProcessorConfig};
+use std::collections::HashSet;
+
+/// Builds a [CryptoRule] without spelling out the struct literal
+///
+/// The rule is created without fallbacks, which can still be pushed to `fallbacks` afterwards for key rotation.
+/// ```
+/// use cookiebox::{CryptoRule, CryptoRuleExt, Key, ProcessorConfig};
+///
+/// let mut config = ProcessorConfig::default();
+/// config.crypto_rules.push(CryptoRule::encrypt(["__session"], Key::generate()));
+/// config.crypto_rules.push(CryptoRule::sign(["__preferences", "__cart"], Key::generate()));
+/// ```
+pub trait CryptoRuleExt {
+    /// Creates a rule that encrypts the cookies named `names` with `key`
+    fn encrypt<I, N>(names: I, key: Key) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>;
+    /// Creates a rule that signs the cookies named `names` with `key`
+    fn sign<I, N>(names: I, key: Key) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>;
+}
+
+impl CryptoRuleExt for CryptoRule {
+    fn encrypt<I, N>(names: I, key: Key) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>,
+    {
+        rule(names, CryptoAlgorithm::Encryption, key)
+    }
+    fn sign<I, N>(names: I, key: Key) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>,
+    {
+        rule(names, CryptoAlgorithm::Signing, key)
+    }
+}
+
+/// Validates a [ProcessorConfig] while building the [Processor], so a misconfiguration surfaces at start up
+///
+/// `ProcessorConfig::into` accepts any configuration, so a mistake only shows once cookies fail to be read. [ProcessorConfigExt::try_build]
+/// checks that:
+/// - every crypto rule targets at least one cookie, and no empty name
+/// - no cookie is targeted by more than one rule, since only the last one would apply
+/// - no fallback key is the same as the key of its rule, which happens when a rotation forgets to replace the key
+///
+/// A [Key] cannot hold less than 32 bytes, so the length is checked when it is created. [ConfigError] converts from the
+/// [KeyError](biscotti::errors::KeyError) returned by `Key::try_from`, so keys loaded from raw bytes can be checked in the same step.
+/// ```
+/// use cookiebox::{ConfigError, CryptoRule, CryptoRuleExt, Key, Processor, ProcessorConfig, ProcessorConfigExt};
+///
+/// fn processor(key_bytes: &[u8]) -> Result<Processor, ConfigError> {
+///     let mut config = ProcessorConfig::default();
+///     config.crypto_rules.push(CryptoRule::encrypt(["__session"], Key::try_from(key_bytes)?));
+///     config.try_build()
+/// }
+///
+/// assert!(processor(&[0; 64]).is_ok());
+/// assert!(processor(&[0; 16]).is_err());
+/// ```
+pub trait ProcessorConfigExt {
+    /// Builds the [Processor], or returns the first problem found in the configuration
+    fn try_build(self) -> Result<Processor, ConfigError>;
+}
+
+impl ProcessorConfigExt for ProcessorConfig {
+    fn try_build(self) -> Result<Processor, ConfigError> {
+        let mut names = HashSet::new();
+
+        for (index, rule) in self.crypto_rules.iter().enumerate() {
+            if rule.cookie_names.is_empty() {
+                return Err(ConfigError::NoCookieNames(index));
+            }
+            for name in &rule.cookie_names {
+                if name.is_empty() {
+                    return Err(ConfigError::EmptyCookieName(index));
+                }
+                if !names.insert(name) {
+                    return Err(ConfigError::DuplicateCookieName(name.clone()));
+                }
+            }
+
+            if rule
+                .fallbacks
+                .iter()
+                .any(|fallback| fallback.key == rule.key)
+            {
+                return Err(ConfigError::FallbackKeyReused(index));
+            }
+        }
+
+        Ok(self.into())
+    }
+}
+
+/// The error returned by [ProcessorConfigExt::try_build] when a [ProcessorConfig] is invalid
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("The crypto rule at index {0} does not target any cookie")]
+    NoCookieNames(usize),
+    #[error("The crypto rule at index {0} targets a cookie with an empty name")]
+    EmptyCookieName(usize),
+    #[error(
+        "The `{0}` cookie is targeted by more than one crypto rule, only the last one would apply"
+    )]
+    DuplicateCookieName(String),
+    #[error(
+        "A fallback key of the crypto rule at index {0} is the same as its key, so the previous key is no longer accepted"
+    )]
+    FallbackKeyReused(usize),
+    #[error("Invalid key: {0}")]
+    Key(#[from] KeyError),
+}
+
+/// Builds a [Processor] that signs the cookies named `names` with `key`, and leaves the other cookies as they are
+///
+/// This is a one-liner for the common case. Build a [ProcessorConfig] with [CryptoRuleExt] to combine several rules,
+/// add fallback keys, or change the percent-encoding.
+/// ```
+/// use cookiebox::{CookieMiddleware, Key};
+///
+/// let middleware = CookieMiddleware::new(cookiebox::signed_processor(["__preferences"], Key::generate()));
+/// ```
+pub fn signed_processor<I, N>(names: I, key: Key) -> Processor
+where
+    I: IntoIterator<Item = N>,
+    N: Into<String>,
+{
+    processor(CryptoRule::sign(names, key))
+}
+
+/// Builds a [Processor] that encrypts the cookies named `names` with `key`, and leaves the other cookies as they are
+///
+/// Like [signed_processor], build a [ProcessorConfig] with [CryptoRuleExt] for anything beyond a single rule.
+/// ```
+/// use cookiebox::{CookieMiddleware, Key};
+///
+/// let middleware = CookieMiddleware::new(cookiebox::encrypted_processor(["__session"], Key::generate()));
+/// ```
+pub fn encrypted_processor<I, N>(names: I, key: Key) -> Processor
+where
+    I: IntoIterator<Item = N>,
+    N: Into<String>,
+{
+    processor(CryptoRule::encrypt(names, key))
+}
+
+/// Builds a [Processor] like [signed_processor], that also verifies the cookies signed with any of `old_keys`
+///
+/// New cookies are always signed with `key`. See [encrypted_processor_with_fallbacks] for the rotation workflow.
+pub fn signed_processor_with_fallbacks<I, N, K>(names: I, key: Key, old_keys: K) -> Processor
+where
+    I: IntoIterator<Item = N>,
+    N: Into<String>,
+    K: IntoIterator<Item = Key>,
+{
+    processor(with_fallbacks(CryptoRule::sign(names, key), old_keys))
+}
+
+/// Builds a [Processor] like [encrypted_processor], that also decrypts the cookies encrypted with any of `old_keys`
+///
+/// New cookies are always encrypted with `key`, while cookies the clients still hold from before a rotation keep being
+/// read. To rotate a key:
+/// 1. Generate a new key and pass it as `key`, moving the current one to `old_keys`
+/// 2. Wait until every cookie encrypted with the old key has expired, or has been written again with the new one
+/// 3. Drop the old key from `old_keys`
+///
+/// The fallbacks use the same algorithm as the rule.
+/// ```
+/// use cookiebox::{CookieMiddleware, Key};
+///
+/// # let (new_key, previous_key) = (Key::generate(), Key::generate());
+/// let processor = cookiebox::encrypted_processor_with_fallbacks(["__session"], new_key, [previous_key]);
+/// let middleware = CookieMiddleware::new(processor);
+/// ```
+pub fn encrypted_processor_with_fallbacks<I, N, K>(names: I, key: Key, old_keys: K) -> Processor
+where
+    I: IntoIterator<Item = N>,
+    N: Into<String>,
+    K: IntoIterator<Item = Key>,
+{
+    processor(with_fallbacks(CryptoRule::encrypt(names, key), old_keys))
+}
+
+fn with_fallbacks<K>(mut rule: CryptoRule, old_keys: K) -> CryptoRule
+where
+    K: IntoIterator<Item = Key>,
+{
+    let algorithm = rule.algorithm;
+    rule.fallbacks.extend(
+        old_keys
+            .into_iter()
+            .map(|key| FallbackConfig { key, algorithm }),
+    );
+    rule
+}
+
+fn processor(rule: CryptoRule) -> Processor {
+    let mut config = ProcessorConfig::default();
+    config.crypto_rules.push(rule);
+    config.into()
+}
+
+fn rule<I, N>(names: I, algorithm: CryptoAlgorithm, key: Key) -> CryptoRule
+where
+    I: IntoIterator<Item = N>,
+    N: Into<String>,
+{
+    CryptoRule {
+        cookie_names: names.into_iter().map(Into::into).collect(),
+        algorithm,
+        key,
+        fallbacks: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ConfigError, CryptoRuleExt, ProcessorConfigExt, encrypted_processor,
+        encrypted_processor_with_fallbacks, signed_processor, signed_processor_with_fallbacks,
+    };
+    use crate::Storage;
+    use crate::cookiebox_macros::cookie;
+    use crate::cookies::{Cookie, CookieName, IncomingConfig};
+    use biscotti::config::{CryptoAlgorithm, CryptoRule, FallbackConfig};
+    use biscotti::{Key, Processor, ProcessorConfig, ResponseCookie};
+
+    #[cookie(name = "__session")]
+    pub struct Session;
+    impl IncomingConfig for Session {
+        type Get = String;
+    }
+
+    // Builds the `Cookie` header a client would send back for a cookie written by `processor`
+    fn cookie_header(processor: &Processor, name: &'static str, value: &'static str) -> String {
+        let cookie = processor.process_outgoing(ResponseCookie::new(name, value));
+        format!("{}={}", cookie.name(), cookie.value())
+    }
+
+    #[test]
+    fn crypto_rules() {
+        let encrypt = CryptoRule::encrypt(["__session"], Key::generate());
+        let sign = CryptoRule::sign(
+            vec!["__cart".to_string(), "__theme".to_string()],
+            Key::generate(),
+        );
+
+        assert_eq!(encrypt.cookie_names, vec!["__session".to_string()]);
+        assert!(matches!(encrypt.algorithm, CryptoAlgorithm::Encryption));
+        assert!(encrypt.fallbacks.is_empty());
+        assert_eq!(
+            sign.cookie_names,
+            vec!["__cart".to_string(), "__theme".to_string()]
+        );
+        assert!(matches!(sign.algorithm, CryptoAlgorithm::Signing));
+
+        let mut config = ProcessorConfig::default();
+        config.crypto_rules.extend([encrypt, sign]);
+        let processor: Processor = config.into();
+
+        assert!(processor.will_encrypt("__session"));
+        assert!(processor.will_sign("__cart"));
+        assert!(processor.will_sign("__theme"));
+    }
+    #[test]
+    fn processors() {
+        let signed = signed_processor(["__cart", "__theme"], Key::generate());
+
+        assert!(signed.will_sign("__cart"));
+        assert!(signed.will_sign("__theme"));
+        assert!(!signed.will_encrypt("__cart"));
+        assert!(!signed.will_sign("__session"));
+
+        let encrypted = encrypted_processor(["__session"], Key::generate());
+
+        assert!(encrypted.will_encrypt("__session"));
+        assert!(!encrypted.will_sign("__session"));
+        assert!(!encrypted.will_encrypt("__cart"));
+    }
+    #[test]
+    fn key_rotation() {
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+
+        // a cookie written before the rotation
+        let header = cookie_header(
+            &encrypted_processor(["__session"], old_key.clone()),
+            "__session",
+            r#""some user""#,
+        );
+
+        let rotated = encrypted_processor_with_fallbacks(["__session"], new_key.clone(), [old_key]);
+        let storage = Storage::from_cookie_header(&header, &rotated).unwrap();
+
+        assert_eq!(
+            Cookie::<Session>::new(&storage).get(),
+            Ok("some user".to_string())
+        );
+
+        // without the fallback, the old cookie is rejected
+        assert!(
+            Storage::from_cookie_header(&header, &encrypted_processor(["__session"], new_key))
+                .is_err()
+        );
+
+        // the same goes for signing
+        let old_key = Key::generate();
+        let header = cookie_header(
+            &signed_processor(["__session"], old_key.clone()),
+            "__session",
+            r#""some user""#,
+        );
+        let rotated = signed_processor_with_fallbacks(["__session"], Key::generate(), [old_key]);
+        let storage = Storage::from_cookie_header(&header, &rotated).unwrap();
+
+        assert_eq!(
+            Cookie::<Session>::new(&storage).get(),
+            Ok("some user".to_string())
+        );
+    }
+    #[test]
+    fn try_build() {
+        let build = |rules: Vec<CryptoRule>| {
+            let mut config = ProcessorConfig::default();
+            config.crypto_rules.extend(rules);
+            config.try_build()
+        };
+
+        let processor = build(vec![
+            CryptoRule::encrypt(["__session"], Key::generate()),
+            CryptoRule::sign(["__cart"], Key::generate()),
+        ])
+        .unwrap();
+        assert!(processor.will_encrypt("__session"));
+        assert!(processor.will_sign("__cart"));
+
+        assert!(matches!(
+            build(vec![CryptoRule::sign(
+                Vec::<String>::new(),
+                Key::generate()
+            )]),
+            Err(ConfigError::NoCookieNames(0))
+        ));
+        assert!(matches!(
+            build(vec![
+                CryptoRule::sign(["__cart"], Key::generate()),
+                CryptoRule::sign(["__theme", ""], Key::generate())
+            ]),
+            Err(ConfigError::EmptyCookieName(1))
+        ));
+        assert!(matches!(
+            build(vec![
+                CryptoRule::sign(["__cart"], Key::generate()),
+                CryptoRule::encrypt(["__cart"], Key::generate())
+            ]),
+            Err(ConfigError::DuplicateCookieName(name)) if name == "__cart"
+        ));
+    }
+    #[test]
+    fn try_build_key() {
+        let processor =
+            |key_bytes: &[u8], fallback_bytes: &[u8]| -> Result<Processor, ConfigError> {
+                let mut config = ProcessorConfig::default();
+                let mut rule = CryptoRule::encrypt(["__session"], Key::try_from(key_bytes)?);
+                rule.fallbacks.push(FallbackConfig {
+                    key: Key::try_from(fallback_bytes)?,
+                    algorithm: CryptoAlgorithm::Encryption,
+                });
+                config.crypto_rules.push(rule);
+                config.try_build()
+            };
+
+        assert!(processor(&[7; 64], &[8; 64]).is_ok());
+
+        let error = processor(&[7; 64], &[7; 64]).unwrap_err();
+        assert!(matches!(error, ConfigError::FallbackKeyReused(0)));
+        assert_eq!(
+            error.to_string(),
+            "A fallback key of the crypto rule at index 0 is the same as its key, so the previous key is no longer accepted"
+        );
+
+        let error = processor(&[7; 64], &[8; 16]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid key: key material is too short: expected >= 32 bytes, got 16 bytes"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Extractors that read cookies straight from the request
+use crate::cookies::{Cookie, IncomingConfig};
+use crate::middleware::e500;
+use crate::storage::Storage;
+use actix_web::{FromRequest, HttpRequest, dev::Payload};
+use anyhow::anyhow;
+use std::future::{Ready, ready};
+
+/// Extractor that yields the value of the cookie type `T`, or `None` if it is missing or malformed
+///
+/// Unlike the [Cookie] get methods, the extraction never fails, which suits handlers that treat a cookie as purely optional.
+/// Use [Cookie::get_optional] instead to tell a missing cookie apart from a malformed one.
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{CookieName, IncomingConfig};
+/// use cookiebox::OptionalCookie;
+/// use actix_web::HttpResponse;
+///
+/// #[cookie(name = "theme")]
+/// pub struct Theme;
+///
+/// impl IncomingConfig for Theme {
+///     type Get = String;
+/// }
+///
+/// async fn index(theme: OptionalCookie<Theme>) -> HttpResponse {
+///     let theme = theme.into_inner().unwrap_or_else(|| "light".to_string());
+///     HttpResponse::Ok().body(theme)
+/// }
+/// ```
+pub struct OptionalCookie<T: IncomingConfig>(Option<T::Get>);
+
+impl<T: IncomingConfig> OptionalCookie<T> {
+    /// Returns the cookie value, if any
+    pub fn into_inner(self) -> Option<T::Get> {
+        self.0
+    }
+}
+
+impl<T: IncomingConfig> std::ops::Deref for OptionalCookie<T> {
+    type Target = Option<T::Get>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: IncomingConfig> FromRequest for OptionalCookie<T> {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        // Without the middleware there is no storage, which reads the same as a missing cookie
+        let value = crate::storage(req).and_then(|storage| Cookie::<T>::new(&storage).get().ok());
+
+        ready(Ok(OptionalCookie(value)))
+    }
+}
+
+/// Extractor that hands out a [Cookie] of any cookie type on demand
+///
+/// This is an alternative to a collection struct deriving `FromRequest`, for code that works with cookie types it does not
+/// know upfront, e.g. a library passing every cookie of the request around. The extraction fails with
+/// `500 Internal Server Error` if [CookieMiddleware](crate::CookieMiddleware) is not registered.
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{CookieBoxError, CookieName, IncomingConfig, OutgoingConfig};
+/// use cookiebox::CookieJar;
+/// use actix_web::HttpResponse;
+///
+/// #[cookie(name = "visits")]
+/// pub struct Visits;
+///
+/// impl IncomingConfig for Visits {
+///     type Get = u32;
+/// }
+/// impl OutgoingConfig for Visits {
+///     type Insert = u32;
+/// }
+///
+/// async fn index(jar: CookieJar<'_>) -> Result<HttpResponse, CookieBoxError> {
+///     let visits = jar.typed::<Visits>().get().unwrap_or(0);
+///     jar.typed::<Visits>().insert(visits + 1)?;
+///     Ok(HttpResponse::Ok().finish())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CookieJar<'c> {
+    storage: Storage<'c>,
+}
+
+impl<'c> CookieJar<'c> {
+    /// Create a [CookieJar] over `storage`
+    pub fn new(storage: &Storage<'c>) -> Self {
+        CookieJar {
+            storage: storage.clone(),
+        }
+    }
+    /// Returns a [Cookie] instance for the cookie type `T`, the same as `Cookie::<T>::new`
+    pub fn typed<T>(&self) -> Cookie<'c, T> {
+        Cookie::new(&self.storage)
+    }
+    /// Returns the [Storage] the jar reads from and writes to
+    pub fn storage(&self) -> &Storage<'c> {
+        &self.storage
+    }
+}
+
+impl FromRequest for CookieJar<'static> {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            crate::storage(req)
+                .map(|storage| CookieJar { storage })
+                .ok_or_else(|| e500(anyhow!("Storage not found in request extension"))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cookiebox_macros::cookie;
+    use crate::cookies::{CookieName, OutgoingConfig};
+    use actix_web::HttpMessage;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use biscotti::RequestCookie;
+
+    #[cookie(name = "visits")]
+    pub struct Visits;
+    impl IncomingConfig for Visits {
+        type Get = u32;
+    }
+    impl OutgoingConfig for Visits {
+        type Insert = u32;
+    }
+
+    #[cookie(name = "theme")]
+    pub struct Theme;
+    impl IncomingConfig for Theme {
+        type Get = String;
+    }
+
+    #[actix_web::test]
+    async fn cookie_jar_typed() {
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("visits", "3"));
+        storage.append_request_cookie(RequestCookie::new("theme", r#""dark""#));
+
+        let req = TestRequest::default().to_http_request();
+        req.extensions_mut().insert(storage.clone());
+
+        let jar = CookieJar::extract(&req).await.unwrap();
+
+        // heterogeneous cookie types are read through the same jar
+        assert_eq!(jar.typed::<Visits>().get(), Ok(3));
+        assert_eq!(jar.typed::<Theme>().get(), Ok("dark".to_string()));
+
+        // and written to the request storage
+        jar.typed::<Visits>().insert(4).unwrap();
+        assert_eq!(
+            storage.pending_response_cookies(),
+            vec![("visits".to_string(), "4".to_string())]
+        );
+    }
+
+    #[actix_web::test]
+    async fn cookie_jar_without_middleware() {
+        let req = TestRequest::default().to_http_request();
+
+        let error = CookieJar::extract(&req).await.err().unwrap();
+
+        assert_eq!(
+            error.as_response_error().status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
@@ -36,7 +36,7 @@
 //!```no_run
 //!use actix_web::HttpMessage;
 //!use cookiebox::cookiebox_macros::{cookie, FromRequest};
-//!use cookiebox::cookies::{Cookie, CookieName, IncomingConfig, OutgoingConfig};
+//!use cookiebox::cookies::{Cookie, CookieBoxError, CookieName, IncomingConfig, OutgoingConfig};
 //!use cookiebox::{Attributes, SameSite};
 //!use serde_json::json;
 //!
@@ -58,10 +58,10 @@
 //!    
 //!    // In most cases, the default serialization should be sufficient. However, if needed,
 //!    // you can customize the way the cookie value is serialized by implementing this method.
-//!    fn serialize(values: Self::Insert) -> serde_json::Value {
-//!        json!(
+//!    fn serialize(values: Self::Insert) -> Result<serde_json::Value, CookieBoxError> {
+//!        Ok(json!(
 //!             format!("String: {} - i32: {}", values.0, values.1)
-//!        )
+//!        ))
 //!    }
 //!    
 //!    // Set the appropriate attribute for the cookie, check `Attributes` for more details
@@ -70,22 +70,54 @@
 //!    }
 //!}
 //!// Once defined, you need to add these cookies in a collection struct and use derive macro to implement FromRequest
-//!// Note: The macro only allows struct with either a single unnamed field or multiple named fields
+//!// Note: The macro only allows struct with either unnamed fields or named fields
 //!#[derive(FromRequest)]
 //!pub struct CookieCollection<'c>(Cookie<'c, MyCookie>);
 //!
 //!```
 //! Now, your cookies can be accessed in request handlers by using `CookieCollection` as a parameter.
 //!
+//! `insert` returns `Err(CookieBoxError::Serialization)` when `serialize` fails, which can be propagated from handlers with `?`.
+//! Earlier versions of `serialize` returned a bare `serde_json::Value`, existing implementations migrate by wrapping the returned value in `Ok`.
+//!
 //! If you would like to see an example, click [here](https://github.com/MSalah73/cookiebox/tree/master/examples).
 
 mod attributes;
+mod backed;
+mod codec;
 pub mod cookies;
+mod crypto;
+mod extractors;
 mod middleware;
+#[cfg(feature = "random")]
+mod random;
+mod same_site;
+mod spans;
 mod storage;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
-pub use attributes::Attributes;
-pub use biscotti::{Expiration, Key, Processor, ProcessorConfig, SameSite, config, time};
+#[cfg(feature = "psl")]
+pub use attributes::PublicSuffixError;
+pub use attributes::{Attributes, Priority, SetCookieParseError};
+pub use backed::{AsyncCookieBacked, CookieBacked, SessionBacked, SessionCookie, SessionStore};
+pub use biscotti::config::{CryptoAlgorithm, CryptoRule};
+pub use biscotti::{
+    Expiration, Key, Processor, ProcessorConfig, RequestCookie, ResponseCookie, SameSite, config,
+    time,
+};
 pub use cookiebox_macros;
-pub use middleware::CookieMiddleware;
-pub use storage::Storage;
+pub use crypto::{
+    ConfigError, CryptoRuleExt, ProcessorConfigExt, encrypted_processor,
+    encrypted_processor_with_fallbacks, signed_processor, signed_processor_with_fallbacks,
+};
+pub use extractors::{CookieJar, OptionalCookie};
+pub use middleware::{
+    CookieMiddleware, DEFAULT_MAX_HEADER_SIZE, InvalidEncodingAction, ResponseCookieError,
+    UnknownCookieAction,
+};
+#[cfg(feature = "psl")]
+pub use publicsuffix;
+#[cfg(feature = "random")]
+pub use random::random_token;
+pub use storage::{Storage, storage};
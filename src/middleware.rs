@@ -1,14 +1,24 @@
-use actix_utils::future::{Ready, ready};
 use actix_web::{
     HttpMessage, HttpResponse,
     dev::{ResponseHead, Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
-    http::header::{HeaderValue, SET_COOKIE},
+    http::{
+        StatusCode,
+        header::{HeaderValue, InvalidHeaderValue, SET_COOKIE, USER_AGENT},
+    },
 };
 use anyhow::anyhow;
-use biscotti::{Processor, RequestCookie, errors::ProcessIncomingError};
-use std::{future::Future, pin::Pin, rc::Rc};
+use biscotti::{Processor, RequestCookie, SameSite, errors::ProcessIncomingError};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::future::{Future, Ready, ready};
+use std::{pin::Pin, rc::Rc};
 
 use crate::Storage;
+use crate::attributes::Attributes;
+use crate::codec::ValueEncoding;
+use crate::cookies::{CookieEnv, CookieName, ReadPolicy, RequiresEncryption, RequiresSigning};
+use crate::same_site::is_incompatible_client;
+use crate::spans::{OperationSpan, Outcome, RequestSpan};
 
 /// cookiebox's cookie middleware
 ///
@@ -36,12 +46,447 @@ use crate::Storage;
 /// ```
 pub struct CookieMiddleware {
     processor: Rc<Processor>,
+    config: MiddlewareConfig,
 }
 
 impl CookieMiddleware {
     pub fn new(processor: Processor) -> Self {
         Self {
             processor: Rc::new(processor),
+            config: MiddlewareConfig::default(),
+        }
+    }
+    /// Adds the name of the cookie type `T` to the list of allowed incoming cookies
+    ///
+    /// Once any name is allowed, incoming cookies outside the list are handled according to [UnknownCookieAction].
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::CookieName;
+    /// use cookiebox::{CookieMiddleware, Processor, ProcessorConfig, UnknownCookieAction};
+    ///
+    /// #[cookie(name = "__my-cookie")]
+    /// pub struct MyCookie;
+    ///
+    /// let processor: Processor = ProcessorConfig::default().into();
+    ///
+    /// let middleware = CookieMiddleware::new(processor)
+    ///     .allow_cookie::<MyCookie>()
+    ///     .on_unknown_cookie(UnknownCookieAction::Reject);
+    /// ```
+    pub fn allow_cookie<T: CookieName>(self) -> Self {
+        self.allow_names([T::COOKIE_NAME])
+    }
+    /// Adds `names` to the list of allowed incoming cookies
+    ///
+    /// Once any name is allowed, incoming cookies outside the list are handled according to [UnknownCookieAction].
+    pub fn allow_names<I, N>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>,
+    {
+        self.config
+            .allowed_names
+            .get_or_insert_with(HashSet::new)
+            .extend(names.into_iter().map(Into::into));
+        self
+    }
+    /// Adds `names` to the list of cookies the middleware passes through untouched
+    ///
+    /// These are cookies owned by someone else, e.g. analytics cookies set by the frontend. A passthrough cookie is stored in
+    /// the [Storage] request collection exactly as it was sent, so it is not percent-decoded, decrypted, or verified, and it
+    /// is kept regardless of the allowed list. It can be read with `get_raw`. On the response side, a passthrough cookie
+    /// queued by a handler is dropped with a `warn` log rather than emitted, while `Set-Cookie` headers a handler sets
+    /// directly are kept.
+    /// ```no_run
+    /// use cookiebox::{CookieMiddleware, Processor, ProcessorConfig};
+    ///
+    /// let processor: Processor = ProcessorConfig::default().into();
+    ///
+    /// let middleware = CookieMiddleware::new(processor).passthrough_names(["_ga", "_gid"]);
+    /// ```
+    pub fn passthrough_names<I, N>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>,
+    {
+        self.config
+            .passthrough_names
+            .extend(names.into_iter().map(Into::into));
+        self
+    }
+    /// Sets the action taken when an incoming cookie is not in the allowed list
+    ///
+    /// This has no effect unless allowed names were added through [CookieMiddleware::allow_cookie] or [CookieMiddleware::allow_names].
+    pub fn on_unknown_cookie(mut self, action: UnknownCookieAction) -> Self {
+        self.config.unknown_cookie_action = action;
+        self
+    }
+    /// Sets whether the middleware runs in read-only mode
+    ///
+    /// In read-only mode the response phase is skipped entirely, so no `Set-Cookie` headers are emitted even if a handler
+    /// inserts or removes cookies. Incoming cookies are still extracted as usual.
+    pub fn read_only(mut self, value: bool) -> Self {
+        self.config.read_only = value;
+        self
+    }
+    /// Sets the maximum total size in bytes of the `Cookie` request headers
+    ///
+    /// Requests with larger cookie headers are rejected with `400 Bad Request` before any cookie is parsed.
+    /// Defaults to [DEFAULT_MAX_HEADER_SIZE].
+    pub fn max_header_size(mut self, bytes: usize) -> Self {
+        self.config.max_header_size = bytes;
+        self
+    }
+    /// Sets whether malformed pairs in the `Cookie` header are skipped
+    ///
+    /// By default, a pair without `=` or with an empty name fails the request with `500 Internal Server Error`.
+    /// When enabled, such pairs are logged at the `warn` level and skipped, and the remaining cookies are extracted as usual.
+    pub fn skip_malformed_cookies(mut self, value: bool) -> Self {
+        self.config.skip_malformed = value;
+        self
+    }
+    /// Sets the action taken when a `Cookie` header carries bytes outside of visible ASCII
+    ///
+    /// By default, such a header fails the request with `500 Internal Server Error`. With [InvalidEncodingAction::Replace]
+    /// or [InvalidEncodingAction::Skip], pairs that are valid UTF-8 are extracted as usual, and only the pairs that are not
+    /// are lossily converted or skipped, so a single bad byte does not fail the whole request.
+    /// ```no_run
+    /// use cookiebox::{CookieMiddleware, InvalidEncodingAction, Processor, ProcessorConfig};
+    ///
+    /// let processor: Processor = ProcessorConfig::default().into();
+    ///
+    /// let middleware = CookieMiddleware::new(processor).on_invalid_encoding(InvalidEncodingAction::Skip);
+    /// ```
+    pub fn on_invalid_encoding(mut self, action: InvalidEncodingAction) -> Self {
+        self.config.invalid_encoding_action = action;
+        self
+    }
+    /// Sets the [ReadPolicy] used by `get` and the methods built on it when a cookie is both read and written in the same request
+    ///
+    /// Defaults to [ReadPolicy::Client]. Cookie types can override it with [IncomingConfig::read_policy](crate::cookies::IncomingConfig::read_policy).
+    pub fn read_policy(mut self, policy: ReadPolicy) -> Self {
+        self.config.read_policy = policy;
+        self
+    }
+    /// Sets the attributes inherited by cookie types whose attributes are created with [Attributes::inherit]
+    ///
+    /// Attributes set by the cookie type take precedence over the ones set here. Defaults to [Attributes::default].
+    /// ```no_run
+    /// use cookiebox::{Attributes, CookieMiddleware, Processor, ProcessorConfig, SameSite};
+    ///
+    /// let processor: Processor = ProcessorConfig::default().into();
+    ///
+    /// let middleware = CookieMiddleware::new(processor)
+    ///     .default_attributes(Attributes::default().secure(true).same_site(SameSite::Strict));
+    /// ```
+    pub fn default_attributes(mut self, attributes: Attributes<'static>) -> Self {
+        self.config.default_attributes = Rc::new(attributes);
+        self
+    }
+    /// Asserts that the processor has a signing rule for the cookie type `T`
+    ///
+    /// # Panics
+    /// Panics if the [Processor] does not sign `T::COOKIE_NAME`, so a missing [CryptoRule](crate::config::CryptoRule) is caught at start up.
+    /// ```no_run
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::CookieName;
+    /// use cookiebox::config::{CryptoAlgorithm, CryptoRule};
+    /// use cookiebox::{CookieMiddleware, Key, Processor, ProcessorConfig};
+    ///
+    /// #[cookie(name = "__session", signed)]
+    /// pub struct Session;
+    ///
+    /// let mut config = ProcessorConfig::default();
+    /// config.crypto_rules.push(CryptoRule {
+    ///     cookie_names: vec!["__session".to_string()],
+    ///     algorithm: CryptoAlgorithm::Signing,
+    ///     key: Key::generate(),
+    ///     fallbacks: vec![],
+    /// });
+    /// let processor: Processor = config.into();
+    ///
+    /// let middleware = CookieMiddleware::new(processor).require_signed::<Session>();
+    /// ```
+    pub fn require_signed<T: RequiresSigning>(self) -> Self {
+        assert!(
+            self.processor.will_sign(T::COOKIE_NAME),
+            "`{}` requires signing, but the processor has no signing rule for it",
+            T::COOKIE_NAME
+        );
+        self
+    }
+    /// Asserts that the processor has an encryption rule for the cookie type `T`
+    ///
+    /// # Panics
+    /// Panics if the [Processor] does not encrypt `T::COOKIE_NAME`, so a missing [CryptoRule](crate::config::CryptoRule) is caught at start up.
+    pub fn require_encrypted<T: RequiresEncryption>(self) -> Self {
+        assert!(
+            self.processor.will_encrypt(T::COOKIE_NAME),
+            "`{}` requires encryption, but the processor has no encryption rule for it",
+            T::COOKIE_NAME
+        );
+        self
+    }
+    /// Restricts the emission of `Set-Cookie` headers to responses whose status satisfies `predicate`
+    ///
+    /// Cookies queued for any other response are dropped. By default, cookies are attached to every response.
+    /// ```no_run
+    /// use cookiebox::{CookieMiddleware, Processor, ProcessorConfig};
+    ///
+    /// let processor: Processor = ProcessorConfig::default().into();
+    ///
+    /// // Only set cookies on successful responses
+    /// let middleware = CookieMiddleware::new(processor).emit_when(|status| status.is_success());
+    /// ```
+    pub fn emit_when<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(StatusCode) -> bool + 'static,
+    {
+        self.config.emit_when = Some(Rc::new(predicate));
+        self
+    }
+    /// Sets whether the `Set-Cookie` headers are skipped on server error responses, `false` by default
+    ///
+    /// When enabled, cookies queued for a response with a `5xx` status are dropped, so a half-completed handler does not
+    /// leave side effects such as a tentatively inserted session on the client. This is a shorthand for the common case of
+    /// [CookieMiddleware::emit_when], and both apply when set. The `Set-Cookie` headers a handler sets directly are kept.
+    /// ```no_run
+    /// use cookiebox::{CookieMiddleware, Processor, ProcessorConfig};
+    ///
+    /// let processor: Processor = ProcessorConfig::default().into();
+    ///
+    /// let middleware = CookieMiddleware::new(processor).skip_on_error(true);
+    /// ```
+    pub fn skip_on_error(mut self, value: bool) -> Self {
+        self.config.skip_on_error = value;
+        self
+    }
+    /// Sets whether cookies with `SameSite=None` are also sent without `SameSite` to clients that mishandle the attribute
+    ///
+    /// Some older clients treat `SameSite=None` as `SameSite=Strict`, or drop the cookie altogether, which breaks cookies
+    /// meant for third-party contexts. When enabled and the `User-Agent` of the request matches one of these clients, every
+    /// `SameSite=None` cookie is followed by a compatibility `Set-Cookie` header for the same cookie without `SameSite`.
+    /// Both share the name, path, and domain, so the compatibility cookie is the one the client keeps, while it already
+    /// behaves as `SameSite=None` there. Other clients receive the cookies unchanged.
+    ///
+    /// The clients are detected from the list of incompatible clients published by the Chromium project:
+    /// - iOS 12, any browser
+    /// - Safari and embedded browsers on macOS 10.14
+    /// - Chrome and Chromium 51 to 66
+    /// - UC Browser on Android before 12.13.2
+    ///
+    /// Disabled by default.
+    pub fn same_site_none_compat(mut self, value: bool) -> Self {
+        self.config.same_site_none_compat = value;
+        self
+    }
+    /// Restricts the middleware to requests whose path starts with one of `prefixes`
+    ///
+    /// Prefixes match whole path segments, so `/admin` matches `/admin` and `/admin/users` but not `/administrator`.
+    /// Requests on any other path are passed to the service untouched: the `Cookie` header is not parsed, no [Storage] is
+    /// added to the request, and no `Set-Cookie` header is emitted. Cookie extractors fail on these paths.
+    /// By default, every path is processed.
+    /// ```no_run
+    /// use cookiebox::{CookieMiddleware, Processor, ProcessorConfig};
+    ///
+    /// let processor: Processor = ProcessorConfig::default().into();
+    ///
+    /// // Leave the public API alone
+    /// let middleware = CookieMiddleware::new(processor).only_paths(["/admin", "/account"]);
+    /// ```
+    pub fn only_paths<I, P>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.config
+            .only_paths
+            .get_or_insert_with(Vec::new)
+            .extend(prefixes.into_iter().map(Into::into));
+        self
+    }
+    /// Excludes requests whose path starts with one of `prefixes` from the middleware
+    ///
+    /// Prefixes match whole path segments, like [CookieMiddleware::only_paths]. Skipped paths take precedence over the
+    /// ones added with [CookieMiddleware::only_paths], and are passed to the service untouched.
+    pub fn skip_paths<I, P>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.config
+            .skip_paths
+            .extend(prefixes.into_iter().map(Into::into));
+        self
+    }
+    /// Sets whether the `cookiebox.request` span records the names of the parsed and emitted cookies
+    ///
+    /// With the `tracing` feature, every request handled by the middleware runs in a `cookiebox.request` span at the `debug`
+    /// level, which records the number of cookies parsed, failed, and emitted. When enabled, the span also records the
+    /// comma-separated names as `cookies.parsed_names` and `cookies.emitted_names`. Cookie values are never recorded.
+    ///
+    /// Disabled by default.
+    #[cfg(feature = "tracing")]
+    pub fn trace_cookie_names(mut self, value: bool) -> Self {
+        self.config.trace_cookie_names = value;
+        self
+    }
+}
+
+/// The action taken by [CookieMiddleware] when a request carries a cookie that is not in the allowed list
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownCookieAction {
+    /// Drop the cookie from the storage and carry on with the request
+    #[default]
+    Strip,
+    /// Respond with `400 Bad Request` without calling the handler
+    Reject,
+}
+
+/// The action taken by [CookieMiddleware] when a `Cookie` header carries bytes outside of visible ASCII
+///
+/// Such bytes are not allowed by RFC 6265, but some clients send latin-1 or UTF-8 encoded values anyway.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InvalidEncodingAction {
+    /// Respond with `500 Internal Server Error` without calling the handler
+    #[default]
+    Reject,
+    /// Keep the pairs that are valid UTF-8, and replace invalid sequences in the other pairs with `U+FFFD`
+    Replace,
+    /// Keep the pairs that are valid UTF-8, and drop the other pairs with a `warn` log
+    Skip,
+}
+
+/// The error returned by [CookieMiddleware] when a queued cookie cannot be attached to the response
+///
+/// The response fails with `500 Internal Server Error`. This happens when the processed cookie is not a valid header value,
+/// e.g. when a custom [OutgoingConfig::serialize](crate::cookies::OutgoingConfig::serialize) produces control characters
+/// and percent-encoding is disabled.
+///
+/// The error names the cookie and carries its processed value, truncated to the first 32 characters, to help find the
+/// offending `serialize` implementation. The value may hold a secret, so the [Display](std::fmt::Display) and [Debug] output
+/// only show its length, and [ResponseCookieError::value] has to be called to read it.
+#[derive(thiserror::Error)]
+#[error(
+    "Failed to attach the `{name}` cookie with a value of {value_len} bytes to the outgoing response: {source}"
+)]
+pub struct ResponseCookieError {
+    name: String,
+    value: String,
+    value_len: usize,
+    #[source]
+    source: InvalidHeaderValue,
+}
+
+impl std::fmt::Debug for ResponseCookieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseCookieError")
+            .field("name", &self.name)
+            .field(
+                "value",
+                &format_args!("<redacted, {} bytes>", self.value_len),
+            )
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+impl ResponseCookieError {
+    // Values longer than this are truncated, so a large cookie does not flood the logs
+    const MAX_VALUE_CHARS: usize = 32;
+
+    fn new(name: String, value: &str, source: InvalidHeaderValue) -> Self {
+        let value_len = value.len();
+        let value = match value.char_indices().nth(Self::MAX_VALUE_CHARS) {
+            Some((end, _)) => format!("{}...", &value[..end]),
+            None => value.to_string(),
+        };
+
+        ResponseCookieError {
+            name,
+            value,
+            value_len,
+            source,
+        }
+    }
+    /// Returns the name of the cookie that failed
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Returns the processed value of the cookie that failed, truncated to the first 32 characters
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// The default limit of [CookieMiddleware::max_header_size], 8 KiB
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 8 * 1024;
+
+#[derive(Clone)]
+pub(crate) struct MiddlewareConfig {
+    allowed_names: Option<HashSet<String>>,
+    passthrough_names: HashSet<String>,
+    unknown_cookie_action: UnknownCookieAction,
+    read_only: bool,
+    skip_malformed: bool,
+    read_policy: ReadPolicy,
+    emit_when: Option<Rc<dyn Fn(StatusCode) -> bool>>,
+    skip_on_error: bool,
+    max_header_size: usize,
+    default_attributes: Rc<Attributes<'static>>,
+    only_paths: Option<Vec<String>>,
+    skip_paths: Vec<String>,
+    same_site_none_compat: bool,
+    trace_cookie_names: bool,
+    invalid_encoding_action: InvalidEncodingAction,
+}
+
+impl MiddlewareConfig {
+    // Whether the request path is subject to cookie processing according to `only_paths` and `skip_paths`
+    fn processes(&self, path: &str) -> bool {
+        if self
+            .skip_paths
+            .iter()
+            .any(|prefix| matches_path_prefix(path, prefix))
+        {
+            return false;
+        }
+
+        match &self.only_paths {
+            Some(prefixes) => prefixes
+                .iter()
+                .any(|prefix| matches_path_prefix(path, prefix)),
+            None => true,
+        }
+    }
+}
+
+// Matches whole path segments, so `/admin` does not match `/administrator`
+fn matches_path_prefix(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'),
+        None => false,
+    }
+}
+
+impl Default for MiddlewareConfig {
+    fn default() -> Self {
+        Self {
+            allowed_names: None,
+            passthrough_names: HashSet::new(),
+            unknown_cookie_action: UnknownCookieAction::default(),
+            read_only: false,
+            skip_malformed: false,
+            read_policy: ReadPolicy::default(),
+            emit_when: None,
+            skip_on_error: false,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            default_attributes: Rc::new(Attributes::default()),
+            only_paths: None,
+            skip_paths: Vec::new(),
+            same_site_none_compat: false,
+            trace_cookie_names: false,
+            invalid_encoding_action: InvalidEncodingAction::default(),
         }
     }
 }
@@ -61,6 +506,7 @@ where
         ready(Ok(InnerCookieMiddleware {
             service: Rc::new(service),
             processor: Rc::clone(&self.processor),
+            config: Rc::new(self.config.clone()),
         }))
     }
 }
@@ -73,9 +519,17 @@ where
         .into()
 }
 
+pub fn e400<T>(e: T) -> actix_web::Error
+where
+    T: std::fmt::Debug + std::fmt::Display + 'static,
+{
+    actix_web::error::InternalError::from_response(e, HttpResponse::BadRequest().finish()).into()
+}
+
 pub struct InnerCookieMiddleware<S> {
     service: Rc<S>,
     processor: Rc<Processor>,
+    config: Rc<MiddlewareConfig>,
 }
 
 impl<S, B> Service<ServiceRequest> for InnerCookieMiddleware<S>
@@ -92,24 +546,92 @@ where
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let service = Rc::clone(&self.service);
         let processor = Rc::clone(&self.processor);
-        let storage = Storage::new();
+        let config = Rc::clone(&self.config);
+
+        if !config.processes(req.path()) {
+            return Box::pin(async move { service.call(req).await });
+        }
+
+        let mut storage = Storage::new();
 
-        Box::pin(async move {
-            extract_cookies(&req, &processor, storage.clone()).map_err(e500)?;
+        // The part of the path matched before reaching the middleware is the scope it wraps
+        let match_info = req.match_info();
+        let base_path =
+            &match_info.as_str()[..match_info.as_str().len() - match_info.unprocessed().len()];
+        let base_path = if base_path.is_empty() { "/" } else { base_path };
+
+        let connection_info = req.connection_info();
+        storage.env = Rc::new(CookieEnv::new(
+            connection_info.scheme() == "https",
+            connection_info.host(),
+            req.path(),
+            base_path,
+        ));
+        drop(connection_info);
+        storage.read_policy = config.read_policy;
+        storage.default_attributes = Rc::clone(&config.default_attributes);
+
+        let request_span = RequestSpan::new(config.trace_cookie_names);
+        Box::pin(request_span.run(move |mut request_span| async move {
+            let span = OperationSpan::middleware("extract");
+            let extracted = extract_cookies(
+                &req,
+                &processor,
+                &config,
+                storage.clone(),
+                &mut request_span,
+            );
+            request_span.record();
+            span.end(match extracted {
+                Ok(_) => Outcome::Ok,
+                Err(_) => Outcome::Error,
+            });
+            extracted?;
 
             req.extensions_mut().insert(storage.clone());
 
+            // The request is handed over to the service, so the client is detected beforehand
+            let same_site_compat = config.same_site_none_compat
+                && req
+                    .headers()
+                    .get(USER_AGENT)
+                    .and_then(|user_agent| user_agent.to_str().ok())
+                    .is_some_and(is_incompatible_client);
+
             let mut response = service.call(req).await?;
 
-            process_response_cookies(
+            if config.read_only {
+                return Ok(response);
+            }
+
+            if let Some(emit_when) = &config.emit_when
+                && !emit_when(response.status())
+            {
+                return Ok(response);
+            }
+
+            if config.skip_on_error && response.status().is_server_error() {
+                return Ok(response);
+            }
+
+            let span = OperationSpan::middleware("emit");
+            let emitted = process_response_cookies(
                 response.response_mut().head_mut(),
                 &processor,
                 storage.clone(),
-            )
-            .map_err(e500)?;
+                same_site_compat,
+                &config.passthrough_names,
+                &mut request_span,
+            );
+            request_span.record();
+            span.end(match emitted {
+                Ok(_) => Outcome::Ok,
+                Err(_) => Outcome::Error,
+            });
+            emitted.map_err(e500)?;
 
             Ok(response)
-        })
+        }))
     }
 }
 
@@ -120,18 +642,80 @@ where
 fn extract_cookies(
     req: &ServiceRequest,
     processor: &Processor,
+    config: &MiddlewareConfig,
     storage: Storage,
-) -> Result<(), anyhow::Error> {
-    let cookie_header = req.headers().get(actix_web::http::header::COOKIE);
+    request_span: &mut RequestSpan,
+) -> Result<(), actix_web::Error> {
+    let header_size: usize = req
+        .headers()
+        .get_all(actix_web::http::header::COOKIE)
+        .map(|header| header.len())
+        .sum();
+
+    if header_size > config.max_header_size {
+        return Err(e400(anyhow!(
+            "The cookie header size of {} bytes exceeds the limit of {} bytes",
+            header_size,
+            config.max_header_size
+        )));
+    }
 
-    let cookie_header = match cookie_header {
-        Some(header) => header
-            .to_str()
-            .map_err(|e| anyhow!("Invalid cookie header encoding: {}", e))?,
-        None => return Ok(()),
-    };
+    // A request can carry several `Cookie` headers, e.g. when a proxy splits them
+    let cookie_headers = req
+        .headers()
+        .get_all(actix_web::http::header::COOKIE)
+        .map(|header| decode_cookie_header(header, config.invalid_encoding_action))
+        .collect::<Result<Vec<_>, _>>()?;
+    let cookie_headers: Vec<&str> = cookie_headers.iter().map(Cow::as_ref).collect();
 
-    for cookie in cookie_header.split(';') {
+    parse_cookie_headers(&cookie_headers, processor, config, &storage, request_span)
+}
+/// Decodes a `Cookie` header value into a string according to `action`
+///
+/// The header is split into pairs at the byte level, `;` never being part of a multi-byte UTF-8 sequence, so only the
+/// pairs that are not valid UTF-8 are replaced or skipped.
+fn decode_cookie_header(
+    header: &HeaderValue,
+    action: InvalidEncodingAction,
+) -> Result<Cow<'_, str>, actix_web::Error> {
+    match header.to_str() {
+        Ok(header) => return Ok(Cow::Borrowed(header)),
+        Err(e) if action == InvalidEncodingAction::Reject => {
+            return Err(e500(anyhow!("Invalid cookie header encoding: {}", e)));
+        }
+        Err(_) => {}
+    }
+
+    let mut pairs = Vec::new();
+    for pair in header.as_bytes().split(|byte| *byte == b';') {
+        match std::str::from_utf8(pair) {
+            Ok(pair) => pairs.push(Cow::Borrowed(pair)),
+            Err(_) if action == InvalidEncodingAction::Replace => {
+                pairs.push(String::from_utf8_lossy(pair))
+            }
+            Err(_) => {
+                // The value may be a secret, so only the name is logged
+                let name = pair.split(|byte| *byte == b'=').next().unwrap_or_default();
+                log::warn!(
+                    "Skipping request cookie `{}` of {} bytes that is not valid UTF-8",
+                    String::from_utf8_lossy(name).trim(),
+                    pair.len()
+                );
+            }
+        }
+    }
+
+    Ok(Cow::Owned(pairs.join(";")))
+}
+/// Parse the `Cookie` header values into the storage request collection, processing each cookie with the processor
+pub(crate) fn parse_cookie_headers(
+    cookie_headers: &[&str],
+    processor: &Processor,
+    config: &MiddlewareConfig,
+    storage: &Storage,
+    request_span: &mut RequestSpan,
+) -> Result<(), actix_web::Error> {
+    for cookie in cookie_headers.iter().flat_map(|header| header.split(';')) {
         if cookie.chars().all(char::is_whitespace) {
             continue;
         }
@@ -139,37 +723,75 @@ fn extract_cookies(
         let (name, value) = match cookie.split_once('=') {
             Some((name, value)) => (name.trim(), value.trim()),
             None => {
-                return Err(anyhow!(
+                request_span.failed();
+                // A bare pair may be a secret value, so only its length is logged
+                if config.skip_malformed {
+                    log::warn!(
+                        "Skipping malformed request cookie: a pair of {} bytes has no `=`",
+                        cookie.trim().len()
+                    );
+                    continue;
+                }
+                return Err(e500(anyhow!(
                     "Expected a name-value pair, but no `=` was found in `{}`",
-                    cookie.to_string()
-                ));
+                    cookie
+                )));
             }
         };
 
         if name.is_empty() {
-            return Err(anyhow!(
+            request_span.failed();
+            if config.skip_malformed {
+                log::warn!(
+                    "Skipping malformed request cookie: a pair with an empty name was dropped"
+                );
+                continue;
+            }
+            return Err(e500(anyhow!(
                 "The name of a cookie cannot be empty, but found an empty name with `{}` as value",
-                value.to_string()
-            ));
+                value
+            )));
+        }
+
+        if config.passthrough_names.contains(name) {
+            request_span.parsed(name);
+            storage.append_request_cookie(RequestCookie::new(name.to_owned(), value.to_owned()));
+            continue;
         }
 
         let cookie = match processor.process_incoming(name, value) {
             Ok(c) => c,
             Err(e) => {
+                request_span.failed();
                 let t = match e {
                     ProcessIncomingError::Crypto(_) => "an encrypted",
                     ProcessIncomingError::Decoding(_) => "a singed",
                     _ => "an unknown",
                 };
-                return Err(anyhow!(
+                return Err(e500(anyhow!(
                     "Failed to process `{}` as {t} request cookie",
                     name
-                ));
+                )));
             }
         };
 
+        if let Some(allowed_names) = &config.allowed_names
+            && !allowed_names.contains(cookie.name())
+        {
+            match config.unknown_cookie_action {
+                UnknownCookieAction::Strip => continue,
+                UnknownCookieAction::Reject => {
+                    return Err(e400(anyhow!(
+                        "`{}` is not an allowed request cookie",
+                        cookie.name()
+                    )));
+                }
+            }
+        }
+
+        request_span.parsed(cookie.name());
         let cookie = RequestCookie::new(cookie.name().to_owned(), cookie.value().to_owned());
-        storage.request_storage.borrow_mut().append(cookie);
+        storage.append_request_cookie(cookie);
     }
 
     Ok(())
@@ -179,12 +801,69 @@ fn process_response_cookies(
     response: &mut ResponseHead,
     processor: &Processor,
     storage: Storage,
-) -> Result<(), anyhow::Error> {
-    let response_storage = storage.response_storage.take();
-    for cookie in response_storage.header_values(processor) {
-        let cookie = HeaderValue::from_str(&cookie)
-            .map_err(|e| anyhow!("Failed to attached cookies to outgoing response: {}", e))?;
-        response.headers_mut().append(SET_COOKIE, cookie);
+    same_site_compat: bool,
+    passthrough_names: &HashSet<String>,
+    request_span: &mut RequestSpan,
+) -> Result<(), ResponseCookieError> {
+    let mut response_storage = storage.response_storage.take();
+    let mut options = storage.response_options.take();
+
+    // Cookies written by the handler take precedence over sliding expiration refreshes
+    let mut refresh_options = storage.refresh_options.take();
+    for cookie in storage.refresh_storage.take().iter() {
+        if response_storage.get(cookie.id()).is_none() {
+            if let Some(refresh_options) = refresh_options.remove(&cookie.id()) {
+                options.insert(cookie.id(), refresh_options);
+            }
+            response_storage.insert(cookie.clone());
+        }
+    }
+
+    // Appending keeps the `Set-Cookie` headers the handler or inner middleware already set
+    for cookie in response_storage.iter() {
+        if passthrough_names.contains(cookie.name()) {
+            log::warn!(
+                "`{}` is a passthrough cookie, dropping the cookie queued for it",
+                cookie.name()
+            );
+            continue;
+        }
+
+        let options = options.get(&cookie.id()).copied().unwrap_or_default();
+
+        let mut cookies = vec![cookie.clone()];
+        if same_site_compat && cookie.same_site() == Some(SameSite::None) {
+            cookies.push(cookie.clone().set_same_site(None));
+        }
+
+        for cookie in cookies {
+            let name = cookie.name().to_string();
+            let value = cookie.value().to_string();
+            let mut cookie = processor.process_outgoing(cookie);
+            // Signed and encrypted values are left as the processor wrote them
+            if options.encoding == ValueEncoding::Raw
+                && !processor.will_sign(&name)
+                && !processor.will_encrypt(&name)
+            {
+                cookie = cookie.set_value(value);
+            }
+
+            let mut header_value = cookie.to_string();
+            // `ResponseCookie` has no `Priority` attribute, so it is appended to the serialized cookie
+            if let Some(priority) = options.priority {
+                header_value.push_str("; Priority=");
+                header_value.push_str(priority.as_str());
+            }
+            let header_value = match HeaderValue::from_str(&header_value) {
+                Ok(header_value) => header_value,
+                Err(source) => {
+                    request_span.failed();
+                    return Err(ResponseCookieError::new(name, cookie.value(), source));
+                }
+            };
+            request_span.emitted(&name);
+            response.headers_mut().append(SET_COOKIE, header_value);
+        }
     }
 
     Ok(())
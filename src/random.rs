@@ -0,0 +1,50 @@
+//! Secure random values for session ids, CSRF tokens, and nonces, available with the `random` feature
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+/// Generates a token from `bytes` random bytes, encoded as unpadded base64url
+///
+/// The bytes come from the operating system CSPRNG, so the token is suitable as a session id or CSRF token.
+/// The encoded token is `4 * bytes / 3` characters long, rounded up, and only uses `A-Z`, `a-z`, `0-9`, `-`, and `_`.
+///
+/// # Panics
+///
+/// Panics if the operating system fails to provide random bytes.
+///
+/// ```
+/// let token = cookiebox::random_token(32);
+///
+/// assert_eq!(token.len(), 43);
+/// ```
+pub fn random_token(bytes: usize) -> String {
+    let mut buffer = vec![0u8; bytes];
+    getrandom::fill(&mut buffer).expect("The operating system failed to provide random bytes");
+
+    URL_SAFE_NO_PAD.encode(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::random_token;
+    use std::collections::HashSet;
+
+    #[test]
+    fn token_length_and_charset() {
+        for (bytes, length) in [(0, 0), (1, 2), (16, 22), (32, 43), (33, 44)] {
+            let token = random_token(bytes);
+
+            assert_eq!(token.len(), length);
+            assert!(
+                token
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            );
+        }
+    }
+    #[test]
+    fn tokens_do_not_repeat() {
+        let tokens: HashSet<String> = (0..1000).map(|_| random_token(16)).collect();
+
+        assert_eq!(tokens.len(), 1000);
+    }
+}
@@ -0,0 +1,101 @@
+//! Detection of the clients that mishandle `SameSite=None`, for [CookieMiddleware::same_site_none_compat](crate::CookieMiddleware::same_site_none_compat)
+
+/// Returns whether the client sending `user_agent` is known to mishandle `SameSite=None`
+///
+/// The heuristics follow the list of incompatible clients published by the Chromium project:
+/// - iOS 12, where every browser treats `SameSite=None` as `SameSite=Strict`
+/// - Safari and embedded browsers on macOS 10.14, which do the same
+/// - Chrome and Chromium 51 to 66, which reject cookies with `SameSite=None`
+/// - UC Browser on Android before 12.13.2, which also rejects them
+pub(crate) fn is_incompatible_client(user_agent: &str) -> bool {
+    has_webkit_same_site_bug(user_agent) || drops_unrecognized_same_site(user_agent)
+}
+
+fn has_webkit_same_site_bug(user_agent: &str) -> bool {
+    let ios_12 = user_agent.contains("iPhone OS 12_") || user_agent.contains("iPad; CPU OS 12_");
+    let macos_10_14 = user_agent.contains("Macintosh; Intel Mac OS X 10_14");
+
+    ios_12 || (macos_10_14 && (is_safari(user_agent) || is_mac_embedded_browser(user_agent)))
+}
+
+fn drops_unrecognized_same_site(user_agent: &str) -> bool {
+    // UC Browser also advertises the Chrome version it is built on, so it is checked first
+    if let Some(version) = version_after(user_agent, "UCBrowser/") {
+        return version < vec![12, 13, 2];
+    }
+
+    version_after(user_agent, "Chrome/")
+        .or_else(|| version_after(user_agent, "Chromium/"))
+        .is_some_and(|version| (51..=66).contains(&version[0]))
+}
+
+fn is_safari(user_agent: &str) -> bool {
+    user_agent.contains("Version/")
+        && user_agent.contains("Safari")
+        && !is_chromium_based(user_agent)
+}
+
+fn is_mac_embedded_browser(user_agent: &str) -> bool {
+    user_agent.contains("AppleWebKit/") && !user_agent.contains("Safari")
+}
+
+fn is_chromium_based(user_agent: &str) -> bool {
+    user_agent.contains("Chrome/") || user_agent.contains("Chromium/")
+}
+
+// Parses the dotted version that follows `product`, e.g. `[66, 0, 3359]` for `Chrome/66.0.3359.117` and `product` `Chrome/`
+fn version_after(user_agent: &str, product: &str) -> Option<Vec<u32>> {
+    let start = user_agent.find(product)? + product.len();
+    let version: Vec<u32> = user_agent[start..]
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()?
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect();
+
+    (!version.is_empty()).then_some(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_incompatible_client;
+
+    #[test]
+    fn incompatible_clients() {
+        for user_agent in [
+            // iOS 12
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 12_1 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/12.0 Mobile/15E148 Safari/604.1",
+            "Mozilla/5.0 (iPad; CPU OS 12_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) CriOS/74.0.3729.121 Mobile/15E148 Safari/605.1",
+            // Safari and an embedded browser on macOS 10.14
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_14_3) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/12.0.3 Safari/605.1.15",
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_14_3) AppleWebKit/605.1.15 (KHTML, like Gecko)",
+            // Chrome 51 to 66
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/51.0.2704.103 Safari/537.36",
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/66.0.3359.117 Safari/537.36",
+            // UC Browser before 12.13.2
+            "Mozilla/5.0 (Linux; U; Android 8.1.0; en-US; Nexus 6P Build/OPM7.181205.001) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/57.0.2987.108 UCBrowser/12.13.1.1187 Mobile Safari/537.36",
+        ] {
+            assert!(is_incompatible_client(user_agent), "{user_agent}");
+        }
+    }
+
+    #[test]
+    fn compatible_clients() {
+        for user_agent in [
+            // iOS 13
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 13_3 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/13.0.4 Mobile/15E148 Safari/604.1",
+            // Chrome on macOS 10.14
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_14_6) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/80.0.3987.132 Safari/537.36",
+            // Chrome 50 and 67
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/50.0.2661.102 Safari/537.36",
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/67.0.3396.99 Safari/537.36",
+            // UC Browser 12.13.2
+            "Mozilla/5.0 (Linux; U; Android 9; en-US; SM-G960F Build/PPR1.180610.011) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/57.0.2987.108 UCBrowser/12.13.2.1208 Mobile Safari/537.36",
+            // Firefox
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:120.0) Gecko/20100101 Firefox/120.0",
+            "",
+        ] {
+            assert!(!is_incompatible_client(user_agent), "{user_agent}");
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! Spans around cookie operations, emitted with the `otel` feature, and around middleware requests, emitted with the
+//! `tracing` feature
+//!
+//! Without the features every method is a no-op, so call sites do not need to be feature gated.
+//! Cookie values are never recorded.
+#[cfg(feature = "otel")]
+use opentelemetry::{
+    KeyValue,
+    global::{self, BoxedSpan},
+    trace::{Span, Status, Tracer},
+};
+
+/// The outcome recorded as `cookie.result`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Ok,
+    NotFound,
+    Error,
+}
+
+impl Outcome {
+    #[cfg(feature = "otel")]
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::NotFound => "not_found",
+            Outcome::Error => "error",
+        }
+    }
+}
+
+pub(crate) struct OperationSpan {
+    #[cfg(feature = "otel")]
+    span: BoxedSpan,
+}
+
+impl OperationSpan {
+    /// Starts a `cookiebox.<operation>` span for the cookie `name`, as a child of the current context
+    pub(crate) fn cookie(operation: &'static str, name: &str) -> Self {
+        #[cfg(not(feature = "otel"))]
+        let _ = (operation, name);
+
+        OperationSpan {
+            #[cfg(feature = "otel")]
+            span: start(operation, Some(name)),
+        }
+    }
+    /// Starts a `cookiebox.<phase>` span for a middleware phase, as a child of the current context
+    pub(crate) fn middleware(phase: &'static str) -> Self {
+        #[cfg(not(feature = "otel"))]
+        let _ = phase;
+
+        OperationSpan {
+            #[cfg(feature = "otel")]
+            span: start(phase, None),
+        }
+    }
+    /// Records the outcome and ends the span
+    pub(crate) fn end(self, outcome: Outcome) {
+        #[cfg(not(feature = "otel"))]
+        let _ = outcome;
+
+        #[cfg(feature = "otel")]
+        {
+            let mut span = self.span;
+            span.set_attribute(KeyValue::new("cookie.result", outcome.as_str()));
+            if outcome == Outcome::Error {
+                span.set_status(Status::error(""));
+            }
+            span.end();
+        }
+    }
+}
+
+/// A `cookiebox.request` debug span around a request handled by the middleware
+///
+/// Records the number of request cookies parsed as `cookies.parsed`, the number of cookies that failed processing in
+/// either direction as `cookies.failed`, and the number of `Set-Cookie` headers as `cookies.emitted`. When names are
+/// included, the names are recorded as `cookies.parsed_names` and `cookies.emitted_names`.
+pub(crate) struct RequestSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    counts: Counts,
+}
+
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct Counts {
+    parsed: usize,
+    failed: usize,
+    emitted: usize,
+    // `None` unless names are included
+    parsed_names: Option<Vec<String>>,
+    emitted_names: Option<Vec<String>>,
+}
+
+impl RequestSpan {
+    /// Starts a `cookiebox.request` span, as a child of the current span
+    pub(crate) fn new(include_names: bool) -> Self {
+        #[cfg(not(feature = "tracing"))]
+        let _ = include_names;
+
+        RequestSpan {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "cookiebox.request",
+                cookies.parsed = tracing::field::Empty,
+                cookies.failed = tracing::field::Empty,
+                cookies.emitted = tracing::field::Empty,
+                cookies.parsed_names = tracing::field::Empty,
+                cookies.emitted_names = tracing::field::Empty,
+            ),
+            #[cfg(feature = "tracing")]
+            counts: Counts {
+                parsed_names: include_names.then(Vec::new),
+                emitted_names: include_names.then(Vec::new),
+                ..Counts::default()
+            },
+        }
+    }
+    /// A span that records nothing, for parsing outside of the middleware
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn none() -> Self {
+        RequestSpan {
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::none(),
+            #[cfg(feature = "tracing")]
+            counts: Counts::default(),
+        }
+    }
+    /// Runs the future returned by `f` inside the span, `f` receives the span to count cookies with
+    #[cfg(feature = "tracing")]
+    pub(crate) fn run<F, Fut>(self, f: F) -> impl Future<Output = Fut::Output>
+    where
+        F: FnOnce(Self) -> Fut,
+        Fut: Future,
+    {
+        let span = self.span.clone();
+        tracing::Instrument::instrument(f(self), span)
+    }
+    /// Runs the future returned by `f` inside the span, `f` receives the span to count cookies with
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn run<F, Fut>(self, f: F) -> impl Future<Output = Fut::Output>
+    where
+        F: FnOnce(Self) -> Fut,
+        Fut: Future,
+    {
+        f(self)
+    }
+    /// Counts a request cookie added to the storage
+    pub(crate) fn parsed(&mut self, name: &str) {
+        #[cfg(not(feature = "tracing"))]
+        let _ = name;
+
+        #[cfg(feature = "tracing")]
+        {
+            self.counts.parsed += 1;
+            if let Some(names) = &mut self.counts.parsed_names {
+                names.push(name.to_string());
+            }
+        }
+    }
+    /// Counts a cookie that failed processing
+    pub(crate) fn failed(&mut self) {
+        #[cfg(feature = "tracing")]
+        {
+            self.counts.failed += 1;
+        }
+    }
+    /// Counts a `Set-Cookie` header added to the response
+    pub(crate) fn emitted(&mut self, name: &str) {
+        #[cfg(not(feature = "tracing"))]
+        let _ = name;
+
+        #[cfg(feature = "tracing")]
+        {
+            self.counts.emitted += 1;
+            if let Some(names) = &mut self.counts.emitted_names {
+                names.push(name.to_string());
+            }
+        }
+    }
+    /// Records the counts on the span, the span itself ends once the instrumented future completes
+    pub(crate) fn record(&self) {
+        #[cfg(feature = "tracing")]
+        {
+            let counts = &self.counts;
+            self.span.record("cookies.parsed", counts.parsed);
+            self.span.record("cookies.failed", counts.failed);
+            self.span.record("cookies.emitted", counts.emitted);
+            if let Some(names) = &counts.parsed_names {
+                self.span
+                    .record("cookies.parsed_names", names.join(",").as_str());
+            }
+            if let Some(names) = &counts.emitted_names {
+                self.span
+                    .record("cookies.emitted_names", names.join(",").as_str());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+fn start(operation: &'static str, name: Option<&str>) -> BoxedSpan {
+    let tracer = global::tracer("cookiebox");
+    let mut span = tracer.start(format!("cookiebox.{operation}"));
+
+    span.set_attribute(KeyValue::new("cookie.operation", operation));
+    if let Some(name) = name {
+        span.set_attribute(KeyValue::new("cookie.name", name.to_string()));
+    }
+
+    span
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use crate::Storage;
+    use crate::cookiebox_macros::cookie;
+    use crate::cookies::{Cookie, CookieName, IncomingConfig, OutgoingConfig};
+    use opentelemetry::global;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+
+    #[cookie(name = "otel_cookie")]
+    pub struct OtelCookie;
+    impl IncomingConfig for OtelCookie {
+        type Get = String;
+    }
+    impl OutgoingConfig for OtelCookie {
+        type Insert = String;
+    }
+
+    fn attribute(span: &SpanData, key: &str) -> Option<String> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.to_string())
+    }
+
+    #[test]
+    fn cookie_operation_spans() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        global::set_tracer_provider(provider);
+
+        let storage = Storage::new();
+        let cookie = Cookie::<OtelCookie>::new(&storage);
+
+        let _ = cookie.get();
+        cookie.insert("secret value".to_string()).unwrap();
+        cookie.remove();
+
+        // Other tests may emit spans concurrently, so only the spans of this cookie are checked
+        let spans: Vec<SpanData> = exporter
+            .get_finished_spans()
+            .unwrap()
+            .into_iter()
+            .filter(|span| attribute(span, "cookie.name").as_deref() == Some("otel_cookie"))
+            .collect();
+
+        let summary: Vec<(String, Option<String>, Option<String>)> = spans
+            .iter()
+            .map(|span| {
+                (
+                    span.name.to_string(),
+                    attribute(span, "cookie.operation"),
+                    attribute(span, "cookie.result"),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "cookiebox.get".to_string(),
+                    Some("get".to_string()),
+                    Some("not_found".to_string())
+                ),
+                (
+                    "cookiebox.insert".to_string(),
+                    Some("insert".to_string()),
+                    Some("ok".to_string())
+                ),
+                (
+                    "cookiebox.remove".to_string(),
+                    Some("remove".to_string()),
+                    Some("ok".to_string())
+                ),
+            ]
+        );
+
+        // Values are never recorded
+        assert!(spans.iter().all(|span| {
+            span.attributes
+                .iter()
+                .all(|kv| !kv.value.to_string().contains("secret value"))
+        }));
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod request_span_tests {
+    use crate::cookiebox_macros::cookie;
+    use crate::cookies::{Cookie, CookieName, OutgoingConfig};
+    use crate::{CookieMiddleware, Processor, ProcessorConfig};
+    use actix_web::{App, HttpRequest, HttpResponse, http::header::COOKIE, test, web};
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Subscriber, subscriber};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use tracing_subscriber::registry::Registry;
+
+    #[cookie(name = "theme")]
+    pub struct Theme;
+    impl OutgoingConfig for Theme {
+        type Insert = String;
+    }
+
+    async fn set_theme(req: HttpRequest) -> HttpResponse {
+        let storage = crate::storage(&req).expect("Storage not found in request extension");
+        Cookie::<Theme>::new(&storage)
+            .insert("secret dark".to_string())
+            .expect("Unable to insert cookie");
+        HttpResponse::Ok().finish()
+    }
+
+    // Collects the fields recorded on `cookiebox.request` spans
+    #[derive(Clone, Default)]
+    struct FieldLayer(Arc<Mutex<HashMap<String, String>>>);
+
+    impl Visit for FieldLayer {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for FieldLayer {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            assert_eq!(attrs.metadata().name(), "cookiebox.request");
+            attrs.record(&mut self.clone());
+        }
+
+        fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+    }
+
+    async fn handle_request(trace_cookie_names: bool) -> HashMap<String, String> {
+        let layer = FieldLayer::default();
+        let _guard = subscriber::set_default(Registry::default().with(layer.clone()));
+
+        let processor: Processor = ProcessorConfig::default().into();
+        let app = test::init_service(
+            App::new()
+                .wrap(
+                    CookieMiddleware::new(processor)
+                        .skip_malformed_cookies(true)
+                        .trace_cookie_names(trace_cookie_names),
+                )
+                .route("/", web::get().to(set_theme)),
+        )
+        .await;
+
+        let req = test::TestRequest::default()
+            .insert_header((COOKIE, "session=secret value; broken; lang=en"))
+            .to_request();
+        test::call_service(&app, req).await;
+
+        layer.0.lock().unwrap().clone()
+    }
+
+    #[actix_web::test]
+    async fn request_span_counts() {
+        let fields = handle_request(false).await;
+
+        assert_eq!(fields.get("cookies.parsed").map(String::as_str), Some("2"));
+        assert_eq!(fields.get("cookies.failed").map(String::as_str), Some("1"));
+        assert_eq!(fields.get("cookies.emitted").map(String::as_str), Some("1"));
+        assert!(!fields.contains_key("cookies.parsed_names"));
+        assert!(!fields.contains_key("cookies.emitted_names"));
+    }
+
+    #[actix_web::test]
+    async fn request_span_names() {
+        let fields = handle_request(true).await;
+
+        assert_eq!(
+            fields.get("cookies.parsed_names").map(String::as_str),
+            Some("\"session,lang\"")
+        );
+        assert_eq!(
+            fields.get("cookies.emitted_names").map(String::as_str),
+            Some("\"theme\"")
+        );
+
+        // Values are never recorded
+        assert!(fields.values().all(|value| !value.contains("secret")));
+    }
+}
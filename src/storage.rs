@@ -1,18 +1,640 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
 
-use biscotti::{RequestCookies, ResponseCookies};
+use actix_web::{HttpMessage, HttpRequest};
+use biscotti::{RequestCookie, RequestCookies, ResponseCookie, ResponseCookieId, ResponseCookies};
+
+use crate::attributes::{Attributes, Priority, removal_cookie};
+use crate::codec::ValueEncoding;
+use crate::cookies::{CookieEnv, ReadPolicy};
 
 /// Holds a collection of both request and response cookies
+///
+/// [Storage] is created by the middleware for every request. It can also be created with [Storage::new] to test cookie
+/// types and extractors without the middleware.
+///
+/// ```
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig, OutgoingConfig};
+/// use cookiebox::{RequestCookie, Storage};
+///
+/// #[cookie(name = "my-cookie")]
+/// pub struct MyCookie;
+///
+/// impl IncomingConfig for MyCookie {
+///     type Get = String;
+/// }
+///
+/// impl OutgoingConfig for MyCookie {
+///     type Insert = String;
+/// }
+///
+/// let storage = Storage::new();
+/// storage.append_request_cookie(RequestCookie::new("my-cookie", r#""Stephanie""#));
+///
+/// let cookie = Cookie::<MyCookie>::new(&storage);
+/// assert_eq!(cookie.get(), Ok("Stephanie".to_string()));
+///
+/// cookie.insert("Zack".to_string()).unwrap();
+/// assert_eq!(storage.response_cookies()[0].value(), r#""Zack""#);
+/// ```
 #[derive(Clone)]
 pub struct Storage<'s> {
     pub(crate) request_storage: Rc<RefCell<RequestCookies<'s>>>,
+    // `RequestCookies` cannot be iterated, so the names are tracked in the order they were first seen
+    pub(crate) request_names: Rc<RefCell<Vec<String>>>,
+    // Counted as cookies are added, so the stats do not walk the request collection
+    request_count: Rc<Cell<usize>>,
+    request_bytes: Rc<Cell<usize>>,
     pub(crate) response_storage: Rc<RefCell<ResponseCookies<'s>>>,
+    pub(crate) refresh_storage: Rc<RefCell<ResponseCookies<'s>>>,
+    // What `ResponseCookie` cannot carry is tracked by cookie id and applied by the middleware
+    pub(crate) response_options: Rc<RefCell<HashMap<ResponseCookieId<'s>, EmitOptions>>>,
+    pub(crate) refresh_options: Rc<RefCell<HashMap<ResponseCookieId<'s>, EmitOptions>>>,
+    pub(crate) env: Rc<CookieEnv>,
+    pub(crate) read_policy: ReadPolicy,
+    pub(crate) default_attributes: Rc<Attributes<'static>>,
 }
-impl Storage<'_> {
-    pub(crate) fn new() -> Self {
+impl<'s> Storage<'s> {
+    /// Create an empty [Storage]
+    pub fn new() -> Self {
         Storage {
             request_storage: Rc::new(RefCell::new(RequestCookies::new())),
+            request_names: Rc::new(RefCell::new(Vec::new())),
+            request_count: Rc::new(Cell::new(0)),
+            request_bytes: Rc::new(Cell::new(0)),
             response_storage: Rc::new(RefCell::new(ResponseCookies::new())),
+            refresh_storage: Rc::new(RefCell::new(ResponseCookies::new())),
+            response_options: Rc::new(RefCell::new(HashMap::new())),
+            refresh_options: Rc::new(RefCell::new(HashMap::new())),
+            env: Rc::new(CookieEnv::default()),
+            read_policy: ReadPolicy::default(),
+            default_attributes: Rc::new(Attributes::default()),
+        }
+    }
+    /// Adds `cookie` to the request collection, as if the client sent it in the `Cookie` header
+    pub fn append_request_cookie(&self, cookie: RequestCookie<'s>) {
+        let mut request_names = self.request_names.borrow_mut();
+        if !request_names.iter().any(|name| name == cookie.name()) {
+            request_names.push(cookie.name().to_string());
+        }
+
+        self.request_count.set(self.request_count.get() + 1);
+        self.request_bytes
+            .set(self.request_bytes.get() + cookie.name().len() + cookie.value().len());
+
+        self.request_storage.borrow_mut().append(cookie);
+    }
+    /// Returns the number of cookies in the request collection, same-named cookies counted separately
+    ///
+    /// The count is kept up to date as the middleware fills the storage, so this is cheap enough to call on every request,
+    /// e.g. for rate-limiting or abuse detection. Cookies stripped by the middleware are not counted.
+    pub fn request_cookie_count(&self) -> usize {
+        self.request_count.get()
+    }
+    /// Returns the total size in bytes of the names and values of the cookies in the request collection
+    ///
+    /// Values are measured after being verified or decrypted by the [Processor](biscotti::Processor), so this is smaller
+    /// than the `Cookie` header, which also carries the separators and the encoding.
+    pub fn request_cookie_bytes(&self) -> usize {
+        self.request_bytes.get()
+    }
+    /// Returns the names and values of all the cookies in the request collection
+    ///
+    /// Cookies are grouped by name in the order the names first appeared, and same-named cookies keep the order they were sent in.
+    /// Values are returned after being verified or decrypted by the [Processor](biscotti::Processor), but are not deserialized.
+    /// This is a read-only view meant for debugging, auditing, or forwarding cookies without knowing their types.
+    pub fn request_cookies(&self) -> Vec<(String, String)> {
+        let request_storage = self.request_storage.borrow();
+
+        self.request_names
+            .borrow()
+            .iter()
+            .filter_map(|name| Some((name, request_storage.get_all(name)?)))
+            .flat_map(|(name, cookies)| {
+                cookies
+                    .values()
+                    .map(|value| (name.clone(), value.to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+    /// Returns a snapshot of the cookies queued in the response collection
+    ///
+    /// These are the cookies that would be emitted as `Set-Cookie` headers, before being processed by the [Processor](biscotti::Processor).
+    pub fn response_cookies(&self) -> Vec<ResponseCookie<'s>> {
+        self.response_storage.borrow().iter().cloned().collect()
+    }
+    /// Returns the names and values of all the cookies that would be emitted as `Set-Cookie` headers
+    ///
+    /// This includes the cookies queued in the response collection, followed by the sliding expiration refreshes that were not
+    /// overwritten, the way the middleware merges them. A queued removal is listed with an empty value. Values are returned
+    /// before being processed by the [Processor](biscotti::Processor), and the storage is left untouched, so this can be called
+    /// at any point of a handler, e.g. to assert on what a handler is about to set.
+    pub fn pending_response_cookies(&self) -> Vec<(String, String)> {
+        let response_storage = self.response_storage.borrow();
+        let refresh_storage = self.refresh_storage.borrow();
+
+        let refreshes = refresh_storage
+            .iter()
+            .filter(|cookie| response_storage.get(cookie.id()).is_none());
+
+        response_storage
+            .iter()
+            .chain(refreshes)
+            .map(|cookie| (cookie.name().to_string(), cookie.value().to_string()))
+            .collect()
+    }
+    /// Adds a removal cookie to the response collection for every cookie name in the request collection
+    ///
+    /// This clears the cookies the client sent without knowing their types, e.g. to reset a session on logout.
+    /// The removal cookies are scoped by the path and domain of `attributes`, or of the middleware default attributes when
+    /// `None` is given. Like [Cookie::remove](crate::cookies::Cookie::remove), the `__Secure-` and `__Host-` prefixes are honored.
+    /// Request cookies do not carry their path or domain, so a cookie set at another scope is not cleared.
+    ///
+    /// Returns the number of removal cookies added.
+    ///
+    /// ```
+    /// use cookiebox::{Attributes, RequestCookie, Storage};
+    ///
+    /// let storage = Storage::new();
+    /// storage.append_request_cookie(RequestCookie::new("session", "abc"));
+    /// storage.append_request_cookie(RequestCookie::new("theme", "dark"));
+    ///
+    /// assert_eq!(storage.clear_all(Some(Attributes::new().path("/"))), 2);
+    /// assert_eq!(storage.response_cookies().len(), 2);
+    /// ```
+    pub fn clear_all(&self, attributes: Option<Attributes<'s>>) -> usize {
+        let attributes = match attributes {
+            Some(attributes) => attributes.inherit_from(&self.default_attributes),
+            None => (*self.default_attributes).clone(),
+        };
+
+        let request_names = self.request_names.borrow();
+
+        for name in request_names.iter() {
+            let attributes = attributes.clone().enforce_name_prefix(name);
+
+            // Inserting the removal cookie will replace any cookie with the same name, path, and domain
+            self.queue_response_cookie(
+                removal_cookie(name.clone(), &attributes),
+                EmitOptions::default(),
+            );
+        }
+
+        request_names.len()
+    }
+    // Inserts `cookie` in the response collection, replacing any cookie with the same name, path, and domain along with its options
+    pub(crate) fn queue_response_cookie(&self, cookie: ResponseCookie<'s>, options: EmitOptions) {
+        set_options(&self.response_options, cookie.id(), options);
+        self.response_storage.borrow_mut().insert(cookie);
+    }
+    // Inserts `cookie` in the refresh collection, like `queue_response_cookie`
+    pub(crate) fn queue_refresh_cookie(&self, cookie: ResponseCookie<'s>, options: EmitOptions) {
+        set_options(&self.refresh_options, cookie.id(), options);
+        self.refresh_storage.borrow_mut().insert(cookie);
+    }
+    /// Computes a digest of the raw values of the named request cookies, e.g. to build an `ETag`
+    ///
+    /// The digest only depends on the set of `names` and the values of those cookies, so the order of `names` and of the
+    /// `Cookie` header does not matter for distinct names. Absent cookies are taken into account, so a missing cookie and an
+    /// empty one produce different digests. The digest is a hex encoded 64-bit FNV-1a hash, which is stable across builds
+    /// but is not suitable for security purposes.
+    pub fn cookie_digest(&self, names: &[&str]) -> String {
+        let mut names = names.to_vec();
+        names.sort_unstable();
+        names.dedup();
+
+        let request_storage = self.request_storage.borrow();
+        let mut hasher = Fnv1a::new();
+
+        for name in names {
+            hasher.write_field(name.as_bytes());
+            match request_storage.get_all(name) {
+                Some(cookies) => {
+                    hasher.write(&[1]);
+                    for value in cookies.values() {
+                        hasher.write_field(value.as_bytes());
+                    }
+                }
+                None => hasher.write(&[0]),
+            }
         }
+
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Storage<'static> {
+    /// Creates a [Storage] filled from the value of a `Cookie` header, the way [CookieMiddleware](crate::CookieMiddleware) does
+    ///
+    /// The cookies are processed by `processor`, so signed and encrypted values are verified or decrypted. This runs the same
+    /// parsing as the middleware with its default settings, and fails the same way on a malformed pair or a cookie that fails
+    /// processing. This is available with the `test-util` feature.
+    ///
+    /// ```
+    /// use cookiebox::cookiebox_macros::cookie;
+    /// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
+    /// use cookiebox::{Processor, ProcessorConfig, Storage};
+    ///
+    /// #[cookie(name = "visits")]
+    /// pub struct Visits;
+    ///
+    /// impl IncomingConfig for Visits {
+    ///     type Get = u32;
+    /// }
+    ///
+    /// let processor: Processor = ProcessorConfig::default().into();
+    /// let storage = Storage::from_cookie_header("visits=3; theme=dark", &processor).unwrap();
+    ///
+    /// assert_eq!(Cookie::<Visits>::new(&storage).get(), Ok(3));
+    /// ```
+    pub fn from_cookie_header(
+        header: &str,
+        processor: &biscotti::Processor,
+    ) -> Result<Self, actix_web::Error> {
+        let storage = Storage::new();
+
+        crate::middleware::parse_cookie_headers(
+            &[header],
+            processor,
+            &crate::middleware::MiddlewareConfig::default(),
+            &storage,
+            &mut crate::spans::RequestSpan::none(),
+        )?;
+
+        Ok(storage)
+    }
+}
+
+impl Default for Storage<'_> {
+    fn default() -> Self {
+        Storage::new()
+    }
+}
+
+impl Storage<'_> {
+    /// Returns a [Debug](std::fmt::Debug) view of `self` that shows the cookie values
+    ///
+    /// The [Debug](std::fmt::Debug) output of [Storage] redacts the values, since they often carry session tokens that
+    /// should not end up in logs. Use this during development only.
+    /// ```
+    /// use cookiebox::{RequestCookie, Storage};
+    ///
+    /// let storage = Storage::new();
+    /// storage.append_request_cookie(RequestCookie::new("session", "abc123"));
+    ///
+    /// assert!(!format!("{storage:?}").contains("abc123"));
+    /// assert!(format!("{:?}", storage.debug_values()).contains("session=abc123"));
+    /// ```
+    pub fn debug_values(&self) -> impl std::fmt::Debug + '_ {
+        DebugStorage {
+            storage: self,
+            values: true,
+        }
+    }
+}
+
+/// Values are redacted, see [Storage::debug_values] to show them
+impl std::fmt::Debug for Storage<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        DebugStorage {
+            storage: self,
+            values: false,
+        }
+        .fmt(f)
+    }
+}
+
+struct DebugStorage<'a, 's> {
+    storage: &'a Storage<'s>,
+    values: bool,
+}
+
+impl std::fmt::Debug for DebugStorage<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pairs = |cookies: Vec<(String, String)>| -> Vec<DebugPair> {
+            cookies
+                .into_iter()
+                .map(|(name, value)| DebugPair {
+                    name,
+                    value,
+                    values: self.values,
+                })
+                .collect()
+        };
+
+        f.debug_struct("Storage")
+            .field("request_cookies", &pairs(self.storage.request_cookies()))
+            .field(
+                "response_cookies",
+                &pairs(self.storage.pending_response_cookies()),
+            )
+            .field("read_policy", &self.storage.read_policy)
+            .field("env", &self.storage.env)
+            .finish_non_exhaustive()
+    }
+}
+
+// A cookie printed as `name=value`, or with only the length of its value when redacted
+struct DebugPair {
+    name: String,
+    value: String,
+    values: bool,
+}
+
+impl std::fmt::Debug for DebugPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.values {
+            write!(f, "{}={}", self.name, self.value)
+        } else {
+            write!(f, "{}=<redacted, {} bytes>", self.name, self.value.len())
+        }
+    }
+}
+
+/// Returns the [Storage] the middleware attached to the request, or `None` if [CookieMiddleware](crate::CookieMiddleware) is not registered
+///
+/// This is the lookup the `FromRequest` derive performs, for collections that implement `FromRequest` by hand.
+///
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{Cookie, CookieName, IncomingConfig};
+/// use actix_web::{FromRequest, HttpRequest, dev::Payload, error::ErrorInternalServerError};
+/// use std::future::{Ready, ready};
+///
+/// #[cookie(name = "theme")]
+/// pub struct Theme;
+///
+/// impl IncomingConfig for Theme {
+///     type Get = String;
+/// }
+///
+/// pub struct CookieCollection<'c> {
+///     theme: Cookie<'c, Theme>,
+/// }
+///
+/// impl FromRequest for CookieCollection<'static> {
+///     type Error = actix_web::Error;
+///     type Future = Ready<Result<Self, Self::Error>>;
+///
+///     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+///         ready(match cookiebox::storage(req) {
+///             Some(storage) => Ok(CookieCollection { theme: Cookie::new(&storage) }),
+///             None => Err(ErrorInternalServerError("Storage not found in request extension")),
+///         })
+///     }
+/// }
+/// ```
+pub fn storage(req: &HttpRequest) -> Option<Storage<'static>> {
+    req.extensions().get::<Storage>().cloned()
+}
+
+/// How the middleware emits a queued cookie, beyond what [ResponseCookie] carries
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct EmitOptions {
+    pub(crate) priority: Option<Priority>,
+    pub(crate) encoding: ValueEncoding,
+}
+
+// Only the options that differ from the defaults are kept
+fn set_options<'s>(
+    options: &RefCell<HashMap<ResponseCookieId<'s>, EmitOptions>>,
+    id: ResponseCookieId<'s>,
+    value: EmitOptions,
+) {
+    let mut options = options.borrow_mut();
+    if value == EmitOptions::default() {
+        options.remove(&id);
+    } else {
+        options.insert(id, value);
+    }
+}
+
+// A minimal FNV-1a implementation, `DefaultHasher` is not guaranteed to be stable across Rust releases
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    // Length prefixed, so adjacent fields cannot run into each other
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use biscotti::RequestCookie;
+
+    fn storage_with(cookies: &[(&'static str, &'static str)]) -> Storage<'static> {
+        let storage = Storage::new();
+        for (name, value) in cookies {
+            storage.append_request_cookie(RequestCookie::new(*name, *value));
+        }
+        storage
+    }
+
+    #[test]
+    fn request_and_response_cookies() {
+        let storage = storage_with(&[("theme", "dark")]);
+
+        assert_eq!(
+            storage
+                .request_storage
+                .borrow()
+                .get("theme")
+                .map(|cookie| cookie.value().to_string()),
+            Some("dark".to_string())
+        );
+        assert!(storage.response_cookies().is_empty());
+
+        storage.append_request_cookie(RequestCookie::new("lang", "en"));
+        storage.append_request_cookie(RequestCookie::new("theme", "light"));
+
+        assert_eq!(
+            storage.request_cookies(),
+            vec![
+                ("theme".to_string(), "dark".to_string()),
+                ("theme".to_string(), "light".to_string()),
+                ("lang".to_string(), "en".to_string()),
+            ]
+        );
+
+        storage
+            .response_storage
+            .borrow_mut()
+            .insert(ResponseCookie::new("lang", "en"));
+        let response_cookies = storage.response_cookies();
+
+        assert_eq!(response_cookies.len(), 1);
+        assert_eq!(response_cookies[0].name(), "lang");
+        assert_eq!(response_cookies[0].value(), "en");
+    }
+    #[test]
+    fn request_cookie_stats() {
+        let storage = Storage::new();
+
+        assert_eq!(storage.request_cookie_count(), 0);
+        assert_eq!(storage.request_cookie_bytes(), 0);
+
+        storage.append_request_cookie(RequestCookie::new("theme", "dark"));
+        storage.append_request_cookie(RequestCookie::new("theme", "light"));
+        storage.append_request_cookie(RequestCookie::new("lang", "en"));
+
+        assert_eq!(storage.request_cookie_count(), 3);
+        assert_eq!(storage.request_cookie_bytes(), 9 + 10 + 6);
+
+        // clones share the stats
+        assert_eq!(storage.clone().request_cookie_count(), 3);
+    }
+    #[test]
+    fn debug_redacts_values() {
+        let storage = Storage::new();
+        storage.append_request_cookie(RequestCookie::new("session", "abc123"));
+        storage
+            .response_storage
+            .borrow_mut()
+            .insert(ResponseCookie::new("theme", "dark"));
+
+        let debug = format!("{storage:?}");
+        assert!(debug.contains("session=<redacted, 6 bytes>"));
+        assert!(debug.contains("theme=<redacted, 4 bytes>"));
+        assert!(!debug.contains("abc123"));
+        assert!(!debug.contains("dark"));
+
+        let debug = format!("{:?}", storage.debug_values());
+        assert!(debug.contains("session=abc123"));
+        assert!(debug.contains("theme=dark"));
+    }
+    #[test]
+    fn pending_response_cookies() {
+        let storage = Storage::new();
+        assert!(storage.pending_response_cookies().is_empty());
+
+        storage
+            .response_storage
+            .borrow_mut()
+            .insert(ResponseCookie::new("lang", "en"));
+        storage
+            .refresh_storage
+            .borrow_mut()
+            .insert(ResponseCookie::new("session", "abc"));
+        // overwritten by the response collection
+        storage
+            .refresh_storage
+            .borrow_mut()
+            .insert(ResponseCookie::new("lang", "fr"));
+
+        let pending = vec![
+            ("lang".to_string(), "en".to_string()),
+            ("session".to_string(), "abc".to_string()),
+        ];
+        assert_eq!(storage.pending_response_cookies(), pending);
+
+        // the storage is not consumed
+        assert_eq!(storage.pending_response_cookies(), pending);
+        assert_eq!(storage.response_cookies().len(), 1);
+    }
+    #[test]
+    fn from_cookie_header() {
+        let processor: biscotti::Processor = biscotti::ProcessorConfig::default().into();
+        let storage =
+            Storage::from_cookie_header("theme=dark; lang=%22en%22 ; theme=light", &processor)
+                .unwrap();
+
+        assert_eq!(
+            storage.request_cookies(),
+            vec![
+                ("theme".to_string(), "dark".to_string()),
+                ("theme".to_string(), "light".to_string()),
+                ("lang".to_string(), r#""en""#.to_string()),
+            ]
+        );
+
+        assert!(Storage::from_cookie_header("theme", &processor).is_err());
+        assert!(
+            Storage::from_cookie_header("", &processor)
+                .unwrap()
+                .request_cookies()
+                .is_empty()
+        );
+    }
+    #[test]
+    fn clear_all() {
+        let storage = storage_with(&[
+            ("theme", "dark"),
+            ("__Host-session", "abc"),
+            ("theme", "light"),
+        ]);
+
+        // A pending cookie of the same scope is replaced by its removal
+        storage
+            .response_storage
+            .borrow_mut()
+            .insert(ResponseCookie::new("theme", "blue").set_path("/admin"));
+
+        assert_eq!(storage.clear_all(Some(Attributes::new().path("/admin"))), 2);
+
+        let response_cookies = storage.response_cookies();
+        assert_eq!(response_cookies.len(), 2);
+
+        let theme = response_cookies
+            .iter()
+            .find(|cookie| cookie.name() == "theme")
+            .unwrap();
+        assert_eq!(theme.value(), "");
+        assert_eq!(theme.path(), Some("/admin"));
+        assert!(theme.expires().unwrap().datetime().unwrap() < biscotti::time::Zoned::now());
+
+        let session = response_cookies
+            .iter()
+            .find(|cookie| cookie.name() == "__Host-session")
+            .unwrap();
+        assert_eq!(session.path(), Some("/"));
+        assert_eq!(session.secure(), Some(true));
+
+        // Nothing to clear
+        assert_eq!(Storage::new().clear_all(None), 0);
+    }
+    #[test]
+    fn cookie_digest() {
+        let storage_a = storage_with(&[("theme", "dark"), ("lang", "en"), ("session", "1")]);
+        let storage_b = storage_with(&[("lang", "en"), ("theme", "dark"), ("session", "2")]);
+        let storage_c = storage_with(&[("lang", "fr"), ("theme", "dark")]);
+        let storage_d = storage_with(&[("lang", ""), ("theme", "dark")]);
+        let storage_e = storage_with(&[("theme", "dark")]);
+
+        let digest = storage_a.cookie_digest(&["theme", "lang"]);
+
+        // Identical cookie sets, unrelated cookies and the order of names are ignored
+        assert_eq!(digest, storage_b.cookie_digest(&["lang", "theme"]));
+        assert_eq!(digest.len(), 16);
+
+        // Different values
+        assert_ne!(digest, storage_c.cookie_digest(&["theme", "lang"]));
+        // Empty and absent cookies differ
+        assert_ne!(
+            storage_d.cookie_digest(&["theme", "lang"]),
+            storage_e.cookie_digest(&["theme", "lang"])
+        );
+        // Different set of names
+        assert_ne!(digest, storage_a.cookie_digest(&["theme", "session"]));
     }
 }
@@ -0,0 +1,274 @@
+//! Helpers for testing cookie types, available with the `test-util` feature
+use crate::cookies::{Cookie, IncomingConfig, OutgoingConfig};
+use crate::storage::Storage;
+use actix_web::http::header::COOKIE;
+use actix_web::test::TestRequest;
+use biscotti::{Processor, ProcessorConfig, RequestCookie, ResponseCookie};
+
+/// Returns the cookie queued in the [Storage] response collection for the cookie type `T`.
+///
+/// The cookie is looked up by the name, path, and domain resolved from [OutgoingConfig::attributes_for_env],
+/// so individual attributes can be asserted on without parsing the `Set-Cookie` header.
+///
+/// # Panics
+///
+/// Panics if no cookie is queued for `T`.
+///
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{Cookie, CookieName, OutgoingConfig};
+/// use cookiebox::test_util::assert_cookie;
+/// use cookiebox::{SameSite, Storage};
+///
+/// #[cookie(name = "my-cookie")]
+/// pub struct MyCookie;
+///
+/// impl OutgoingConfig for MyCookie {
+///     type Insert = String;
+/// }
+///
+/// fn check(storage: &Storage) {
+///     let response_cookie = assert_cookie::<MyCookie>(storage);
+///     assert_eq!(response_cookie.same_site(), Some(SameSite::Lax));
+/// }
+/// ```
+#[track_caller]
+pub fn assert_cookie<'s, T: OutgoingConfig>(storage: &Storage<'s>) -> ResponseCookie<'s> {
+    match Cookie::<T>::new(storage).peek() {
+        Some(response_cookie) => response_cookie,
+        None => panic!("`{}` is not queued in the response storage", T::COOKIE_NAME),
+    }
+}
+
+/// Writes `value` the way `insert` does for the cookie type `T`, then reads it back with `get` and returns the result
+///
+/// This checks that [OutgoingConfig::serialize] and [IncomingConfig::deserialize] agree, including their codecs and
+/// [IncomingConfig::validate], so the caller only has to compare the returned value. No [Processor] is involved.
+///
+/// # Panics
+///
+/// Panics if the value cannot be serialized, or if the serialized value cannot be read back.
+///
+/// ```no_run
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{CookieName, IncomingConfig, OutgoingConfig};
+/// use cookiebox::test_util::assert_roundtrip;
+///
+/// #[cookie(name = "my-cookie")]
+/// pub struct MyCookie;
+///
+/// impl IncomingConfig for MyCookie {
+///     type Get = (String, u32);
+/// }
+///
+/// impl OutgoingConfig for MyCookie {
+///     type Insert = (String, u32);
+/// }
+///
+/// assert_eq!(assert_roundtrip::<MyCookie>(("Stephanie".to_string(), 7)), ("Stephanie".to_string(), 7));
+/// ```
+#[track_caller]
+pub fn assert_roundtrip<T>(value: T::Insert) -> T::Get
+where
+    T: IncomingConfig + OutgoingConfig,
+{
+    let data = T::serialize(value)
+        .and_then(|data| <T as OutgoingConfig>::CODEC.encode(T::COOKIE_NAME, &data))
+        .unwrap_or_else(|e| panic!("{e}"));
+
+    let storage = Storage::new();
+    storage.append_request_cookie(RequestCookie::new(T::COOKIE_NAME, data));
+
+    match Cookie::<T>::new(&storage).get() {
+        Ok(value) => value,
+        Err(e) => panic!("`{}` does not round-trip: {e}", T::COOKIE_NAME),
+    }
+}
+
+/// Extends [TestRequest] to send cookies by type rather than by header string
+///
+/// Values are serialized with [OutgoingConfig::serialize], written in [OutgoingConfig::CODEC], and encoded as a [Processor]
+/// would for the response, so the
+/// middleware reads them back as if the client sent a cookie it received earlier. Each call adds a `Cookie` header.
+///
+/// ```no_run
+/// use actix_web::test::TestRequest;
+/// use cookiebox::cookiebox_macros::cookie;
+/// use cookiebox::cookies::{CookieName, OutgoingConfig};
+/// use cookiebox::test_util::TestRequestExt;
+///
+/// #[cookie(name = "my-cookie")]
+/// pub struct MyCookie;
+///
+/// impl OutgoingConfig for MyCookie {
+///     type Insert = String;
+/// }
+///
+/// let request = TestRequest::get()
+///     .typed_cookie::<MyCookie>("Stephanie".to_string())
+///     .to_request();
+/// ```
+pub trait TestRequestExt {
+    /// Adds the cookie `T` with `value`, encoded by the default [Processor]
+    ///
+    /// Panics if [OutgoingConfig::serialize] or [OutgoingConfig::CODEC] fails.
+    fn typed_cookie<T: OutgoingConfig>(self, value: T::Insert) -> Self;
+    /// Adds the cookie `T` with `value`, encoded by `processor` so signed and encrypted cookies can be sent
+    fn typed_cookie_with<T: OutgoingConfig>(self, value: T::Insert, processor: &Processor) -> Self;
+}
+
+impl TestRequestExt for TestRequest {
+    fn typed_cookie<T: OutgoingConfig>(self, value: T::Insert) -> Self {
+        self.typed_cookie_with::<T>(value, &ProcessorConfig::default().into())
+    }
+    fn typed_cookie_with<T: OutgoingConfig>(self, value: T::Insert, processor: &Processor) -> Self {
+        let data = T::serialize(value)
+            .and_then(|data| <T as OutgoingConfig>::CODEC.encode(T::COOKIE_NAME, &data))
+            .unwrap_or_else(|e| panic!("{e}"));
+        let cookie = processor.process_outgoing(ResponseCookie::new(T::COOKIE_NAME, data));
+
+        self.append_header((COOKIE, format!("{}={}", cookie.name(), cookie.value())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TestRequestExt, assert_roundtrip};
+    use crate::cookiebox_macros::cookie;
+    #[cfg(feature = "messagepack")]
+    use crate::cookies::Codec;
+    use crate::cookies::{Cookie, CookieName, IncomingConfig, OutgoingConfig};
+    use crate::{CookieMiddleware, Key, Processor, ProcessorConfig};
+    use actix_web::test::{TestRequest, call_and_read_body_json, init_service};
+    use actix_web::{App, HttpRequest, HttpResponse, web};
+    use biscotti::config::{CryptoAlgorithm, CryptoRule};
+
+    #[cookie(name = "typed-cookie")]
+    pub struct TypedCookie;
+    impl IncomingConfig for TypedCookie {
+        type Get = (String, u32);
+    }
+    impl OutgoingConfig for TypedCookie {
+        type Insert = (String, u32);
+    }
+
+    #[cookie(name = "mismatched-cookie")]
+    pub struct MismatchedCookie;
+    impl IncomingConfig for MismatchedCookie {
+        type Get = String;
+    }
+    impl OutgoingConfig for MismatchedCookie {
+        type Insert = u32;
+    }
+
+    // The `FromRequest` derive refers to the crate by name, so the storage is looked up by hand
+    async fn get_cookie(req: HttpRequest) -> HttpResponse {
+        let storage = crate::storage(&req).expect("Storage not found in request extension");
+        let cookie = Cookie::<TypedCookie>::new(&storage)
+            .get()
+            .expect("Unable to get cookie");
+        HttpResponse::Ok().json(cookie)
+    }
+
+    #[actix_web::test]
+    async fn typed_cookie() {
+        let processor: Processor = ProcessorConfig::default().into();
+        let app = init_service(
+            App::new()
+                .wrap(CookieMiddleware::new(processor))
+                .route("/get", web::get().to(get_cookie)),
+        )
+        .await;
+
+        let request = TestRequest::get()
+            .uri("/get")
+            .typed_cookie::<TypedCookie>(("Stephanie".to_string(), 7))
+            .to_request();
+        let body: (String, u32) = call_and_read_body_json(&app, request).await;
+
+        assert_eq!(body, ("Stephanie".to_string(), 7));
+    }
+
+    #[cfg(feature = "messagepack")]
+    #[cookie(name = "packed-cookie")]
+    pub struct PackedCookie;
+    #[cfg(feature = "messagepack")]
+    impl IncomingConfig for PackedCookie {
+        type Get = (String, u32);
+        const CODEC: Codec = Codec::MessagePack;
+    }
+    #[cfg(feature = "messagepack")]
+    impl OutgoingConfig for PackedCookie {
+        type Insert = (String, u32);
+        const CODEC: Codec = Codec::MessagePack;
+    }
+
+    #[cfg(feature = "messagepack")]
+    async fn get_packed_cookie(req: HttpRequest) -> HttpResponse {
+        let storage = crate::storage(&req).expect("Storage not found in request extension");
+        let cookie = Cookie::<PackedCookie>::new(&storage)
+            .get()
+            .expect("Unable to get cookie");
+        HttpResponse::Ok().json(cookie)
+    }
+
+    #[cfg(feature = "messagepack")]
+    #[actix_web::test]
+    async fn typed_cookie_with_messagepack() {
+        let processor: Processor = ProcessorConfig::default().into();
+        let app = init_service(
+            App::new()
+                .wrap(CookieMiddleware::new(processor))
+                .route("/get", web::get().to(get_packed_cookie)),
+        )
+        .await;
+
+        let request = TestRequest::get()
+            .uri("/get")
+            .typed_cookie::<PackedCookie>(("Stephanie".to_string(), 7))
+            .to_request();
+        let body: (String, u32) = call_and_read_body_json(&app, request).await;
+
+        assert_eq!(body, ("Stephanie".to_string(), 7));
+    }
+
+    #[actix_web::test]
+    async fn typed_cookie_with_processor() {
+        let mut config = ProcessorConfig::default();
+        config.crypto_rules.push(CryptoRule {
+            cookie_names: vec![TypedCookie::COOKIE_NAME.to_string()],
+            algorithm: CryptoAlgorithm::Encryption,
+            key: Key::generate(),
+            fallbacks: vec![],
+        });
+        let processor: Processor = config.into();
+        let app = init_service(
+            App::new()
+                .wrap(CookieMiddleware::new(processor.clone()))
+                .route("/get", web::get().to(get_cookie)),
+        )
+        .await;
+
+        let request = TestRequest::get()
+            .uri("/get")
+            .typed_cookie_with::<TypedCookie>(("Stephanie".to_string(), 7), &processor)
+            .to_request();
+        let body: (String, u32) = call_and_read_body_json(&app, request).await;
+
+        assert_eq!(body, ("Stephanie".to_string(), 7));
+    }
+
+    #[test]
+    fn roundtrip() {
+        assert_eq!(
+            assert_roundtrip::<TypedCookie>(("Stephanie".to_string(), 7)),
+            ("Stephanie".to_string(), 7)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`mismatched-cookie` does not round-trip")]
+    fn roundtrip_mismatch() {
+        assert_roundtrip::<MismatchedCookie>(7);
+    }
+}
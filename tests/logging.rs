@@ -0,0 +1,90 @@
+use actix_web::http::header::{COOKIE, HeaderValue};
+use actix_web::{App, HttpResponse, test, web};
+use cookiebox::{CookieMiddleware, InvalidEncodingAction, Processor, ProcessorConfig};
+use std::sync::Mutex;
+
+// Collects the formatted log records, the logger is global so every test in this file shares it
+struct CaptureLogger(Mutex<Vec<String>>);
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+    fn log(&self, record: &log::Record) {
+        self.0
+            .lock()
+            .expect("Log lock poisoned")
+            .push(record.args().to_string());
+    }
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger(Mutex::new(Vec::new()));
+
+// Installs the capturing logger, once per test binary
+fn capture_logs() {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(log::LevelFilter::Trace);
+}
+
+// Returns the records logged so far that mention `needle`
+fn logs_containing(needle: &str) -> Vec<String> {
+    LOGGER
+        .0
+        .lock()
+        .expect("Log lock poisoned")
+        .iter()
+        .filter(|record| record.contains(needle))
+        .cloned()
+        .collect()
+}
+
+#[actix_web::test]
+async fn skipped_invalid_encoding_logs_no_value() {
+    capture_logs();
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor).on_invalid_encoding(InvalidEncodingAction::Skip))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    // `\xe9` is `é` in latin-1, and is not valid UTF-8 on its own
+    let request = test::TestRequest::get()
+        .insert_header((
+            COOKIE,
+            HeaderValue::from_bytes(b"latin-session=s3cr\xe9t-token").unwrap(),
+        ))
+        .to_request();
+    test::call_service(&app, request).await;
+
+    let records = logs_containing("latin-session");
+    assert_eq!(records.len(), 1);
+    assert!(!records[0].contains("s3cr"));
+}
+
+#[actix_web::test]
+async fn skipped_malformed_cookies_log_no_value() {
+    capture_logs();
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor).skip_malformed_cookies(true))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let request = test::TestRequest::get()
+        .insert_header((COOKIE, "=empty-name-token; bare-token"))
+        .to_request();
+    test::call_service(&app, request).await;
+
+    let records = logs_containing("Skipping malformed request cookie");
+    assert_eq!(records.len(), 2);
+    assert!(
+        records
+            .iter()
+            .all(|record| !record.contains("empty-name-token") && !record.contains("bare-token"))
+    );
+}
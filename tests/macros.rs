@@ -0,0 +1,18 @@
+use cookiebox::cookiebox_macros::cookie;
+use cookiebox::cookies::CookieName;
+
+#[cookie(name = "acme_cart", require_prefix = "acme_")]
+pub struct PrefixedCookie;
+
+#[cookie]
+pub struct HTTPSessionId;
+
+#[test]
+fn cookie_macro_require_prefix_tests() {
+    assert_eq!(PrefixedCookie::COOKIE_NAME, "acme_cart");
+}
+
+#[test]
+fn cookie_macro_derived_name_tests() {
+    assert_eq!(HTTPSessionId::COOKIE_NAME, "http-session-id");
+}
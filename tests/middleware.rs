@@ -1,9 +1,17 @@
-use actix_web::{App, HttpMessage, HttpResponse, test, web};
+use actix_web::http::header::HeaderValue;
+use actix_web::{App, HttpMessage, HttpRequest, HttpResponse, test, web};
+use cookiebox::config::{CryptoAlgorithm, CryptoRule};
 use cookiebox::cookiebox_macros::{FromRequest, cookie};
-use cookiebox::cookies::{Cookie, CookieName, IncomingConfig, OutgoingConfig};
-use cookiebox::{Attributes, CookieMiddleware, Processor, ProcessorConfig, SameSite};
+use cookiebox::cookies::{
+    Cookie, CookieEnv, CookieName, IncomingConfig, OutgoingConfig, ValueEncoding,
+};
+use cookiebox::time::SignedDuration;
+use cookiebox::{
+    Attributes, CookieMiddleware, InvalidEncodingAction, Key, OptionalCookie, Priority, Processor,
+    ProcessorConfig, SameSite, UnknownCookieAction,
+};
 
-#[cookie(name = "Type A")]
+#[cookie(name = "Type A", allow_invalid)]
 pub struct TypeA;
 impl IncomingConfig for TypeA {
     type Get = String;
@@ -16,17 +24,279 @@ impl OutgoingConfig for TypeA {
     }
 }
 
+#[cookie(name = "Type B", allow_invalid)]
+pub struct TypeB;
+impl IncomingConfig for TypeB {
+    type Get = String;
+
+    fn sliding_expiration<'c>() -> Option<Attributes<'c>> {
+        Some(Self::attributes().max_age(SignedDuration::from_mins(30)))
+    }
+}
+impl OutgoingConfig for TypeB {
+    type Insert = String;
+
+    fn attributes<'c>() -> Attributes<'c> {
+        Attributes::new().http_only(true)
+    }
+}
+
+#[cookie(name = "__signed", signed)]
+pub struct SignedCookie;
+
+#[cookie(name = "__encrypted", encrypted)]
+pub struct EncryptedCookie;
+
+#[cookie(name = "shorthand", same_site = "strict", max_age = 3600)]
+pub struct ShorthandCookie;
+impl OutgoingConfig for ShorthandCookie {
+    type Insert = String;
+}
+
+#[cookie(name = "overridden", same_site = "none", max_age = "60")]
+pub struct OverriddenShorthandCookie;
+impl OutgoingConfig for OverriddenShorthandCookie {
+    type Insert = String;
+
+    fn attributes<'c>() -> Attributes<'c> {
+        Attributes::new().path("/account")
+    }
+}
+
+#[cookie(name = "inheriting")]
+pub struct InheritingCookie;
+impl OutgoingConfig for InheritingCookie {
+    type Insert = String;
+
+    fn attributes<'c>() -> Attributes<'c> {
+        Attributes::inherit().path("/account")
+    }
+}
+
+#[cookie(name = "priority")]
+pub struct PriorityCookie;
+impl OutgoingConfig for PriorityCookie {
+    type Insert = String;
+
+    fn attributes<'c>() -> Attributes<'c> {
+        Attributes::new().path("/").priority(Priority::High)
+    }
+}
+
+#[cookie(name = "raw")]
+pub struct RawCookie;
+impl OutgoingConfig for RawCookie {
+    type Insert = String;
+
+    fn encode_value() -> ValueEncoding {
+        ValueEncoding::Raw
+    }
+}
+
+#[cookie(name = "scoped")]
+pub struct ScopedCookie;
+impl OutgoingConfig for ScopedCookie {
+    type Insert = String;
+
+    fn attributes_for_env<'c>(env: &CookieEnv) -> Attributes<'c> {
+        Attributes::new()
+            .path(env.base_path().to_string())
+            .domain(env.host().to_string())
+    }
+}
+
+#[cookie(name = "_ga")]
+pub struct AnalyticsCookie;
+impl IncomingConfig for AnalyticsCookie {
+    type Get = String;
+}
+impl OutgoingConfig for AnalyticsCookie {
+    type Insert = String;
+}
+
+#[cookie(name = "broken")]
+pub struct BrokenCookie;
+impl OutgoingConfig for BrokenCookie {
+    type Insert = String;
+
+    // JSON does not escape `DEL`, which is not valid in a header value and is left as is without percent-encoding
+    fn serialize(values: String) -> Result<serde_json::Value, cookiebox::cookies::CookieBoxError> {
+        Ok(serde_json::Value::String(format!("\u{7f} {values}")))
+    }
+}
+
 #[derive(FromRequest)]
 pub struct CookieCollection<'c>(Cookie<'c, TypeA>);
 
+#[derive(FromRequest)]
+pub struct BrokenCookieCollection<'c>(Cookie<'c, BrokenCookie>);
+
+#[derive(FromRequest)]
+pub struct ScopedCookieCollection<'c>(Cookie<'c, ScopedCookie>);
+
+#[derive(FromRequest)]
+pub struct AnalyticsCookieCollection<'c>(Cookie<'c, AnalyticsCookie>);
+
+#[derive(FromRequest)]
+pub struct PriorityCookieCollection<'c>(Cookie<'c, PriorityCookie>);
+
+#[derive(FromRequest)]
+pub struct RawCookieCollection<'c>(Cookie<'c, RawCookie>, Cookie<'c, TypeA>);
+
+#[derive(FromRequest)]
+pub struct SlidingCookieCollection<'c>(Cookie<'c, TypeB>);
+
+#[derive(FromRequest)]
+pub struct InheritingCookieCollection<'c>(Cookie<'c, InheritingCookie>, Cookie<'c, TypeA>);
+
+#[derive(Debug)]
+pub struct AppError(String);
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl actix_web::ResponseError for AppError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        actix_web::http::StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+impl From<&'static str> for AppError {
+    fn from(message: &'static str) -> Self {
+        AppError(message.to_string())
+    }
+}
+
+#[derive(FromRequest)]
+#[from_request(error = "AppError")]
+pub struct AppErrorCookieCollection<'c>(Cookie<'c, TypeA>);
+
+#[derive(FromRequest)]
+pub struct TupleCookieCollection<'c>(Cookie<'c, TypeA>, Cookie<'c, TypeB>);
+
 async fn register_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
-    cookie.0.insert("id".to_string());
+    cookie
+        .0
+        .insert("id".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::Ok().finish()
+}
+async fn register_priority_cookie(cookie: PriorityCookieCollection<'_>) -> HttpResponse {
+    cookie
+        .0
+        .insert("id".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::Ok().finish()
+}
+async fn remove_priority_cookie(cookie: PriorityCookieCollection<'_>) -> HttpResponse {
+    cookie.0.remove();
+    HttpResponse::Ok().finish()
+}
+async fn register_raw_encoded_cookie(cookie: RawCookieCollection<'_>) -> HttpResponse {
+    cookie
+        .0
+        .insert("id".to_string())
+        .expect("Unable to insert cookie");
+    cookie
+        .1
+        .insert("id".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::Ok().finish()
+}
+async fn register_scoped_cookie(cookie: ScopedCookieCollection<'_>) -> HttpResponse {
+    cookie
+        .0
+        .insert("id".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::Ok().finish()
+}
+async fn get_and_rewrite_analytics_cookie(cookie: AnalyticsCookieCollection<'_>) -> HttpResponse {
+    let raw = cookie.0.get_raw().expect("Unable to get cookie");
+    cookie
+        .0
+        .insert("rewritten".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::Ok().body(raw)
+}
+async fn register_broken_cookie(cookie: BrokenCookieCollection<'_>) -> HttpResponse {
+    cookie
+        .0
+        .insert("a value long enough to be truncated".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::Ok().finish()
+}
+async fn has_storage(req: HttpRequest) -> HttpResponse {
+    let has_storage = req
+        .extensions()
+        .get::<cookiebox::Storage<'static>>()
+        .is_some();
+    HttpResponse::Ok().json(has_storage)
+}
+async fn register_cookie_and_header(cookie: CookieCollection<'_>) -> HttpResponse {
+    cookie
+        .0
+        .insert("id".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::Ok()
+        .append_header((actix_web::http::header::SET_COOKIE, "flash=saved; Path=/"))
+        .finish()
+}
+async fn insert_cookie_paths(storage: web::ReqData<cookiebox::Storage<'static>>) -> HttpResponse {
+    let storage = storage.into_inner();
+    Cookie::<TypeA>::new(&storage)
+        .insert("root".to_string())
+        .expect("Unable to insert cookie");
+    Cookie::<TypeA>::new(&storage)
+        .with_attributes(TypeA::attributes().path("/admin"))
+        .insert("admin".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::Ok().finish()
+}
+async fn register_raw_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
+    cookie
+        .0
+        .insert_raw("bad\nvalue")
+        .expect("Unable to insert cookie");
     HttpResponse::Ok().finish()
 }
+async fn register_cookie_and_fail(cookie: CookieCollection<'_>) -> HttpResponse {
+    cookie
+        .0
+        .insert("id".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::InternalServerError().finish()
+}
+async fn register_inheriting_cookies(cookie: InheritingCookieCollection<'_>) -> HttpResponse {
+    cookie
+        .0
+        .insert("id".to_string())
+        .expect("Unable to insert cookie");
+    cookie
+        .1
+        .insert("id".to_string())
+        .expect("Unable to insert cookie");
+    HttpResponse::Ok().finish()
+}
+async fn get_app_error_cookie(cookie: AppErrorCookieCollection<'_>) -> HttpResponse {
+    let cookie = cookie.0.get().expect("Unable to get cookie");
+    HttpResponse::Ok().json(cookie)
+}
+async fn get_tuple_cookies(cookie: TupleCookieCollection<'_>) -> HttpResponse {
+    let cookie_a = cookie.0.get().expect("Unable to get cookie");
+    let cookie_b = cookie.1.get().expect("Unable to get cookie");
+    HttpResponse::Ok().json((cookie_a, cookie_b))
+}
+async fn get_optional_cookie(cookie: OptionalCookie<TypeA>) -> HttpResponse {
+    HttpResponse::Ok().json(cookie.into_inner())
+}
 async fn get_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
     let cookie = cookie.0.get().expect("Unable to get cookie");
     HttpResponse::Ok().json(cookie)
 }
+async fn try_get_cookie(cookie: CookieCollection<'_>) -> Result<HttpResponse, actix_web::Error> {
+    let cookie = cookie.0.get()?;
+    Ok(HttpResponse::Ok().json(cookie))
+}
 async fn get_all_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
     let cookie = cookie.0.get_all().expect("Unable to get cookies");
     HttpResponse::Ok().json(cookie)
@@ -35,6 +305,15 @@ async fn remove_cookie(cookie: CookieCollection<'_>) -> HttpResponse {
     cookie.0.remove();
     HttpResponse::Ok().finish()
 }
+async fn get_sliding_cookie(cookie: SlidingCookieCollection<'_>) -> HttpResponse {
+    let cookie = cookie.0.get().expect("Unable to get cookie");
+    HttpResponse::Ok().json(cookie)
+}
+async fn get_and_remove_sliding_cookie(cookie: SlidingCookieCollection<'_>) -> HttpResponse {
+    cookie.0.get().expect("Unable to get cookie");
+    cookie.0.remove();
+    HttpResponse::Ok().finish()
+}
 
 #[actix_web::test]
 async fn cookie_middleware_tests() -> std::io::Result<()> {
@@ -100,3 +379,1016 @@ async fn cookie_middleware_tests() -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[actix_web::test]
+async fn cookie_middleware_allowlist_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()).allow_cookie::<TypeA>())
+            .route("/get-all", web::post().to(get_all_cookie)),
+    )
+    .await;
+
+    // unknown cookies are stripped by default
+    let cookie_header = "Type%20A=%22id%22; Type%20B=%22id2%22;";
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get-all")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let body_vec: Vec<String> = test::read_body_json(response).await;
+
+    assert_eq!(body_vec, vec!["id"]);
+
+    let app = test::init_service(
+        App::new()
+            .wrap(
+                CookieMiddleware::new(processor.clone())
+                    .allow_cookie::<TypeA>()
+                    .on_unknown_cookie(UnknownCookieAction::Reject),
+            )
+            .route("/get-all", web::post().to(get_all_cookie)),
+    )
+    .await;
+
+    // allowed cookies pass through
+    let cookie_header = "Type%20A=%22id%22";
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get-all")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(response.status(), actix_web::http::StatusCode::OK);
+
+    // unknown cookies reject the request
+    let cookie_header = "Type%20A=%22id%22; Type%20B=%22id2%22;";
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get-all")
+        .to_request();
+    let response = test::try_call_service(&app, request).await;
+
+    assert_eq!(
+        response
+            .map(|r| r.status())
+            .unwrap_or_else(|e| e.as_response_error().status_code()),
+        actix_web::http::StatusCode::BAD_REQUEST
+    );
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_sliding_expiration_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()))
+            .route("/get", web::post().to(get_sliding_cookie))
+            .route("/get-remove", web::post().to(get_and_remove_sliding_cookie)),
+    )
+    .await;
+
+    // reading the cookie refreshes its max age
+    let cookie_header = "Type%20B=%22id%22";
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_header = response
+        .headers()
+        .get(actix_web::http::header::SET_COOKIE)
+        .expect("Cookie header not found")
+        .to_str()
+        .expect("Unable to stringify cookie header");
+
+    assert_eq!(cookie_header, "Type%20B=%22id%22; HttpOnly; Max-Age=1800");
+
+    // writing to the cookie takes precedence over the refresh
+    let cookie_header = "Type%20B=%22id%22";
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get-remove")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_headers = response
+        .headers()
+        .get_all(actix_web::http::header::SET_COOKIE)
+        .collect::<Vec<_>>();
+
+    assert_eq!(cookie_headers.len(), 1);
+    assert_eq!(
+        cookie_headers[0],
+        "Type%20B=; Expires=Thu, 01 Jan 1970 00:00:00 GMT"
+    );
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_path_filter_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(
+                CookieMiddleware::new(processor.clone())
+                    .only_paths(["/admin"])
+                    .skip_paths(["/admin/public"]),
+            )
+            .route("/admin/register", web::post().to(register_cookie))
+            .route("/admin", web::post().to(has_storage))
+            .route("/admin/public", web::post().to(has_storage))
+            .route("/administrator", web::post().to(has_storage))
+            .route("/api", web::post().to(has_storage)),
+    )
+    .await;
+
+    // processed paths
+    let request = test::TestRequest::post()
+        .uri("/admin/register")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert!(
+        response
+            .headers()
+            .get(actix_web::http::header::SET_COOKIE)
+            .is_some()
+    );
+
+    let request = test::TestRequest::post().uri("/admin").to_request();
+    let response = test::call_service(&app, request).await;
+    let has_storage: bool = test::read_body_json(response).await;
+
+    assert!(has_storage);
+
+    // bypassed paths, a malformed cookie header is not even parsed
+    for path in ["/admin/public", "/administrator", "/api"] {
+        let request = test::TestRequest::post()
+            .insert_header((actix_web::http::header::COOKIE, "malformed"))
+            .uri(path)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), actix_web::http::StatusCode::OK, "{path}");
+        let has_storage: bool = test::read_body_json(response).await;
+        assert!(!has_storage, "{path}");
+    }
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_read_only_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()).read_only(true))
+            .route("/register", web::post().to(register_cookie))
+            .route("/get", web::post().to(get_cookie)),
+    )
+    .await;
+
+    // inserts do not reach the browser
+    let request = test::TestRequest::post().uri("/register").to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert!(
+        response
+            .headers()
+            .get(actix_web::http::header::SET_COOKIE)
+            .is_none()
+    );
+
+    // incoming cookies are still readable
+    let cookie_header = "Type%20A=%22id%22";
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let body_str: String = test::read_body_json(response).await;
+
+    assert_eq!(body_str, "id");
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_error_response_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()))
+            .route("/get", web::post().to(try_get_cookie)),
+    )
+    .await;
+
+    // missing cookie
+    let request = test::TestRequest::post().uri("/get").to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(response.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+    // malformed cookie
+    let cookie_header = "Type%20A=id";
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(response.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+    // present cookie
+    let cookie_header = "Type%20A=%22id%22";
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(response.status(), actix_web::http::StatusCode::OK);
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_emit_when_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()).emit_when(|status| status.is_success()))
+            .route("/register", web::post().to(register_cookie))
+            .route("/register-fail", web::post().to(register_cookie_and_fail)),
+    )
+    .await;
+
+    // successful responses carry cookies
+    let request = test::TestRequest::post().uri("/register").to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert!(
+        response
+            .headers()
+            .get(actix_web::http::header::SET_COOKIE)
+            .is_some()
+    );
+
+    // failed responses drop the queued cookies
+    let request = test::TestRequest::post().uri("/register-fail").to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(
+        response.status(),
+        actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+    );
+    assert!(
+        response
+            .headers()
+            .get(actix_web::http::header::SET_COOKIE)
+            .is_none()
+    );
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_skip_on_error_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+
+    for (skip_on_error, emitted) in [(true, false), (false, true)] {
+        let app = test::init_service(
+            App::new()
+                .wrap(CookieMiddleware::new(processor.clone()).skip_on_error(skip_on_error))
+                .route("/register", web::post().to(register_cookie))
+                .route("/register-fail", web::post().to(register_cookie_and_fail)),
+        )
+        .await;
+
+        // successful responses carry cookies either way
+        let request = test::TestRequest::post().uri("/register").to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert!(
+            response
+                .headers()
+                .get(actix_web::http::header::SET_COOKIE)
+                .is_some()
+        );
+
+        let request = test::TestRequest::post().uri("/register-fail").to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(
+            response.status(),
+            actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            response
+                .headers()
+                .get(actix_web::http::header::SET_COOKIE)
+                .is_some(),
+            emitted
+        );
+    }
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_require_crypto_rule_tests() {
+    let mut config = ProcessorConfig::default();
+    config.crypto_rules.push(CryptoRule {
+        cookie_names: vec![SignedCookie::COOKIE_NAME.to_string()],
+        algorithm: CryptoAlgorithm::Signing,
+        key: Key::generate(),
+        fallbacks: vec![],
+    });
+    config.crypto_rules.push(CryptoRule {
+        cookie_names: vec![EncryptedCookie::COOKIE_NAME.to_string()],
+        algorithm: CryptoAlgorithm::Encryption,
+        key: Key::generate(),
+        fallbacks: vec![],
+    });
+    let processor: Processor = config.into();
+
+    CookieMiddleware::new(processor)
+        .require_signed::<SignedCookie>()
+        .require_encrypted::<EncryptedCookie>();
+}
+
+#[actix_web::test]
+#[should_panic(expected = "`__signed` requires signing")]
+async fn cookie_middleware_require_signed_without_rule_panics() {
+    let processor: Processor = ProcessorConfig::default().into();
+
+    CookieMiddleware::new(processor).require_signed::<SignedCookie>();
+}
+
+#[actix_web::test]
+async fn cookie_middleware_tuple_collection_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()))
+            .route("/get-tuple", web::post().to(get_tuple_cookies)),
+    )
+    .await;
+
+    let cookie_header = "Type%20A=%22a%22; Type%20B=%22b%22";
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get-tuple")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let body: (String, String) = test::read_body_json(response).await;
+
+    assert_eq!(body, ("a".to_string(), "b".to_string()));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_macro_attributes_shorthand_tests() {
+    let attributes = ShorthandCookie::attributes();
+    assert_eq!(attributes.get_same_site(), Some(SameSite::Strict));
+    assert_eq!(
+        attributes.get_max_age(),
+        Some(SignedDuration::from_secs(3600))
+    );
+    // the rest of the defaults are kept
+    assert_eq!(attributes.get_path(), Some("/"));
+    assert_eq!(attributes.get_http_only(), Some(true));
+
+    // a hand-written `attributes` takes precedence over the shorthand
+    let attributes = OverriddenShorthandCookie::attributes();
+    assert_eq!(attributes.get_same_site(), None);
+    assert_eq!(attributes.get_max_age(), None);
+    assert_eq!(attributes.get_path(), Some("/account"));
+}
+
+#[actix_web::test]
+async fn optional_cookie_extractor_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()))
+            .route("/get-optional", web::post().to(get_optional_cookie)),
+    )
+    .await;
+
+    let cases = [
+        (Some("Type%20A=%22id%22"), Some("id".to_string())),
+        (None, None),
+        (Some("Type%20A=not-json"), None),
+    ];
+
+    for (cookie_header, expected) in cases {
+        let mut request = test::TestRequest::post().uri("/get-optional");
+        if let Some(cookie_header) = cookie_header {
+            request = request.insert_header((actix_web::http::header::COOKIE, cookie_header));
+        }
+        let response = test::call_service(&app, request.to_request()).await;
+
+        assert!(response.status().is_success());
+
+        let body: Option<String> = test::read_body_json(response).await;
+
+        assert_eq!(body, expected);
+    }
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_skip_malformed_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let strict_app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()))
+            .route("/get", web::post().to(get_cookie)),
+    )
+    .await;
+    let lenient_app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()).skip_malformed_cookies(true))
+            .route("/get", web::post().to(get_cookie)),
+    )
+    .await;
+
+    let cookie_header = "junk; =no-name; Type%20A=%22id%22";
+
+    // strict by default
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get")
+        .to_request();
+    let response = test::try_call_service(&strict_app, request).await;
+
+    assert_eq!(
+        response
+            .map(|r| r.status())
+            .unwrap_or_else(|e| e.as_response_error().status_code()),
+        actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+    );
+
+    // malformed pairs are skipped
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, cookie_header))
+        .uri("/get")
+        .to_request();
+    let response = test::call_service(&lenient_app, request).await;
+    let body_str: String = test::read_body_json(response).await;
+
+    assert_eq!(body_str, "id");
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_invalid_encoding_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = |action| {
+        test::init_service(
+            App::new()
+                .wrap(CookieMiddleware::new(processor.clone()).on_invalid_encoding(action))
+                .route("/get", web::post().to(get_optional_cookie)),
+        )
+    };
+    let strict_app = app(InvalidEncodingAction::Reject).await;
+    let replace_app = app(InvalidEncodingAction::Replace).await;
+    let skip_app = app(InvalidEncodingAction::Skip).await;
+
+    // `\xe9` is `é` in latin-1, and is not valid UTF-8 on its own
+    let request = || {
+        test::TestRequest::post()
+            .insert_header((
+                actix_web::http::header::COOKIE,
+                HeaderValue::from_bytes(b"Type%20A=%22caf\xe9%22; Type%20A=%22id%22").unwrap(),
+            ))
+            .uri("/get")
+            .to_request()
+    };
+
+    // strict by default
+    let response = test::try_call_service(&strict_app, request()).await;
+
+    assert_eq!(
+        response
+            .map(|r| r.status())
+            .unwrap_or_else(|e| e.as_response_error().status_code()),
+        actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+    );
+
+    // the invalid byte is replaced
+    let response = test::call_service(&replace_app, request()).await;
+    let body: Option<String> = test::read_body_json(response).await;
+
+    assert_eq!(body, Some("caf\u{FFFD}".to_string()));
+
+    // the invalid pair is skipped
+    let response = test::call_service(&skip_app, request()).await;
+    let body: Option<String> = test::read_body_json(response).await;
+
+    assert_eq!(body, Some("id".to_string()));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_multiple_cookie_headers_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()))
+            .route("/get-tuple", web::post().to(get_tuple_cookies)),
+    )
+    .await;
+
+    // each cookie is sent in its own `Cookie` header
+    let request = test::TestRequest::post()
+        .append_header((actix_web::http::header::COOKIE, "Type%20A=%22a%22"))
+        .append_header((actix_web::http::header::COOKIE, "Type%20B=%22b%22"))
+        .uri("/get-tuple")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let body: (String, String) = test::read_body_json(response).await;
+
+    assert_eq!(body, ("a".to_string(), "b".to_string()));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_max_header_size_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor.clone()).max_header_size(32))
+            .route("/get", web::post().to(get_cookie)),
+    )
+    .await;
+
+    // within the limit
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, "Type%20A=%22id%22"))
+        .uri("/get")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert!(response.status().is_success());
+
+    // over the limit, counted across all cookie headers
+    let request = test::TestRequest::post()
+        .append_header((actix_web::http::header::COOKIE, "Type%20A=%22id%22"))
+        .append_header((
+            actix_web::http::header::COOKIE,
+            "Type%20B=%22some-long-value%22",
+        ))
+        .uri("/get")
+        .to_request();
+    let response = test::try_call_service(&app, request).await;
+
+    assert_eq!(
+        response
+            .map(|r| r.status())
+            .unwrap_or_else(|e| e.as_response_error().status_code()),
+        actix_web::http::StatusCode::BAD_REQUEST
+    );
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_default_attributes_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(
+                CookieMiddleware::new(processor.clone()).default_attributes(
+                    Attributes::default()
+                        .secure(true)
+                        .same_site(SameSite::Strict),
+                ),
+            )
+            .route("/register", web::post().to(register_inheriting_cookies)),
+    )
+    .await;
+
+    let request = test::TestRequest::post().uri("/register").to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_headers: Vec<&str> = response
+        .headers()
+        .get_all(actix_web::http::header::SET_COOKIE)
+        .map(|header| header.to_str().expect("Unable to stringify cookie header"))
+        .collect();
+
+    // the inheriting cookie takes the middleware default and overrides the path
+    assert!(
+        cookie_headers
+            .contains(&"inheriting=%22id%22; HttpOnly; SameSite=Strict; Secure; Path=/account")
+    );
+    // cookies with their own attributes are left untouched
+    assert!(cookie_headers.contains(&"Type%20A=%22id%22; HttpOnly; SameSite=Lax"));
+
+    // without a configured default, the inheriting cookie builds on `Attributes::default`
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor))
+            .route("/register", web::post().to(register_inheriting_cookies)),
+    )
+    .await;
+
+    let request = test::TestRequest::post().uri("/register").to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_headers: Vec<&str> = response
+        .headers()
+        .get_all(actix_web::http::header::SET_COOKIE)
+        .map(|header| header.to_str().expect("Unable to stringify cookie header"))
+        .collect();
+
+    assert!(cookie_headers.contains(&"inheriting=%22id%22; HttpOnly; SameSite=Lax; Path=/account"));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_same_site_none_compat_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(
+                CookieMiddleware::new(processor)
+                    .default_attributes(
+                        Attributes::default().secure(true).same_site(SameSite::None),
+                    )
+                    .same_site_none_compat(true),
+            )
+            .route("/register", web::post().to(register_inheriting_cookies)),
+    )
+    .await;
+
+    // Chrome 66 drops cookies with `SameSite=None`
+    let request = test::TestRequest::post()
+        .insert_header((
+            actix_web::http::header::USER_AGENT,
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/66.0.3359.117 Safari/537.36",
+        ))
+        .uri("/register")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_headers: Vec<&str> = response
+        .headers()
+        .get_all(actix_web::http::header::SET_COOKIE)
+        .map(|header| header.to_str().expect("Unable to stringify cookie header"))
+        .collect();
+
+    assert_eq!(cookie_headers.len(), 3);
+    let none_position = cookie_headers
+        .iter()
+        .position(|header| {
+            *header == "inheriting=%22id%22; HttpOnly; SameSite=None; Secure; Path=/account"
+        })
+        .expect("the SameSite=None cookie is emitted");
+    // the compatibility cookie follows, so it is the one the client keeps
+    assert_eq!(
+        cookie_headers[none_position + 1],
+        "inheriting=%22id%22; HttpOnly; Secure; Path=/account"
+    );
+    // cookies with another SameSite are left untouched
+    assert!(cookie_headers.contains(&"Type%20A=%22id%22; HttpOnly; SameSite=Lax"));
+
+    // modern clients only receive the SameSite=None cookie
+    let request = test::TestRequest::post()
+        .insert_header((
+            actix_web::http::header::USER_AGENT,
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
+        ))
+        .uri("/register")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(
+        response
+            .headers()
+            .get_all(actix_web::http::header::SET_COOKIE)
+            .count(),
+        2
+    );
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_preserves_handler_set_cookie_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor))
+            .route("/register", web::post().to(register_cookie_and_header)),
+    )
+    .await;
+
+    let request = test::TestRequest::post().uri("/register").to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_headers: Vec<&str> = response
+        .headers()
+        .get_all(actix_web::http::header::SET_COOKIE)
+        .map(|header| header.to_str().expect("Unable to stringify cookie header"))
+        .collect();
+
+    // the header set by the handler comes first, followed by the cookies managed by cookiebox
+    assert_eq!(
+        cookie_headers,
+        vec![
+            "flash=saved; Path=/",
+            "Type%20A=%22id%22; HttpOnly; SameSite=Lax"
+        ]
+    );
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_collection_custom_error_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor))
+            .route("/get", web::post().to(get_app_error_cookie)),
+    )
+    .await;
+
+    let request = test::TestRequest::post()
+        .insert_header((actix_web::http::header::COOKIE, "Type%20A=%22id%22"))
+        .uri("/get")
+        .to_request();
+    let body_str: String = test::call_and_read_body_json(&app, request).await;
+
+    assert_eq!(body_str, "id");
+
+    // without the middleware, the storage is missing and the custom error is returned
+    let app =
+        test::init_service(App::new().route("/get", web::post().to(get_app_error_cookie))).await;
+
+    let request = test::TestRequest::post().uri("/get").to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(
+        response.status(),
+        actix_web::http::StatusCode::SERVICE_UNAVAILABLE
+    );
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_scoped_insert_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor))
+            .route("/insert", web::post().to(insert_cookie_paths)),
+    )
+    .await;
+
+    let request = test::TestRequest::post().uri("/insert").to_request();
+    let response = test::call_service(&app, request).await;
+    let mut cookie_headers: Vec<&str> = response
+        .headers()
+        .get_all(actix_web::http::header::SET_COOKIE)
+        .map(|header| header.to_str().expect("Unable to stringify cookie header"))
+        .collect();
+    cookie_headers.sort_unstable();
+
+    // both scopes survive as separate headers
+    assert_eq!(
+        cookie_headers,
+        vec![
+            "Type%20A=%22admin%22; HttpOnly; SameSite=Lax; Path=/admin",
+            "Type%20A=%22root%22; HttpOnly; SameSite=Lax"
+        ]
+    );
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_response_cookie_error_tests() -> std::io::Result<()> {
+    // Without percent-encoding, the control character reaches the header value
+    let mut config = ProcessorConfig::default();
+    config.percent_encode = false;
+    let processor: Processor = config.into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor))
+            .route("/register", web::post().to(register_raw_cookie)),
+    )
+    .await;
+
+    let request = test::TestRequest::post().uri("/register").to_request();
+    let error = test::try_call_service(&app, request)
+        .await
+        .expect_err("The response should fail");
+
+    assert_eq!(
+        error.as_response_error().status_code(),
+        actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+    );
+    assert!(error.to_string().contains("`Type A`"));
+
+    // The cookie produced by `serialize` is named, its value is redacted
+    let mut config = ProcessorConfig::default();
+    config.percent_encode = false;
+    let processor: Processor = config.into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor))
+            .route("/register", web::post().to(register_broken_cookie)),
+    )
+    .await;
+
+    let request = test::TestRequest::post().uri("/register").to_request();
+    let error = test::try_call_service(&app, request)
+        .await
+        .expect_err("The response should fail");
+
+    assert!(error.to_string().starts_with(
+        "Failed to attach the `broken` cookie with a value of 39 bytes to the outgoing response"
+    ));
+    assert!(!format!("{error:?}").contains("long enough"));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_priority_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor))
+            .route("/register", web::post().to(register_priority_cookie))
+            .route("/remove", web::post().to(remove_priority_cookie)),
+    )
+    .await;
+
+    let request = test::TestRequest::post().uri("/register").to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_header = response
+        .headers()
+        .get(actix_web::http::header::SET_COOKIE)
+        .expect("Cookie header not found")
+        .to_str()
+        .expect("Unable to stringify cookie header");
+
+    assert_eq!(cookie_header, "priority=%22id%22; Path=/; Priority=High");
+
+    // removal cookies are emitted without it
+    let request = test::TestRequest::post().uri("/remove").to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_header = response
+        .headers()
+        .get(actix_web::http::header::SET_COOKIE)
+        .expect("Cookie header not found")
+        .to_str()
+        .expect("Unable to stringify cookie header");
+
+    assert!(cookie_header.starts_with("priority=; Path=/; Expires="));
+    assert!(!cookie_header.contains("Priority"));
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_raw_value_encoding_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .wrap(CookieMiddleware::new(processor))
+            .route("/register", web::post().to(register_raw_encoded_cookie)),
+    )
+    .await;
+
+    let request = test::TestRequest::post().uri("/register").to_request();
+    let response = test::call_service(&app, request).await;
+    let mut cookie_headers: Vec<&str> = response
+        .headers()
+        .get_all(actix_web::http::header::SET_COOKIE)
+        .map(|header| header.to_str().expect("Unable to stringify cookie header"))
+        .collect();
+    cookie_headers.sort_unstable();
+
+    // only the `Raw` cookie skips the percent-encoding
+    assert_eq!(
+        cookie_headers,
+        vec![
+            "Type%20A=%22id%22; HttpOnly; SameSite=Lax",
+            r#"raw="id"; HttpOnly; SameSite=Lax; Path=/"#
+        ]
+    );
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_passthrough_tests() -> std::io::Result<()> {
+    let mut config = ProcessorConfig::default();
+    config.crypto_rules.push(CryptoRule {
+        cookie_names: vec!["_ga".to_string()],
+        algorithm: CryptoAlgorithm::Signing,
+        key: Key::generate(),
+        fallbacks: vec![],
+    });
+    let processor: Processor = config.into();
+    let app = test::init_service(
+        App::new()
+            .wrap(
+                CookieMiddleware::new(processor)
+                    .passthrough_names(["_ga"])
+                    .allow_names(["Type A"])
+                    .on_unknown_cookie(UnknownCookieAction::Reject),
+            )
+            .route(
+                "/analytics",
+                web::post().to(get_and_rewrite_analytics_cookie),
+            ),
+    )
+    .await;
+
+    // Neither verified nor percent-decoded, and kept despite the allowed list
+    let request = test::TestRequest::post()
+        .uri("/analytics")
+        .insert_header((actix_web::http::header::COOKIE, "_ga=GA1.2.%2F.1700000000"))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert!(response.status().is_success());
+    assert!(
+        response
+            .headers()
+            .get(actix_web::http::header::SET_COOKIE)
+            .is_none()
+    );
+
+    let body = test::read_body(response).await;
+    assert_eq!(body, "GA1.2.%2F.1700000000");
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn cookie_middleware_env_tests() -> std::io::Result<()> {
+    let processor: Processor = ProcessorConfig::default().into();
+    let app = test::init_service(
+        App::new()
+            .service(
+                web::scope("/api")
+                    .wrap(CookieMiddleware::new(processor.clone()))
+                    .route("/users/register", web::post().to(register_scoped_cookie)),
+            )
+            .service(
+                web::scope("")
+                    .wrap(CookieMiddleware::new(processor))
+                    .route("/register", web::post().to(register_scoped_cookie)),
+            ),
+    )
+    .await;
+
+    // The port is not part of the domain, and the path is the one of the wrapped scope
+    let request = test::TestRequest::post()
+        .uri("/api/users/register")
+        .insert_header((actix_web::http::header::HOST, "example.com:8080"))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_header = response
+        .headers()
+        .get(actix_web::http::header::SET_COOKIE)
+        .expect("Cookie header not found")
+        .to_str()
+        .expect("Unable to stringify cookie header");
+
+    assert_eq!(
+        cookie_header,
+        "scoped=%22id%22; Path=/api; Domain=example.com"
+    );
+
+    let request = test::TestRequest::post()
+        .uri("/register")
+        .insert_header((actix_web::http::header::HOST, "example.com"))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let cookie_header = response
+        .headers()
+        .get(actix_web::http::header::SET_COOKIE)
+        .expect("Cookie header not found")
+        .to_str()
+        .expect("Unable to stringify cookie header");
+
+    assert_eq!(cookie_header, "scoped=%22id%22; Path=/; Domain=example.com");
+
+    Ok(())
+}
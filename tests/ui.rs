@@ -0,0 +1,5 @@
+#[test]
+fn cookie_macro_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
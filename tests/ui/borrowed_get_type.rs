@@ -0,0 +1,11 @@
+use cookiebox::cookiebox_macros::cookie;
+use cookiebox::cookies::{CookieName, IncomingConfig};
+
+#[cookie(name = "my-cookie")]
+pub struct MyCookie;
+
+impl IncomingConfig for MyCookie {
+    type Get = &'static str;
+}
+
+fn main() {}
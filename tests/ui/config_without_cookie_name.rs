@@ -0,0 +1,9 @@
+use cookiebox::cookies::IncomingConfig;
+
+pub struct MyCookie;
+
+impl IncomingConfig for MyCookie {
+    type Get = String;
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use cookiebox::cookiebox_macros::{FromRequest, cookie};
+use cookiebox::cookies::{Cookie, CookieName};
+
+#[cookie(name = "session")]
+pub struct Session;
+
+#[derive(FromRequest)]
+pub struct CookieCollection<'c>(Cookie<Session>, Cookie<'c, Session>);
+
+fn main() {}
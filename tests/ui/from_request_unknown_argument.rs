@@ -0,0 +1,11 @@
+use cookiebox::cookiebox_macros::{FromRequest, cookie};
+use cookiebox::cookies::{Cookie, CookieName};
+
+#[cookie(name = "session")]
+pub struct Session;
+
+#[derive(FromRequest)]
+#[from_request(err = "actix_web::Error")]
+pub struct CookieCollection<'c>(Cookie<'c, Session>);
+
+fn main() {}
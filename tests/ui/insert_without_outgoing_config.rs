@@ -0,0 +1,17 @@
+use cookiebox::cookiebox_macros::cookie;
+use cookiebox::cookies::{Cookie, CookieName, IncomingConfig, OutgoingConfig};
+use cookiebox::Storage;
+
+#[cookie(name = "my-cookie")]
+pub struct MyCookie;
+
+impl IncomingConfig for MyCookie {
+    type Get = String;
+}
+
+fn insert(storage: &Storage) {
+    let _ = <MyCookie as OutgoingConfig>::attributes();
+    Cookie::<MyCookie>::new(storage).insert("value".to_string());
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use cookiebox::cookiebox_macros::cookie;
+
+#[cookie(name = "my cookie")]
+pub struct MyCookie;
+
+fn main() {}
@@ -0,0 +1,6 @@
+use cookiebox::cookiebox_macros::cookie;
+
+#[cookie(name = "theme", same_site = "relaxed")]
+pub struct Theme;
+
+fn main() {}
@@ -0,0 +1,6 @@
+use cookiebox::cookiebox_macros::cookie;
+
+#[cookie(name = "cart", require_prefix = "acme_")]
+pub struct Cart;
+
+fn main() {}